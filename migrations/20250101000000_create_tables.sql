@@ -0,0 +1,98 @@
+-- The schema is shared by SQLite and Postgres, the api tokens and provider configs are encrypted by the app.
+-- The flags are stored as 0 or 1 in BIGINT columns, as the sqlx Any driver can't decode the SQLite booleans
+
+CREATE TABLE slack_installations (
+    id TEXT PRIMARY KEY,
+    team_id TEXT NOT NULL,
+    team_name TEXT NOT NULL,
+    enterprise_id TEXT NOT NULL,
+    enterprise_name TEXT NOT NULL,
+    is_enterprise_install BIGINT NOT NULL,
+
+    access_token TEXT NOT NULL,
+    token_type TEXT NOT NULL,
+    scope TEXT NOT NULL,
+
+    authed_user_id TEXT NOT NULL,
+    app_id TEXT NOT NULL,
+    bot_user_id TEXT NOT NULL,
+
+    pager_duty_token TEXT,
+    opsgenie_token TEXT,
+    opsgenie_region TEXT,
+
+    created_at TEXT NOT NULL,
+    last_updated_at TEXT NOT NULL
+);
+
+CREATE TABLE scheduled_tasks (
+    team TEXT NOT NULL,
+    task_id TEXT NOT NULL,
+    legacy_task_id TEXT,
+    next_update_timestamp_utc BIGINT NOT NULL,
+    next_update_time TEXT NOT NULL,
+
+    team_id TEXT NOT NULL,
+    team_domain TEXT NOT NULL,
+    channel_id TEXT NOT NULL,
+    channel_name TEXT NOT NULL,
+    enterprise_id TEXT NOT NULL,
+    enterprise_name TEXT NOT NULL,
+    is_enterprise_install BIGINT NOT NULL,
+
+    user_group_id TEXT NOT NULL,
+    user_group_handle TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    provider_config TEXT NOT NULL,
+    cron TEXT NOT NULL,
+    timezone TEXT NOT NULL,
+
+    paused BIGINT NOT NULL,
+    resume_timestamp_utc BIGINT,
+    resume_time TEXT,
+
+    created_by_user_id TEXT NOT NULL,
+    created_by_user_name TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    last_updated_at TEXT NOT NULL,
+
+    PRIMARY KEY (team, task_id)
+);
+
+CREATE INDEX scheduled_tasks_next_update ON scheduled_tasks (next_update_timestamp_utc);
+
+CREATE TABLE rotations (
+    team TEXT NOT NULL,
+    name TEXT NOT NULL,
+    members TEXT NOT NULL,
+    cadence TEXT NOT NULL,
+    timezone TEXT NOT NULL,
+    start_at TEXT NOT NULL,
+    shift_size BIGINT NOT NULL,
+
+    created_by_user_id TEXT NOT NULL,
+    created_by_user_name TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    last_updated_at TEXT NOT NULL,
+
+    PRIMARY KEY (team, name)
+);
+
+CREATE TABLE schedule_overrides (
+    task TEXT NOT NULL,
+    override_id TEXT NOT NULL,
+    team TEXT NOT NULL,
+    task_id TEXT NOT NULL,
+    replacement_user_ids TEXT NOT NULL,
+    replaced_user_ids TEXT NOT NULL,
+    start_timestamp_utc BIGINT NOT NULL,
+    end_timestamp_utc BIGINT NOT NULL,
+    start_time TEXT NOT NULL,
+    end_time TEXT NOT NULL,
+
+    created_by_user_id TEXT NOT NULL,
+    created_by_user_name TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+
+    PRIMARY KEY (task, override_id)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE scheduled_tasks ADD COLUMN missed_runs BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE scheduled_tasks ADD COLUMN last_missed_time TEXT;
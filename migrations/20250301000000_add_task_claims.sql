@@ -0,0 +1,2 @@
+ALTER TABLE scheduled_tasks ADD COLUMN version BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE scheduled_tasks ADD COLUMN claimed_until_timestamp_utc BIGINT;
@@ -0,0 +1,13 @@
+ALTER TABLE scheduled_tasks ADD COLUMN missing_user_policy TEXT NOT NULL DEFAULT 'skip';
+
+CREATE TABLE email_aliases (
+    team TEXT NOT NULL,
+    email TEXT NOT NULL,
+    slack_user_id TEXT NOT NULL,
+
+    created_by_user_id TEXT NOT NULL,
+    created_by_user_name TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+
+    PRIMARY KEY (team, email)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE scheduled_tasks ADD COLUMN min_group_size BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE scheduled_tasks ADD COLUMN max_group_size BIGINT;
+ALTER TABLE scheduled_tasks ADD COLUMN max_churn BIGINT;
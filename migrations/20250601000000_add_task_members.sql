@@ -0,0 +1,3 @@
+ALTER TABLE scheduled_tasks ADD COLUMN member_ids TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE scheduled_tasks ADD COLUMN previous_member_ids TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE scheduled_tasks ADD COLUMN members_changed_time TEXT;
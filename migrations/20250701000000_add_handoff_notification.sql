@@ -0,0 +1,2 @@
+ALTER TABLE scheduled_tasks ADD COLUMN notification TEXT NOT NULL DEFAULT '{}';
+ALTER TABLE scheduled_tasks ADD COLUMN notification_thread_ts TEXT;
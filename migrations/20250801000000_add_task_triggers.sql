@@ -0,0 +1 @@
+ALTER TABLE scheduled_tasks ADD COLUMN next_trigger TEXT NOT NULL DEFAULT 'update';
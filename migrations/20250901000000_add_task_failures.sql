@@ -0,0 +1,3 @@
+ALTER TABLE scheduled_tasks ADD COLUMN failed_runs BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE scheduled_tasks ADD COLUMN last_failed_time TEXT;
+ALTER TABLE scheduled_tasks ADD COLUMN last_error TEXT;
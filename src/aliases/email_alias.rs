@@ -0,0 +1,20 @@
+/**
+ * Map the email of a user in the schedule provider to a Slack user, e.g. when the PagerDuty email isn't the Slack email
+ */
+#[derive(Debug, Clone)]
+pub struct EmailAlias {
+    pub team: String, // Partition Key
+    pub email: String, // Sort Key, in lower case
+
+    pub slack_user_id: String,
+
+    pub created_by_user_id: String,
+    pub created_by_user_name: String,
+    pub created_at: String,
+}
+
+impl EmailAlias {
+    pub fn normalize_email(email: &str) -> String {
+        email.trim().to_lowercase()
+    }
+}
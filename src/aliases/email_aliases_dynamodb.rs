@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use aws_config::SdkConfig;
+use aws_sdk_dynamodb::{Client, types::AttributeValue};
+
+use crate::errors::AppError;
+use crate::db::dynamodb_client::get_attribute;
+
+use super::email_alias::EmailAlias;
+use super::email_aliases_repository::EmailAliasesRepository;
+
+pub struct EmailAliasesDynamodb {
+    client: Client,
+    table_name: String,
+}
+
+impl EmailAliasesDynamodb {
+    pub fn new(config: &SdkConfig, table_name: String) -> EmailAliasesDynamodb {
+        EmailAliasesDynamodb{ client: Client::new(config), table_name }
+    }
+
+    fn to_alias(&self, item: &HashMap<String, AttributeValue>) -> EmailAlias {
+        EmailAlias {
+            team: get_attribute(item, "team"),
+            email: get_attribute(item, "email"),
+            slack_user_id: get_attribute(item, "slack_user_id"),
+
+            created_by_user_id: get_attribute(item, "created_by_user_id"),
+            created_by_user_name: get_attribute(item, "created_by_user_name"),
+            created_at: get_attribute(item, "created_at"),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailAliasesRepository for EmailAliasesDynamodb {
+    async fn save_alias(&self, alias: &EmailAlias) -> Result<(), AppError> {
+        let a = alias.clone();
+
+        let builder = self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("team", AttributeValue::S(a.team))
+            .item("email", AttributeValue::S(a.email))
+            .item("slack_user_id", AttributeValue::S(a.slack_user_id))
+
+            .item("created_by_user_id", AttributeValue::S(a.created_by_user_id))
+            .item("created_by_user_name", AttributeValue::S(a.created_by_user_name))
+            .item("created_at", AttributeValue::S(a.created_at))
+        ;
+
+        println!("Saving alias of {} to {}", alias.email, alias.slack_user_id);
+        builder.send().await?;
+
+        Ok(())
+    }
+
+    async fn list_aliases(&self, team: &str) -> Result<Vec<EmailAlias>, AppError> {
+        let items: Result<Vec<_>, _> = self.client
+            .query()
+            .table_name(&self.table_name)
+            .key_condition_expression("team = :team")
+            .expression_attribute_values(":team", AttributeValue::S(team.to_string()))
+            .into_paginator()
+            .items()
+            .send()
+            .collect()
+            .await;
+
+        Ok(items?.iter().map(|item| self.to_alias(item)).collect())
+    }
+
+    async fn delete_alias(&self, team: &str, email: &str) -> Result<(), AppError> {
+        let request = self.client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("team", AttributeValue::S(team.to_string()))
+            .key("email", AttributeValue::S(email.to_string()));
+
+        println!("Deleting alias of {}", email);
+        request.send().await?;
+
+        Ok(())
+    }
+}
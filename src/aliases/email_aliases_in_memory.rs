@@ -0,0 +1,42 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use async_trait::async_trait;
+
+use crate::errors::AppError;
+
+use super::email_alias::EmailAlias;
+use super::email_aliases_repository::EmailAliasesRepository;
+
+/**
+ * Keep the email aliases in memory, for running and testing without AWS
+ */
+#[derive(Default)]
+pub struct EmailAliasesInMemory {
+    // Keyed by the team and the email
+    aliases: Mutex<BTreeMap<(String, String), EmailAlias>>,
+}
+
+impl EmailAliasesInMemory {
+    pub fn new() -> EmailAliasesInMemory {
+        EmailAliasesInMemory::default()
+    }
+}
+
+#[async_trait]
+impl EmailAliasesRepository for EmailAliasesInMemory {
+    async fn save_alias(&self, alias: &EmailAlias) -> Result<(), AppError> {
+        self.aliases.lock().unwrap().insert((alias.team.clone(), alias.email.clone()), alias.clone());
+
+        Ok(())
+    }
+
+    async fn list_aliases(&self, team: &str) -> Result<Vec<EmailAlias>, AppError> {
+        Ok(self.aliases.lock().unwrap().values().filter(|a| a.team == team).cloned().collect())
+    }
+
+    async fn delete_alias(&self, team: &str, email: &str) -> Result<(), AppError> {
+        self.aliases.lock().unwrap().remove(&(team.to_string(), email.to_string()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+use async_trait::async_trait;
+
+use crate::errors::AppError;
+
+use super::email_alias::EmailAlias;
+
+/**
+ * The storage of the email aliases, which are unique by email in a team
+ */
+#[async_trait]
+pub trait EmailAliasesRepository: Send + Sync {
+    async fn save_alias(&self, alias: &EmailAlias) -> Result<(), AppError>;
+
+    async fn list_aliases(&self, team: &str) -> Result<Vec<EmailAlias>, AppError>;
+
+    async fn delete_alias(&self, team: &str, email: &str) -> Result<(), AppError>;
+}
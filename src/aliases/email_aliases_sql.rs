@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use sqlx::{any::AnyRow, AnyPool, Row};
+
+use crate::errors::AppError;
+
+use super::email_alias::EmailAlias;
+use super::email_aliases_repository::EmailAliasesRepository;
+
+pub struct EmailAliasesSql {
+    pool: AnyPool,
+}
+
+impl EmailAliasesSql {
+    pub fn new(pool: AnyPool) -> EmailAliasesSql {
+        EmailAliasesSql { pool }
+    }
+
+    fn to_alias(&self, row: &AnyRow) -> EmailAlias {
+        EmailAlias {
+            team: row.get("team"),
+            email: row.get("email"),
+            slack_user_id: row.get("slack_user_id"),
+
+            created_by_user_id: row.get("created_by_user_id"),
+            created_by_user_name: row.get("created_by_user_name"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailAliasesRepository for EmailAliasesSql {
+    async fn save_alias(&self, alias: &EmailAlias) -> Result<(), AppError> {
+        let a = alias.clone();
+
+        println!("Saving alias of {} to {}", alias.email, alias.slack_user_id);
+        sqlx::query(r#"
+            INSERT INTO email_aliases (team, email, slack_user_id, created_by_user_id, created_by_user_name, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (team, email) DO UPDATE SET
+                slack_user_id = excluded.slack_user_id,
+                created_by_user_id = excluded.created_by_user_id,
+                created_by_user_name = excluded.created_by_user_name,
+                created_at = excluded.created_at
+        "#)
+            .bind(a.team)
+            .bind(a.email)
+            .bind(a.slack_user_id)
+            .bind(a.created_by_user_id)
+            .bind(a.created_by_user_name)
+            .bind(a.created_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_aliases(&self, team: &str) -> Result<Vec<EmailAlias>, AppError> {
+        let rows = sqlx::query("SELECT * FROM email_aliases WHERE team = $1 ORDER BY email")
+            .bind(team.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(|row| self.to_alias(row)).collect())
+    }
+
+    async fn delete_alias(&self, team: &str, email: &str) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM email_aliases WHERE team = $1 AND email = $2")
+            .bind(team.to_string())
+            .bind(email.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
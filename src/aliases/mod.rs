@@ -0,0 +1,11 @@
+mod email_alias;
+mod email_aliases_dynamodb;
+mod email_aliases_in_memory;
+mod email_aliases_repository;
+mod email_aliases_sql;
+
+pub use email_alias::EmailAlias;
+pub use email_aliases_dynamodb::EmailAliasesDynamodb;
+pub use email_aliases_in_memory::EmailAliasesInMemory;
+pub use email_aliases_repository::EmailAliasesRepository;
+pub use email_aliases_sql::EmailAliasesSql;
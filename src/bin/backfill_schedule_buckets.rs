@@ -0,0 +1,16 @@
+#![allow(clippy::result_large_err)]
+
+use std::env;
+
+use on_call_support::errors::AppError;
+use on_call_support::migrations::backfill_schedule_buckets;
+
+#[tokio::main]
+async fn main() -> Result<(), AppError> {
+    let env = env::args().nth(1).unwrap_or("dev".to_string());
+
+    let updated = backfill_schedule_buckets(&env).await?;
+    println!("Saved the schedule bucket of {} tasks in {}", updated, env);
+
+    Ok(())
+}
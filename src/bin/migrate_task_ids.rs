@@ -0,0 +1,16 @@
+#![allow(clippy::result_large_err)]
+
+use std::env;
+
+use on_call_support::errors::AppError;
+use on_call_support::migrations::migrate_task_ids;
+
+#[tokio::main]
+async fn main() -> Result<(), AppError> {
+    let env = env::args().nth(1).unwrap_or("dev".to_string());
+
+    let migrated = migrate_task_ids(&env).await?;
+    println!("Migrated {} tasks in {}", migrated, env);
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+#![allow(clippy::result_large_err)]
+
+use std::{env, net::SocketAddr};
+
+use axum::{body::Bytes, extract::State, http::{HeaderMap, StatusCode, Uri}, response::{IntoResponse, Response}, Router};
+
+use on_call_support::{config::{Config, SchedulerBackend}, errors::AppError, http_router::{route_request, HttpRequest}, secrets::load_secrets, user_group_updater::run_in_process_scheduler};
+
+/**
+ * Serve the Slack requests over plain HTTP, e.g. on Kubernetes, instead of behind API Gateway.
+ * Usage: on_call_support_server [env], listening on the port in the PORT environment variable, defaults to 3000.
+ * With SCHEDULER=in-process, the user groups are updated by a timer in the server instead of EventBridge.
+ */
+#[tokio::main]
+async fn main() -> Result<(), AppError> {
+    let app_env = env::args().nth(1).unwrap_or("dev".to_string());
+    let port: u16 = env::var("PORT").ok().and_then(|port| port.parse().ok()).unwrap_or(3000);
+
+    let config = Config::new(&app_env)?;
+    if config.scheduler_backend == SchedulerBackend::InProcess {
+        let secrets = load_secrets(&config.load_aws_config().await, &config.secrets_source).await?;
+        tokio::spawn(async move {
+            if let Err(err) = run_in_process_scheduler(config, secrets).await {
+                println!("In-process scheduler stopped: {:?}", err);
+            }
+        });
+    }
+
+    let app = Router::new().fallback(handle_request).with_state(app_env);
+    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port))).await
+        .map_err(|err| AppError::UnexpectedError(format!("Couldn't listen on port {}: {}", port, err)))?;
+
+    println!("Listening on port {}", port);
+    axum::serve(listener, app).await
+        .map_err(|err| AppError::UnexpectedError(format!("Server stopped: {}", err)))
+}
+
+async fn handle_request(State(app_env): State<String>, uri: Uri, headers: HeaderMap, body: Bytes) -> Response {
+    let request = HttpRequest {
+        path: uri.path().to_string(),
+        headers,
+        query: HttpRequest::parse_query(uri.query().unwrap_or_default()),
+        body: Some(String::from_utf8_lossy(&body).to_string()),
+    };
+
+    match route_request(&app_env, request).await {
+        Ok(response) => {
+            let status_code = StatusCode::from_u16(response.status_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status_code, response.headers, response.body).into_response()
+        },
+        Err(err) => {
+            println!("Failed to process request {}, error: {:?}", uri.path(), err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
+        },
+    }
+}
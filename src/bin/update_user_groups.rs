@@ -1,3 +1,5 @@
+#![allow(clippy::result_large_err)]
+
 use std::collections::HashMap;
 use std::env;
 
@@ -5,7 +7,6 @@ use aws_config::BehaviorVersion;
 use on_call_support::errors::AppError;
 use on_call_support::user_group_updater::update_user_groups;
 use aws_sdk_cloudformation::Client as CloudformationClient;
-use tokio;
 
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
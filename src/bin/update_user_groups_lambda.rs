@@ -1,5 +1,4 @@
 use on_call_support::user_group_updater::update_user_groups;
-use tokio;
 
 use lambda_runtime::{service_fn, LambdaEvent, Error};
 use serde_json::{json, Value};
@@ -1,21 +1,157 @@
+use std::env;
+
+use aws_config::{BehaviorVersion, SdkConfig};
+
+use crate::errors::AppError;
+
+const DEFAULT_MAX_CONCURRENT_TASKS: usize = 4;
+const DEFAULT_SECRET_NAME: &str = "on-call-support/secrets";
+
+/**
+ * Where the installations, tasks, rotations, overrides and email aliases are stored
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageBackend {
+    DynamoDB,
+
+    // SQLite or Postgres, for self-hosting outside AWS
+    Sql { database_url: String },
+
+    // Nothing is persisted, for local testing only
+    InMemory,
+}
+
+impl StorageBackend {
+    /**
+     * Chosen by STORAGE_BACKEND (dynamodb, sql or memory) and DATABASE_URL, defaults to DynamoDB
+     */
+    pub fn from_env() -> Result<StorageBackend, AppError> {
+        match env::var("STORAGE_BACKEND").unwrap_or_default().to_ascii_lowercase().as_str() {
+            "sql" => Ok(StorageBackend::Sql {
+                database_url: required_env_var("DATABASE_URL", "the sql storage backend")?,
+            }),
+            "memory" => Ok(StorageBackend::InMemory),
+            _ => Ok(StorageBackend::DynamoDB),
+        }
+    }
+}
+
+/**
+ * What triggers the user group updater at the next scheduled time
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchedulerBackend {
+    // One-off EventBridge schedules invoking the updater Lambda
+    EventBridge,
+
+    // A timer in the standalone server
+    InProcess,
+}
+
+impl SchedulerBackend {
+    /**
+     * Chosen by SCHEDULER (eventbridge or in-process), defaults to EventBridge
+     */
+    pub fn from_env() -> SchedulerBackend {
+        match env::var("SCHEDULER").unwrap_or_default().to_ascii_lowercase().as_str() {
+            "in-process" => SchedulerBackend::InProcess,
+            _ => SchedulerBackend::EventBridge,
+        }
+    }
+}
+
+/**
+ * Where the encryption key and the Slack app credentials are read from
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretsSource {
+    // A JSON secret in AWS Secrets Manager, for the Lambda deployment
+    SecretsManager { secret_name: String },
+
+    // ENCRYPTION_KEY, SLACK_CLIENT_ID, SLACK_CLIENT_SECRET, SLACK_SIGNING_SECRET and PREVIOUS_SLACK_SIGNING_SECRETS
+    Env,
+
+    // A JSON file in the same format as the secret in Secrets Manager, e.g. a mounted Kubernetes secret
+    File { path: String },
+}
+
+impl SecretsSource {
+    /**
+     * Chosen by SECRETS_SOURCE (secrets-manager, env or file) and SECRETS_FILE.
+     * Defaults to Secrets Manager with DynamoDB, and to the environment variables with the other storage backends.
+     */
+    pub fn from_env(storage_backend: &StorageBackend) -> Result<SecretsSource, AppError> {
+        match env::var("SECRETS_SOURCE").unwrap_or_default().to_ascii_lowercase().as_str() {
+            "secrets-manager" => Ok(SecretsSource::SecretsManager { secret_name: DEFAULT_SECRET_NAME.to_string() }),
+            "env" => Ok(SecretsSource::Env),
+            "file" => Ok(SecretsSource::File {
+                path: required_env_var("SECRETS_FILE", "the file secrets source")?,
+            }),
+            _ if *storage_backend == StorageBackend::DynamoDB => Ok(SecretsSource::SecretsManager { secret_name: DEFAULT_SECRET_NAME.to_string() }),
+            _ => Ok(SecretsSource::Env),
+        }
+    }
+}
+
+fn required_env_var(name: &str, required_by: &str) -> Result<String, AppError> {
+    env::var(name).map_err(|_| AppError::ConfigError(format!("{} is required for {}", name, required_by)))
+}
+
 pub struct Config {
-    pub secret_name: String,
+    pub secrets_source: SecretsSource,
 
+    pub storage_backend: StorageBackend,
     pub schedules_table_name: String,
     pub installations_table_name: String,
+    pub rotations_table_name: String,
+    pub overrides_table_name: String,
+    pub aliases_table_name: String,
     
+    pub scheduler_backend: SchedulerBackend,
     pub schedule_name_prefix: String,
+
+    // How many tasks the updater runs at the same time, from MAX_CONCURRENT_TASKS
+    pub max_concurrent_tasks: usize,
 }
 
 impl Config {
-    pub fn new(env: &str) -> Config {
-        Config {
-            secret_name: "on-call-support/secrets".to_string(),
-            
+    /**
+     * Read the backends from the environment variables, a missing variable required by the chosen backend is an error
+     */
+    pub fn new(env: &str) -> Result<Config, AppError> {
+        let storage_backend = StorageBackend::from_env()?;
+
+        Ok(Config {
+            secrets_source: SecretsSource::from_env(&storage_backend)?,
+
+            storage_backend,
             schedules_table_name: format!("on-call-support-schedules-{}", env),
             installations_table_name: format!("on-call-support-installations-{}", env),
+            rotations_table_name: format!("on-call-support-rotations-{}", env),
+            overrides_table_name: format!("on-call-support-overrides-{}", env),
+            aliases_table_name: format!("on-call-support-aliases-{}", env),
 
+            scheduler_backend: SchedulerBackend::from_env(),
             schedule_name_prefix: "on-call-support-dev_UpdateUserGroupSchedule_".to_string(),
+
+            max_concurrent_tasks: env::var("MAX_CONCURRENT_TASKS").ok().and_then(|max| max.parse().ok()).unwrap_or(DEFAULT_MAX_CONCURRENT_TASKS),
+        })
+    }
+
+    pub fn uses_aws(&self) -> bool {
+        matches!(self.secrets_source, SecretsSource::SecretsManager { .. })
+            || self.storage_backend == StorageBackend::DynamoDB
+            || self.scheduler_backend == SchedulerBackend::EventBridge
+    }
+
+    /**
+     * Load the AWS credentials and region only when a backend on AWS is used, so a self-hosted server never looks them up
+     */
+    pub async fn load_aws_config(&self) -> SdkConfig {
+        if self.uses_aws() {
+            ::aws_config::load_defaults(BehaviorVersion::latest()).await
+        } else {
+            SdkConfig::builder().build()
         }
     }
 }
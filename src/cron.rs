@@ -16,16 +16,25 @@ fn one_off_cron(at: &DateTime<Tz>) -> String {
     format!("{} {} {} {} * {}", at.minute(), at.hour(), at.day(), at.month(), at.year())
 }
 
-/**
-  * Return the next schedule by a given cron expression and from time 
- */
-pub fn get_next_schedule_from(cron_expression: &str, from: &DateTime<Tz>) -> Option<CronSchedule> {
+// The cron crate requires the seconds field, which is optional in the AWS cron format
+fn with_seconds(cron_expression: &str) -> String {
     let cron_parts: Vec<_> = cron_expression.split(" ").collect();
-    let expression = if cron_parts.len() == 6 {
+    if cron_parts.len() == 6 {
         format!("0 {}", cron_expression)
     } else {
         cron_expression.to_string()
-    };
+    }
+}
+
+pub fn is_valid_cron(cron_expression: &str) -> bool {
+    Schedule::from_str(&with_seconds(cron_expression)).is_ok()
+}
+
+/**
+  * Return the next schedule by a given cron expression and from time 
+ */
+pub fn get_next_schedule_from(cron_expression: &str, from: &DateTime<Tz>) -> Option<CronSchedule> {
+    let expression = with_seconds(cron_expression);
 
     let expression_parts: Vec<_> = expression.split(" ").collect();
     let expression_without_seconds = expression_parts[1..].join(" ");
@@ -45,11 +54,35 @@ pub fn get_next_schedule_from(cron_expression: &str, from: &DateTime<Tz>) -> Opt
     None
 }
 
+/**
+  * A schedule which only runs once at the given time
+ */
+pub fn one_off_schedule(at: &DateTime<Tz>) -> CronSchedule {
+    let cron = one_off_cron(at);
+
+    CronSchedule {
+        cron: cron.clone(),
+        timezone: at.timezone(),
+        next_oneoff_cron: cron,
+        next_timestamp_utc: at.timestamp(),
+        next_datetime: *at,
+    }
+}
+
+/**
+  * Count the scheduled times of a cron expression in the range of (from, until]
+ */
+pub fn count_schedules_between(cron_expression: &str, from: &DateTime<Tz>, until: &DateTime<Tz>) -> usize {
+    let schedule = Schedule::from_str(&with_seconds(cron_expression)).unwrap();
+
+    schedule.after(from).take_while(|next| next <= until).count()
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
     use chrono_tz::Tz;
-    use crate::cron::get_next_schedule_from;
+    use crate::cron::{count_schedules_between, get_next_schedule_from, is_valid_cron};
     use chrono::prelude::*;
 
     #[test]
@@ -94,4 +127,16 @@ mod tests {
             assert_eq!(false, true, "should get next schedule")
         }
     }
+
+    #[test]
+    fn test_count_schedules_between() {
+        let melbourne_tz = Tz::from_str("Australia/Melbourne").unwrap();
+        let from = melbourne_tz.with_ymd_and_hms(2023, 1, 2, 9, 0, 0).unwrap(); // Monday
+        let until = melbourne_tz.with_ymd_and_hms(2023, 1, 16, 9, 0, 0).unwrap();
+
+        assert_eq!(count_schedules_between("0 9 ? * MON-FRI *", &from, &until), 10);
+        assert_eq!(count_schedules_between("0 9 ? * MON-FRI *", &from, &from), 0);
+        assert!(is_valid_cron("0 9 ? * MON-FRI *"));
+        assert!(!is_valid_cron("weekly"));
+    }
 }
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use aws_sdk_dynamodb::types::AttributeValue;
 
+use crate::errors::AppError;
+
 pub fn get_attribute(item: &HashMap<String, AttributeValue>, name: &str) -> String {
     item
         .get(name)
@@ -11,10 +13,14 @@ pub fn get_attribute(item: &HashMap<String, AttributeValue>, name: &str) -> Stri
                 attr.as_s().ok()
             }
         )
-        .expect(format!("field {} is null", name).as_str())
+        .unwrap_or_else(|| panic!("field {} is null", name))
         .clone()
 }
 
+pub fn get_required_attribute(item: &HashMap<String, AttributeValue>, name: &str) -> Result<String, AppError> {
+    get_optional_attribute(item, name).ok_or_else(|| AppError::StorageError(format!("field {} is null", name)))
+}
+
 pub fn get_optional_attribute(item: &HashMap<String, AttributeValue>, name: &str) -> Option<String> {
     item
         .get(name)
@@ -25,5 +31,13 @@ pub fn get_optional_attribute(item: &HashMap<String, AttributeValue>, name: &str
                 attr.as_s().ok()
             }
         )
-        .map(|s| s.clone())
+        .cloned()
+}
+
+pub fn get_list_attribute(item: &HashMap<String, AttributeValue>, name: &str) -> Vec<String> {
+    item
+        .get(name)
+        .and_then(|attr| attr.as_l().ok())
+        .map(|values| values.iter().filter_map(|value| value.as_s().ok()).cloned().collect())
+        .unwrap_or_default()
 }
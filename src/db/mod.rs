@@ -1,6 +1,15 @@
 pub mod dynamodb_client;
+mod repositories;
 mod slack_installation;
 mod slack_installation_dynamodb;
+mod slack_installations_in_memory;
+mod slack_installations_repository;
+mod slack_installations_sql;
+pub mod sql_client;
 
+pub use repositories::Repositories;
 pub use slack_installation::SlackInstallation;
 pub use slack_installation_dynamodb::SlackInstallationsDynamoDb;
+pub use slack_installations_in_memory::SlackInstallationsInMemory;
+pub use slack_installations_repository::SlackInstallationsRepository;
+pub use slack_installations_sql::SlackInstallationsSql;
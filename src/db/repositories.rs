@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use aws_config::SdkConfig;
+use tokio::sync::OnceCell;
+
+use crate::{config::{Config, StorageBackend}, encryptor::Encryptor, errors::AppError};
+use crate::aliases::{EmailAliasesDynamodb, EmailAliasesInMemory, EmailAliasesRepository, EmailAliasesSql};
+use crate::overrides::{OverridesDynamodb, OverridesInMemory, OverridesRepository, OverridesSql};
+use crate::rotations::{RotationsDynamodb, RotationsInMemory, RotationsRepository, RotationsSql};
+use crate::scheduled_tasks::{ScheduledTasksDynamodb, ScheduledTasksInMemory, ScheduledTasksRepository, ScheduledTasksSql};
+
+use super::{sql_client, SlackInstallationsDynamoDb, SlackInstallationsInMemory, SlackInstallationsRepository, SlackInstallationsSql};
+
+// The SQL pool and the in-memory storage are shared by the requests and the scheduler in the standalone server
+static SHARED_REPOSITORIES: OnceCell<Repositories> = OnceCell::const_new();
+
+/**
+ * The storage used by the Slack handler and the user group updater, either DynamoDB, SQL or in memory
+ */
+#[derive(Clone)]
+pub struct Repositories {
+    pub installations: Arc<dyn SlackInstallationsRepository>,
+    pub scheduled_tasks: Arc<dyn ScheduledTasksRepository>,
+    pub rotations: Arc<dyn RotationsRepository>,
+    pub overrides: Arc<dyn OverridesRepository>,
+    pub aliases: Arc<dyn EmailAliasesRepository>,
+}
+
+impl Repositories {
+    pub async fn from_config(aws_config: &SdkConfig, config: &Config, encryptor: Encryptor) -> Result<Repositories, AppError> {
+        match &config.storage_backend {
+            StorageBackend::DynamoDB => Ok(Repositories::dynamodb(aws_config, config, encryptor)),
+            StorageBackend::Sql { database_url } => SHARED_REPOSITORIES.get_or_try_init(|| Repositories::sql(database_url, encryptor)).await.cloned(),
+            StorageBackend::InMemory => Ok(SHARED_REPOSITORIES.get_or_init(|| async { Repositories::in_memory() }).await.clone()),
+        }
+    }
+
+    pub fn dynamodb(aws_config: &SdkConfig, config: &Config, encryptor: Encryptor) -> Repositories {
+        Repositories {
+            installations: Arc::new(SlackInstallationsDynamoDb::new(aws_config, config.installations_table_name.clone(), encryptor.clone())),
+            scheduled_tasks: Arc::new(ScheduledTasksDynamodb::new(aws_config, config.schedules_table_name.clone(), encryptor)),
+            rotations: Arc::new(RotationsDynamodb::new(aws_config, config.rotations_table_name.clone())),
+            overrides: Arc::new(OverridesDynamodb::new(aws_config, config.overrides_table_name.clone())),
+            aliases: Arc::new(EmailAliasesDynamodb::new(aws_config, config.aliases_table_name.clone())),
+        }
+    }
+
+    pub async fn sql(database_url: &str, encryptor: Encryptor) -> Result<Repositories, AppError> {
+        let pool = sql_client::connect(database_url).await?;
+
+        Ok(Repositories {
+            installations: Arc::new(SlackInstallationsSql::new(pool.clone(), encryptor.clone())),
+            scheduled_tasks: Arc::new(ScheduledTasksSql::new(pool.clone(), encryptor)),
+            rotations: Arc::new(RotationsSql::new(pool.clone())),
+            overrides: Arc::new(OverridesSql::new(pool.clone())),
+            aliases: Arc::new(EmailAliasesSql::new(pool)),
+        })
+    }
+
+    pub fn in_memory() -> Repositories {
+        Repositories {
+            installations: Arc::new(SlackInstallationsInMemory::new()),
+            scheduled_tasks: Arc::new(ScheduledTasksInMemory::new()),
+            rotations: Arc::new(RotationsInMemory::new()),
+            overrides: Arc::new(OverridesInMemory::new()),
+            aliases: Arc::new(EmailAliasesInMemory::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::aliases::EmailAlias;
+    use crate::db::{SlackInstallation, SlackInstallationsRepository, SlackInstallationsSql};
+    use crate::encryptor::Encryptor;
+    use crate::errors::AppError;
+    use crate::rotations::{Rotation, RotationCadence};
+    use crate::scheduled_tasks::{MissingUserPolicy, ScheduledTask, ScheduledTasksRepository, ScheduledTasksSql, TaskTrigger, TASK_CLAIM_SECONDS};
+    use crate::service_provider::schedule_provider::ScheduleProviderConfig;
+
+    use super::{sql_client, Repositories};
+
+    fn task(next_update_timestamp_utc: i64, paused: bool) -> ScheduledTask {
+        ScheduledTask {
+            task_id: "".to_string(),
+            next_update_timestamp_utc,
+            next_update_time: Utc.timestamp_opt(next_update_timestamp_utc, 0).unwrap().to_rfc3339(),
+            provider_config: ScheduleProviderConfig::PagerDuty { schedule_id: "P123".to_string(), api_token: Some("pagerduty-key".to_string()) },
+            paused,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn store_in_sqlite() {
+        let repositories = Repositories::sql("sqlite::memory:", Encryptor::new("plain text key which should be s")).await.unwrap();
+
+        let installation = SlackInstallation::default();
+        repositories.installations.save_slack_installation(&installation).await.unwrap();
+        repositories.installations.update_pagerduty_token("T123".to_string(), "E123".to_string(), "pagerduty-token").await.unwrap();
+        repositories.installations.save_slack_installation(&installation).await.unwrap();
+
+        let saved = repositories.installations.get_slack_installation("T123", "E123").await.unwrap().unwrap();
+        assert_eq!(saved.access_token, "xoxb-token");
+        assert_eq!(saved.pager_duty_token, Some("pagerduty-token".to_string()));
+        assert!(repositories.installations.update_pagerduty_token("T456".to_string(), "E123".to_string(), "token").await.is_err());
+
+        let mut due_task = task(100, false);
+        due_task.missing_user_policy = MissingUserPolicy::Alias;
+        due_task.guard_rails.max_churn = Some(2);
+        due_task.notification.template = Some("{incoming} took over".to_string());
+        let mut paused_task = task(200, true);
+        let mut next_task = task(300, false);
+        for t in [&mut due_task, &mut paused_task, &mut next_task] {
+            repositories.scheduled_tasks.create_scheduled_task(t).await.unwrap();
+        }
+
+        let due_tasks = repositories.scheduled_tasks.list_due_scheduled_tasks(&Utc.timestamp_opt(150, 0).unwrap()).await.unwrap();
+        assert_eq!(due_tasks.iter().map(|t| t.task_id.clone()).collect::<Vec<String>>(), vec![due_task.task_id.clone()]);
+        assert_eq!(due_tasks[0].provider_config, due_task.provider_config);
+        assert_eq!(due_tasks[0].missing_user_policy, MissingUserPolicy::Alias);
+        assert_eq!(due_tasks[0].guard_rails, due_task.guard_rails);
+        assert_eq!(due_tasks[0].notification, due_task.notification);
+
+        let next = repositories.scheduled_tasks.get_next_scheduled_task(&Utc.timestamp_opt(150, 0).unwrap()).await.unwrap().unwrap();
+        assert_eq!(next.task_id, next_task.task_id);
+
+        // Saving the whole task is refused when it's changed since it's read
+        repositories.scheduled_tasks.save_scheduled_task(&ScheduledTask { cron: "0 10 ? * MON-FRI *".to_string(), ..next.clone() }).await.unwrap();
+        assert!(matches!(repositories.scheduled_tasks.save_scheduled_task(&next).await, Err(AppError::ConcurrentUpdateError(_))));
+
+        // Only one updater claims the task, until the claim expires
+        let mut other_updater_task = due_task.clone();
+        assert!(repositories.scheduled_tasks.claim_scheduled_task(&mut due_task, &Utc.timestamp_opt(150, 0).unwrap()).await.unwrap());
+        assert!(!repositories.scheduled_tasks.claim_scheduled_task(&mut other_updater_task, &Utc.timestamp_opt(150, 0).unwrap()).await.unwrap());
+        assert!(!repositories.scheduled_tasks.claim_scheduled_task(&mut due_task.clone(), &Utc.timestamp_opt(160, 0).unwrap()).await.unwrap());
+        assert!(repositories.scheduled_tasks.claim_scheduled_task(&mut due_task, &Utc.timestamp_opt(150 + TASK_CLAIM_SECONDS, 0).unwrap()).await.unwrap());
+
+        // Pausing or resuming is refused when the task is changed since it's read, e.g. claimed by an updater
+        let mut paused_while_claimed = ScheduledTask { paused: true, ..other_updater_task.clone() };
+        assert!(matches!(repositories.scheduled_tasks.update_paused(&mut paused_while_claimed).await, Err(AppError::ConcurrentUpdateError(_))));
+        paused_task.paused = false;
+        repositories.scheduled_tasks.update_paused(&mut paused_task).await.unwrap();
+        assert_eq!(paused_task.version, 1);
+
+        // The reminder an hour before the update at 9am is triggered first
+        due_task.notification.remind_before_hours = Some(1);
+        due_task.set_next_schedule_from(&Utc.timestamp_opt(150, 0).unwrap());
+        due_task.record_members(&BTreeSet::from(["U1".to_string()]), &BTreeSet::from(["U2".to_string()]), &Utc.timestamp_opt(150, 0).unwrap());
+        repositories.scheduled_tasks.update_next_schedule(&due_task).await.unwrap();
+        assert!(repositories.scheduled_tasks.update_next_schedule(&due_task).await.is_err());
+
+        let updated_task = repositories.scheduled_tasks.list_scheduled_tasks_in_workspace("T123", "E123").await.unwrap()
+            .into_iter()
+            .find(|t| t.task_id == due_task.task_id)
+            .unwrap();
+        assert_eq!((updated_task.previous_member_ids, updated_task.member_ids), (vec!["U1".to_string()], vec!["U2".to_string()]));
+        assert_eq!(updated_task.members_changed_time, due_task.members_changed_time);
+        assert_eq!((updated_task.next_trigger, updated_task.next_update_time), (TaskTrigger::Reminder, "1970-01-01T08:00:00+00:00".to_string()));
+
+        repositories.scheduled_tasks.delete_scheduled_task("T123", "E123", &due_task.task_id).await.unwrap();
+        assert!(matches!(repositories.scheduled_tasks.update_paused(&mut due_task).await, Err(AppError::ConcurrentUpdateError(_))));
+        assert_eq!(repositories.scheduled_tasks.list_scheduled_tasks_in_workspace("T123", "E123").await.unwrap().len(), 2);
+
+        let rotation = Rotation {
+            team: "T123:E123".to_string(),
+            name: "support".to_string(),
+            members: vec!["U1".to_string(), "U2".to_string()],
+            cadence: RotationCadence::Weekly,
+            timezone: "UTC".to_string(),
+            start_at: "2024-01-01T09:00:00+00:00".to_string(),
+            shift_size: 1,
+            created_by_user_id: "U6HHTEST".to_string(),
+            created_by_user_name: "test-user".to_string(),
+            created_at: "".to_string(),
+            last_updated_at: "".to_string(),
+        };
+        repositories.rotations.save_rotation(&rotation).await.unwrap();
+
+        let saved = repositories.rotations.get_rotation("T123:E123", "support").await.unwrap().unwrap();
+        assert_eq!(saved.members, rotation.members);
+        assert_eq!(saved.cadence, RotationCadence::Weekly);
+
+        let alias = EmailAlias {
+            team: "T123:E123".to_string(),
+            email: "bob@example.com".to_string(),
+            slack_user_id: "U2".to_string(),
+            created_by_user_id: "U6HHTEST".to_string(),
+            created_by_user_name: "test-user".to_string(),
+            created_at: "".to_string(),
+        };
+        repositories.aliases.save_alias(&alias).await.unwrap();
+        repositories.aliases.save_alias(&EmailAlias { slack_user_id: "U3".to_string(), ..alias.clone() }).await.unwrap();
+
+        let aliases = repositories.aliases.list_aliases("T123:E123").await.unwrap();
+        assert_eq!(aliases.iter().map(|a| a.slack_user_id.clone()).collect::<Vec<String>>(), vec!["U3".to_string()]);
+
+        repositories.aliases.delete_alias("T123:E123", "bob@example.com").await.unwrap();
+        assert!(repositories.aliases.list_aliases("T123:E123").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn skip_tasks_which_fail_to_decrypt() {
+        let pool = sql_client::connect("sqlite::memory:").await.unwrap();
+        let tasks = ScheduledTasksSql::new(pool.clone(), Encryptor::new("plain text key which should be s"));
+        let rotated_key_tasks = ScheduledTasksSql::new(pool, Encryptor::new("another key which should be s123"));
+
+        let now = Utc::now();
+        tasks.save_scheduled_task(&ScheduledTask { task_id: "ocs-0001".to_string(), ..task(now.timestamp() - 60, false) }).await.unwrap();
+        rotated_key_tasks.save_scheduled_task(&ScheduledTask { task_id: "ocs-0002".to_string(), ..task(now.timestamp() - 60, false) }).await.unwrap();
+
+        let due_tasks = tasks.list_due_scheduled_tasks(&now).await.unwrap();
+        assert_eq!(due_tasks.iter().map(|t| t.task_id.as_str()).collect::<Vec<&str>>(), vec!["ocs-0001"]);
+
+        // The next readable task is found after an unreadable one
+        rotated_key_tasks.save_scheduled_task(&ScheduledTask { task_id: "ocs-0003".to_string(), ..task(now.timestamp() + 60, false) }).await.unwrap();
+        tasks.save_scheduled_task(&ScheduledTask { task_id: "ocs-0004".to_string(), ..task(now.timestamp() + 120, false) }).await.unwrap();
+        let next_task = tasks.get_next_scheduled_task(&now).await.unwrap();
+        assert_eq!(next_task.map(|t| t.task_id), Some("ocs-0004".to_string()));
+    }
+
+    #[tokio::test]
+    async fn skip_installations_which_fail_to_decrypt() {
+        let pool = sql_client::connect("sqlite::memory:").await.unwrap();
+        let installations = SlackInstallationsSql::new(pool.clone(), Encryptor::new("plain text key which should be s"));
+        let rotated_key_installations = SlackInstallationsSql::new(pool, Encryptor::new("another key which should be s123"));
+
+        installations.save_slack_installation(&SlackInstallation::default()).await.unwrap();
+        rotated_key_installations.save_slack_installation(&SlackInstallation { team_id: "T456".to_string(), ..Default::default() }).await.unwrap();
+
+        let listed = installations.list_installations().await.unwrap();
+        assert_eq!(listed.iter().map(|i| i.team_id.as_str()).collect::<Vec<&str>>(), vec!["T123"]);
+        assert!(matches!(installations.get_slack_installation("T456", "E123").await, Err(AppError::StorageError(_))));
+    }
+}
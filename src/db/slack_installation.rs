@@ -1,4 +1,6 @@
 
+use crate::service_provider::opsgenie::OpsgenieRegion;
+
 #[derive(Debug, Clone)]
 pub struct SlackInstallation {
     pub team_id: String,
@@ -16,4 +18,31 @@ pub struct SlackInstallation {
     pub bot_user_id: String,
 
     pub pager_duty_token: Option<String>,
+    pub opsgenie_token: Option<String>,
+    pub opsgenie_region: Option<OpsgenieRegion>,
+}
+
+/**
+ * The installation of the app to workspace T123, which the default test task belongs to
+ */
+#[cfg(test)]
+impl Default for SlackInstallation {
+    fn default() -> Self {
+        SlackInstallation {
+            team_id: "T123".to_string(),
+            team_name: "test".to_string(),
+            enterprise_id: "E123".to_string(),
+            enterprise_name: "test".to_string(),
+            is_enterprise_install: false,
+            access_token: "xoxb-token".to_string(),
+            token_type: "bot".to_string(),
+            scope: "usergroups:write".to_string(),
+            authed_user_id: "U6HHTEST".to_string(),
+            app_id: "A123".to_string(),
+            bot_user_id: "B123".to_string(),
+            pager_duty_token: None,
+            opsgenie_token: None,
+            opsgenie_region: None,
+        }
+    }
 }
@@ -1,11 +1,14 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
 use aws_config::SdkConfig;
 use aws_sdk_dynamodb::{Client, types::AttributeValue};
 use chrono::Utc;
 
-use crate::{encryptor::Encryptor, errors::AppError};
-use super::dynamodb_client::{get_attribute, get_optional_attribute};
+use crate::{encryptor::Encryptor, errors::AppError, service_provider::opsgenie::OpsgenieRegion};
+use super::dynamodb_client::{get_attribute, get_optional_attribute, get_required_attribute};
 
-use super::SlackInstallation;
+use super::{slack_installations_repository::skip_unreadable_installations, SlackInstallation, SlackInstallationsRepository};
 
 pub struct SlackInstallationsDynamoDb {
     client: Client,
@@ -15,14 +18,49 @@ pub struct SlackInstallationsDynamoDb {
 
 impl SlackInstallationsDynamoDb {
     pub fn new(config: &SdkConfig, table_name: String, encryptor: Encryptor) -> SlackInstallationsDynamoDb {
-        SlackInstallationsDynamoDb{ client: Client::new(&config), table_name, encryptor }
+        SlackInstallationsDynamoDb{ client: Client::new(config), table_name, encryptor }
     }
    
     pub fn installation_id(&self, slack_team_id: &str, slack_enterprise_id: &str) -> String {
         format!("{}:{}", slack_team_id, slack_enterprise_id)
     }
 
-    pub async fn save_slack_installation(&self, installation: &SlackInstallation) -> Result<(), AppError> {
+    fn to_slack_installation(&self, item: &HashMap<String, AttributeValue>) -> Result<SlackInstallation, AppError> {
+        let team_id = get_required_attribute(item, "team_id")?;
+        let decrypt = |name: &str| get_optional_attribute(item, name)
+            .map(|json| self.encryptor.decrypt_json(&json)
+                .map_err(|err| AppError::StorageError(format!("Couldn't decrypt {} for installation {}, error: {:?}", name, team_id, err))))
+            .transpose();
+
+        let access_token = decrypt("access_token")?
+            .ok_or_else(|| AppError::StorageError(format!("Missing access_token for installation {}", team_id)))?;
+        let pagerduty_token = decrypt("pagerduty_token")?;
+        let opsgenie_token = decrypt("opsgenie_token")?;
+
+        Ok(SlackInstallation {
+            team_id,
+            team_name: get_attribute(item, "team_name"),
+            enterprise_id: get_attribute(item, "enterprise_id"),
+            enterprise_name: get_attribute(item, "enterprise_name"),
+            is_enterprise_install: get_attribute(item, "is_enterprise_install").eq_ignore_ascii_case("true"),
+
+            access_token,
+            token_type: get_attribute(item, "token_type"),
+            scope: get_attribute(item, "scope"),
+            authed_user_id: get_attribute(item, "authed_user_id"),
+            app_id: get_attribute(item, "app_id"),
+            bot_user_id: get_attribute(item, "bot_user_id"),
+
+            pager_duty_token: pagerduty_token,
+            opsgenie_token,
+            opsgenie_region: get_optional_attribute(item, "opsgenie_region").and_then(|region| region.parse().ok()),
+        })
+    }
+}
+
+#[async_trait]
+impl SlackInstallationsRepository for SlackInstallationsDynamoDb {
+    async fn save_slack_installation(&self, installation: &SlackInstallation) -> Result<(), AppError> {
         let now = Utc::now();
 
         let t = installation.clone();
@@ -56,7 +94,7 @@ impl SlackInstallationsDynamoDb {
         Ok(())
     }
 
-    pub async fn update_pagerduty_token(&self, slack_team_id: String, slack_enterprise_id: String, pagerduty_token: &str) -> Result<(), AppError> {
+    async fn update_pagerduty_token(&self, slack_team_id: String, slack_enterprise_id: String, pagerduty_token: &str) -> Result<(), AppError> {
         let now = Utc::now();
         let installation_id = self.installation_id(&slack_team_id, &slack_enterprise_id);
         let encrypted_token = self.encryptor.encrypt(pagerduty_token)?;
@@ -79,47 +117,51 @@ impl SlackInstallationsDynamoDb {
         Ok(())
     }
 
-    pub async fn list_installations(&self) -> Result<Vec<SlackInstallation>, AppError> {
-        let scan_output = self.client
+    async fn update_opsgenie_token(&self, slack_team_id: String, slack_enterprise_id: String, opsgenie_token: &str, opsgenie_region: OpsgenieRegion) -> Result<(), AppError> {
+        let now = Utc::now();
+        let installation_id = self.installation_id(&slack_team_id, &slack_enterprise_id);
+        let encrypted_token = self.encryptor.encrypt(opsgenie_token)?;
+        let encrypted_token_json = serde_json::to_string(&encrypted_token).unwrap();
+
+        let request = self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("id", AttributeValue::S(installation_id.to_string()))
+            .update_expression("SET opsgenie_token = :opsgenie_token, opsgenie_region = :opsgenie_region, last_updated_at = :last_updated_at")
+            .condition_expression("id = :id")
+            .expression_attribute_values(":opsgenie_token", AttributeValue::S(encrypted_token_json))
+            .expression_attribute_values(":opsgenie_region", AttributeValue::S(opsgenie_region.to_string()))
+            .expression_attribute_values(":last_updated_at", AttributeValue::S(now.to_rfc3339()))
+            .expression_attribute_values(":id", AttributeValue::S(installation_id.to_string()))
+        ;
+
+        println!("Update opsgenie token for slack installation in DynamoDB, team_id: {}, enterprise_id: {}", slack_team_id, slack_enterprise_id);
+        request.send().await?;
+        
+        Ok(())
+    }
+
+    async fn list_installations(&self) -> Result<Vec<SlackInstallation>, AppError> {
+        let items: Result<Vec<_>, _> = self.client
             .scan()
             .table_name(&self.table_name)
+            .into_paginator()
+            .items()
+            .send()
+            .collect()
+            .await;
+
+        Ok(skip_unreadable_installations(items?.iter().map(|item| self.to_slack_installation(item))))
+    }
+
+    async fn get_slack_installation(&self, slack_team_id: &str, slack_enterprise_id: &str) -> Result<Option<SlackInstallation>, AppError> {
+        let output = self.client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("id", AttributeValue::S(self.installation_id(slack_team_id, slack_enterprise_id)))
             .send()
             .await?;
-        
-        let items: Vec<SlackInstallation> = scan_output.items.unwrap_or_else(Vec::new)
-            .into_iter()
-            .map(|item| {
-                let team_id = get_attribute(&item, "team_id");
-                let encrypted_token_json = get_attribute(&item, "access_token");
-                let encrypted_token = serde_json::from_str(&encrypted_token_json).unwrap();
-                let access_token = self.encryptor.decrypt(&encrypted_token)
-                    .expect(format!("Couldn't decrypt slack token for installation {}", team_id).as_str());
-
-                let pagerduty_token = get_optional_attribute(&item, "pagerduty_token")
-                    .map(|json| serde_json::from_str(&json).unwrap())
-                    .map(|encrypted| self.encryptor.decrypt(&encrypted))
-                    .map(|token| token.expect(format!("Couldn't decrypt pagerduty token for installation {}", team_id).as_str()))
-                ;
-
-                SlackInstallation {
-                    team_id,
-                    team_name: get_attribute(&item, "team_name"),
-                    enterprise_id: get_attribute(&item, "enterprise_id"),
-                    enterprise_name: get_attribute(&item, "enterprise_name"),
-                    is_enterprise_install: get_attribute(&item, "is_enterprise_install").eq_ignore_ascii_case("true"),
-                    
-                    access_token,
-                    token_type: get_attribute(&item, "token_type"),
-                    scope: get_attribute(&item, "scope"),
-                    authed_user_id: get_attribute(&item, "authed_user_id"),
-                    app_id: get_attribute(&item, "app_id"),
-                    bot_user_id: get_attribute(&item, "bot_user_id"),
-
-                    pager_duty_token: pagerduty_token,
-                }
-            })
-            .collect();
-
-        Ok(items)
+
+        output.item.map(|item| self.to_slack_installation(&item)).transpose()
     }
 }
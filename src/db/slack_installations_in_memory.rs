@@ -0,0 +1,61 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use async_trait::async_trait;
+
+use crate::{errors::AppError, service_provider::opsgenie::OpsgenieRegion};
+
+use super::{SlackInstallation, SlackInstallationsRepository};
+
+/**
+ * Keep the installations in memory, for running and testing without AWS
+ */
+#[derive(Default)]
+pub struct SlackInstallationsInMemory {
+    installations: Mutex<BTreeMap<String, SlackInstallation>>,
+}
+
+impl SlackInstallationsInMemory {
+    pub fn new() -> SlackInstallationsInMemory {
+        SlackInstallationsInMemory::default()
+    }
+
+    fn update<F>(&self, slack_team_id: &str, slack_enterprise_id: &str, update: F) -> Result<(), AppError> where F: FnOnce(&mut SlackInstallation) {
+        let installation_id = format!("{}:{}", slack_team_id, slack_enterprise_id);
+        match self.installations.lock().unwrap().get_mut(&installation_id) {
+            Some(installation) => {
+                update(installation);
+                Ok(())
+            },
+            None => Err(AppError::StorageError(format!("Slack installation not found: {}", installation_id))),
+        }
+    }
+}
+
+#[async_trait]
+impl SlackInstallationsRepository for SlackInstallationsInMemory {
+    async fn save_slack_installation(&self, installation: &SlackInstallation) -> Result<(), AppError> {
+        let installation_id = format!("{}:{}", installation.team_id, installation.enterprise_id);
+        self.installations.lock().unwrap().insert(installation_id, installation.clone());
+
+        Ok(())
+    }
+
+    async fn update_pagerduty_token(&self, slack_team_id: String, slack_enterprise_id: String, pagerduty_token: &str) -> Result<(), AppError> {
+        self.update(&slack_team_id, &slack_enterprise_id, |installation| installation.pager_duty_token = Some(pagerduty_token.to_string()))
+    }
+
+    async fn update_opsgenie_token(&self, slack_team_id: String, slack_enterprise_id: String, opsgenie_token: &str, opsgenie_region: OpsgenieRegion) -> Result<(), AppError> {
+        self.update(&slack_team_id, &slack_enterprise_id, |installation| {
+            installation.opsgenie_token = Some(opsgenie_token.to_string());
+            installation.opsgenie_region = Some(opsgenie_region);
+        })
+    }
+
+    async fn list_installations(&self) -> Result<Vec<SlackInstallation>, AppError> {
+        Ok(self.installations.lock().unwrap().values().cloned().collect())
+    }
+
+    async fn get_slack_installation(&self, slack_team_id: &str, slack_enterprise_id: &str) -> Result<Option<SlackInstallation>, AppError> {
+        Ok(self.installations.lock().unwrap().get(&format!("{}:{}", slack_team_id, slack_enterprise_id)).cloned())
+    }
+}
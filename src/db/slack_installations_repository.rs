@@ -0,0 +1,30 @@
+use async_trait::async_trait;
+
+use crate::{errors::AppError, service_provider::opsgenie::OpsgenieRegion};
+
+use super::SlackInstallation;
+
+/**
+ * Skip the installations which couldn't be read, e.g. failed to decrypt, so they don't stop the other workspaces from updating
+ */
+pub(super) fn skip_unreadable_installations(installations: impl IntoIterator<Item = Result<SlackInstallation, AppError>>) -> Vec<SlackInstallation> {
+    installations.into_iter()
+        .filter_map(|installation| installation.inspect_err(|err| println!("Skipped unreadable slack installation, error: {:?}", err)).ok())
+        .collect()
+}
+
+/**
+ * The storage of the Slack workspaces the app is installed to, with the api tokens of the schedule providers
+ */
+#[async_trait]
+pub trait SlackInstallationsRepository: Send + Sync {
+    async fn save_slack_installation(&self, installation: &SlackInstallation) -> Result<(), AppError>;
+
+    async fn update_pagerduty_token(&self, slack_team_id: String, slack_enterprise_id: String, pagerduty_token: &str) -> Result<(), AppError>;
+
+    async fn update_opsgenie_token(&self, slack_team_id: String, slack_enterprise_id: String, opsgenie_token: &str, opsgenie_region: OpsgenieRegion) -> Result<(), AppError>;
+
+    async fn list_installations(&self) -> Result<Vec<SlackInstallation>, AppError>;
+
+    async fn get_slack_installation(&self, slack_team_id: &str, slack_enterprise_id: &str) -> Result<Option<SlackInstallation>, AppError>;
+}
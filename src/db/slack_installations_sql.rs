@@ -0,0 +1,158 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{any::AnyRow, AnyPool, Row};
+
+use crate::{encryptor::Encryptor, errors::AppError, service_provider::opsgenie::OpsgenieRegion};
+
+use super::{slack_installations_repository::skip_unreadable_installations, SlackInstallation, SlackInstallationsRepository};
+
+pub struct SlackInstallationsSql {
+    pool: AnyPool,
+    encryptor: Encryptor,
+}
+
+impl SlackInstallationsSql {
+    pub fn new(pool: AnyPool, encryptor: Encryptor) -> SlackInstallationsSql {
+        SlackInstallationsSql { pool, encryptor }
+    }
+
+    fn installation_id(&self, slack_team_id: &str, slack_enterprise_id: &str) -> String {
+        format!("{}:{}", slack_team_id, slack_enterprise_id)
+    }
+
+    fn to_slack_installation(&self, row: &AnyRow) -> Result<SlackInstallation, AppError> {
+        let team_id: String = row.try_get("team_id")?;
+        let decrypt = |column: &str| row.try_get::<Option<String>, _>(column)?
+            .map(|json| self.encryptor.decrypt_json(&json)
+                .map_err(|err| AppError::StorageError(format!("Couldn't decrypt {} for installation {}, error: {:?}", column, team_id, err))))
+            .transpose();
+
+        Ok(SlackInstallation {
+            team_id: team_id.clone(),
+            team_name: row.get("team_name"),
+            enterprise_id: row.get("enterprise_id"),
+            enterprise_name: row.get("enterprise_name"),
+            is_enterprise_install: row.get::<i64, _>("is_enterprise_install") != 0,
+
+            access_token: decrypt("access_token")?
+                .ok_or_else(|| AppError::StorageError(format!("Missing access_token for installation {}", team_id)))?,
+            token_type: row.get("token_type"),
+            scope: row.get("scope"),
+            authed_user_id: row.get("authed_user_id"),
+            app_id: row.get("app_id"),
+            bot_user_id: row.get("bot_user_id"),
+
+            pager_duty_token: decrypt("pager_duty_token")?,
+            opsgenie_token: decrypt("opsgenie_token")?,
+            opsgenie_region: row.get::<Option<String>, _>("opsgenie_region").and_then(|region| region.parse().ok()),
+        })
+    }
+
+    async fn update_tokens(&self, slack_team_id: &str, slack_enterprise_id: &str, statement: &str, values: Vec<Option<String>>) -> Result<(), AppError> {
+        let mut query = sqlx::query(statement);
+        for value in values {
+            query = query.bind(value);
+        }
+
+        let result = query
+            .bind(Utc::now().to_rfc3339())
+            .bind(self.installation_id(slack_team_id, slack_enterprise_id))
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::StorageError(format!("Slack installation not found, team_id: {}, enterprise_id: {}", slack_team_id, slack_enterprise_id)));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SlackInstallationsRepository for SlackInstallationsSql {
+    async fn save_slack_installation(&self, installation: &SlackInstallation) -> Result<(), AppError> {
+        let now = Utc::now().to_rfc3339();
+        let t = installation.clone();
+        let encrypt = |token: Option<String>| token.map(|token| self.encryptor.encrypt_to_json(&token)).transpose();
+
+        // Reinstalling the app keeps the api keys of the schedule providers
+        sqlx::query(r#"
+            INSERT INTO slack_installations (
+                id, team_id, team_name, enterprise_id, enterprise_name, is_enterprise_install,
+                access_token, token_type, scope, authed_user_id, app_id, bot_user_id,
+                pager_duty_token, opsgenie_token, opsgenie_region, created_at, last_updated_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+            ON CONFLICT (id) DO UPDATE SET
+                team_name = excluded.team_name,
+                enterprise_name = excluded.enterprise_name,
+                is_enterprise_install = excluded.is_enterprise_install,
+                access_token = excluded.access_token,
+                token_type = excluded.token_type,
+                scope = excluded.scope,
+                authed_user_id = excluded.authed_user_id,
+                app_id = excluded.app_id,
+                bot_user_id = excluded.bot_user_id,
+                last_updated_at = excluded.last_updated_at
+        "#)
+            .bind(self.installation_id(&t.team_id, &t.enterprise_id))
+            .bind(t.team_id)
+            .bind(t.team_name)
+            .bind(t.enterprise_id)
+            .bind(t.enterprise_name)
+            .bind(t.is_enterprise_install as i64)
+            .bind(self.encryptor.encrypt_to_json(&t.access_token)?)
+            .bind(t.token_type)
+            .bind(t.scope)
+            .bind(t.authed_user_id)
+            .bind(t.app_id)
+            .bind(t.bot_user_id)
+            .bind(encrypt(t.pager_duty_token)?)
+            .bind(encrypt(t.opsgenie_token)?)
+            .bind(t.opsgenie_region.map(|region| region.to_string()))
+            .bind(now.clone())
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        println!("Saved slack installation, team_id: {}, enterprise_id: {}", installation.team_id, installation.enterprise_id);
+
+        Ok(())
+    }
+
+    async fn update_pagerduty_token(&self, slack_team_id: String, slack_enterprise_id: String, pagerduty_token: &str) -> Result<(), AppError> {
+        println!("Update pagerduty token for slack installation, team_id: {}, enterprise_id: {}", slack_team_id, slack_enterprise_id);
+        self.update_tokens(
+            &slack_team_id,
+            &slack_enterprise_id,
+            "UPDATE slack_installations SET pager_duty_token = $1, last_updated_at = $2 WHERE id = $3",
+            vec![Some(self.encryptor.encrypt_to_json(pagerduty_token)?)],
+        ).await
+    }
+
+    async fn update_opsgenie_token(&self, slack_team_id: String, slack_enterprise_id: String, opsgenie_token: &str, opsgenie_region: OpsgenieRegion) -> Result<(), AppError> {
+        println!("Update opsgenie token for slack installation, team_id: {}, enterprise_id: {}", slack_team_id, slack_enterprise_id);
+        self.update_tokens(
+            &slack_team_id,
+            &slack_enterprise_id,
+            "UPDATE slack_installations SET opsgenie_token = $1, opsgenie_region = $2, last_updated_at = $3 WHERE id = $4",
+            vec![Some(self.encryptor.encrypt_to_json(opsgenie_token)?), Some(opsgenie_region.to_string())],
+        ).await
+    }
+
+    async fn list_installations(&self) -> Result<Vec<SlackInstallation>, AppError> {
+        let rows = sqlx::query("SELECT * FROM slack_installations")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(skip_unreadable_installations(rows.iter().map(|row| self.to_slack_installation(row))))
+    }
+
+    async fn get_slack_installation(&self, slack_team_id: &str, slack_enterprise_id: &str) -> Result<Option<SlackInstallation>, AppError> {
+        let row = sqlx::query("SELECT * FROM slack_installations WHERE id = $1")
+            .bind(self.installation_id(slack_team_id, slack_enterprise_id))
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| self.to_slack_installation(&row)).transpose()
+    }
+}
@@ -51,10 +51,24 @@ impl Encryptor {
         let encrypted = base64::decode_no_pad(encrypted_data.data.as_ref())?;
 
         let nonce = XNonce::from_slice(nonce_bytes.as_slice());
-        let plaintext = self.cipher.decrypt(&nonce, encrypted.as_slice())?;
+        let plaintext = self.cipher.decrypt(nonce, encrypted.as_slice())?;
     
         Ok(String::from_utf8(plaintext).expect("Invalid UTF-8 sequence"))
     }    
+
+    /**
+     * Encrypt to the json of the encrypted data, which is how the secrets are saved in the databases
+     */
+    pub fn encrypt_to_json(&self, plaintext: &str) -> Result<String, AppError> {
+        Ok(serde_json::to_string(&self.encrypt(plaintext)?).unwrap())
+    }
+
+    pub fn decrypt_json(&self, encrypted_json: &str) -> Result<String, AppError> {
+        let encrypted_data: EncryptedData = serde_json::from_str(encrypted_json)
+            .map_err(|err| AppError::UnexpectedError(format!("Invalid encrypted data: {}", err)))?;
+
+        self.decrypt(&encrypted_data)
+    }
 }
 
 #[cfg(test)]
@@ -65,7 +79,7 @@ mod tests {
     fn encrypt_decrypt_string() {
         let key_plaintext = "plain text key which should be s";
         
-        let encryptor = Encryptor::new(&key_plaintext);
+        let encryptor = Encryptor::new(key_plaintext);
         
         let original = "plain text string";
         let encrypted = encryptor.encrypt(original).expect("Failed to encrypt text");
@@ -1,8 +1,8 @@
 use std::{num::ParseIntError, env::VarError};
 
 use aws_sdk_cloudformation::operation::describe_stacks::DescribeStacksError;
-use aws_sdk_dynamodb::{operation::{put_item::PutItemError, delete_item::DeleteItemError, scan::ScanError, update_item::UpdateItemError}, error::SdkError};
-use aws_sdk_scheduler::operation::{create_schedule::CreateScheduleError, delete_schedule::DeleteScheduleError};
+use aws_sdk_dynamodb::{operation::{get_item::GetItemError, put_item::PutItemError, query::QueryError, delete_item::DeleteItemError, scan::ScanError, update_item::UpdateItemError}, error::SdkError};
+use aws_sdk_scheduler::operation::{create_schedule::CreateScheduleError, delete_schedule::DeleteScheduleError, get_schedule::GetScheduleError};
 use aws_sdk_scheduler::operation::list_schedules::ListSchedulesError;
 use aws_sdk_secretsmanager::operation::get_secret_value::GetSecretValueError;
 use lambda_runtime::Diagnostic;
@@ -22,9 +22,39 @@ pub enum AppError {
     #[error("Slack error: `{0:?}`")]
     SlackError(String),
 
+    #[error("Missing header in Slack request: `{0:?}`")]
+    MissingSlackHeaderError(String),
+
+    #[error("Slack request timestamp is too old or in the future: `{0:?}`")]
+    StaleSlackRequestError(i64),
+
+    #[error("Invalid Slack request signature")]
+    InvalidSlackSignatureError,
+
     #[error("Failed to send request to PagerDuty, error: `{0:?}`")]
     PagerDutyError(String),
 
+    #[error("Failed to send request to Opsgenie, error: `{0:?}`")]
+    OpsgenieError(String),
+
+    #[error("Failed to read iCalendar feed, error: `{0:?}`")]
+    IcalError(String),
+
+    #[error("Invalid rotation: `{0:?}`")]
+    RotationError(String),
+
+    #[error("Invalid schedule: `{0:?}`")]
+    ScheduledTaskError(String),
+
+    #[error("Invalid override: `{0:?}`")]
+    OverrideError(String),
+
+    #[error("Invalid email alias: `{0:?}`")]
+    AliasError(String),
+
+    #[error("Invalid date time: `{0:?}`")]
+    InvalidDateTimeError(String),
+
     #[error("Failed to parse int, error: `{0:?}`")]
     ParseIntError(ParseIntError),
 
@@ -34,6 +64,12 @@ pub enum AppError {
     #[error("Failed to update user group in Slack, error: `{0:?}`")]
     SlackUpdateUserGroupError(String),
 
+    #[error("Refused to update user group: `{0:?}`")]
+    GuardRailError(String),
+
+    #[error("User not found in Slack by email: `{0:?}`")]
+    SlackUserNotFoundError(String),
+
     #[error("User group not found in Slack: `{0:?}`")]
     SlackUserGroupNotFoundError(String),
 
@@ -43,6 +79,15 @@ pub enum AppError {
     #[error("Failed to put item to DynamoDB: `{0:?}`")]
     GetSecretValueError(#[from] SdkError<GetSecretValueError>),
 
+    #[error("Failed to load secrets: `{0:?}`")]
+    SecretsError(String),
+
+    #[error("Invalid configuration: `{0:?}`")]
+    ConfigError(String),
+
+    #[error("Failed to get item from DynamoDB: `{0:?}`")]
+    DynamoDBGetItemError(#[from] SdkError<GetItemError>),
+
     #[error("Failed to put item to DynamoDB: `{0:?}`")]
     DynamoDBPutItemError(#[from] SdkError<PutItemError>),
 
@@ -52,15 +97,33 @@ pub enum AppError {
     #[error("Failed to delete item from DynamoDB: `{0:?}`")]
     DynamoDBDeleteItemError(#[from] SdkError<DeleteItemError>),
 
+    #[error("Failed to query DynamoDB table: `{0:?}`")]
+    DynamoDBQueryError(#[from] SdkError<QueryError>),
+
     #[error("Failed to scan DynamoDB table: `{0:?}`")]
     DynamoDBScanError(#[from] SdkError<ScanError>),
 
+    #[error("Failed to access storage: `{0:?}`")]
+    StorageError(String),
+
+    #[error("Scheduled task was changed by another updater: `{0:?}`")]
+    ConcurrentUpdateError(String),
+
+    #[error("Failed to query SQL database: `{0:?}`")]
+    SqlError(#[from] sqlx::Error),
+
+    #[error("Failed to migrate SQL database: `{0:?}`")]
+    SqlMigrateError(#[from] sqlx::migrate::MigrateError),
+
     #[error("Failed to create schedule in AWS Scheduler: `{0:?}`")]
     CreateScheduleError(#[from] SdkError<CreateScheduleError>),
 
     #[error("Failed to list current schedules in AWS Scheduler: `{0:?}`")]
     ListScheduleError(#[from] SdkError<ListSchedulesError>),
 
+    #[error("Failed to get schedule details from AWS Scheduler: `{0:?}`")]
+    GetScheduleError(#[from] SdkError<GetScheduleError>),
+
     #[error("Failed to delete schedule in AWS Scheduler: `{0:?}`")]
     DeleteScheduleError(#[from] SdkError<DeleteScheduleError>),
 
@@ -0,0 +1,296 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use derive_more::Display;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::errors::AppError;
+
+pub const DEFAULT_MESSAGE_TEMPLATE: &str = "Updated support user group {user_group} to: {on_call}";
+
+// The placeholders available in the message templates, links are written in the template as <https://example.com|text>
+pub const PLACEHOLDERS: [&str; 7] = ["user_group", "on_call", "incoming", "outgoing", "schedule", "next_handoff", "task_id"];
+
+// The part of the channel topic managed by the updater, the rest of the topic is kept as is
+pub const TOPIC_START_MARKER: &str = "[on-call]";
+pub const TOPIC_END_MARKER: &str = "[/on-call]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageFormat {
+    #[default]
+    #[display("text")]
+    Text,
+
+    // Rendered as Block Kit sections, with the plain text as the fallback of the notifications
+    #[display("blocks")]
+    Blocks,
+}
+
+impl FromStr for MessageFormat {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(MessageFormat::Text),
+            "blocks" => Ok(MessageFormat::Blocks),
+            _ => Err(AppError::ScheduledTaskError(format!("Unknown message format: {}, expecting text or blocks", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum PostMode {
+    // A new message in the channel for every handoff
+    #[default]
+    #[display("new")]
+    NewMessage,
+
+    // Replies in the thread of the first handoff message, to keep the channel quiet
+    #[display("thread")]
+    Thread,
+}
+
+impl FromStr for PostMode {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "new" => Ok(PostMode::NewMessage),
+            "thread" => Ok(PostMode::Thread),
+            _ => Err(AppError::ScheduledTaskError(format!("Unknown post mode: {}, expecting new or thread", s))),
+        }
+    }
+}
+
+/**
+ * How the changes of the user group are announced, configured per task
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HandoffNotification {
+    // The default template is used when it's not set
+    pub template: Option<String>,
+    pub format: MessageFormat,
+    pub post_mode: PostMode,
+
+    // Send a direct message to the users joining the group
+    pub notify_incoming: bool,
+
+    // The channel topic is only updated when it's set, e.g. On call: {on_call} until {next_handoff}
+    pub topic_template: Option<String>,
+
+    // Send a direct message to the users joining the group the hours before their shift starts
+    pub remind_before_hours: Option<u32>,
+}
+
+/**
+ * A change of the on-call users, rendered into the handoff messages
+ */
+#[derive(Debug, Clone)]
+pub struct Handoff {
+    pub task_id: String,
+    pub user_group_id: String,
+    pub schedule: String,
+
+    // The mentions of the on-call users, with the users they are covering
+    pub on_call: Vec<String>,
+    pub incoming_user_ids: Vec<String>,
+    pub outgoing_user_ids: Vec<String>,
+
+    // In the timezone of the task
+    pub next_handoff_at: Option<DateTime<Tz>>,
+}
+
+// Shown in the timezone of the reader, falling back to the time in the timezone of the task
+fn slack_date(at: &DateTime<Tz>) -> String {
+    format!("<!date^{}^{{date_short_pretty}} {{time}}|{}>", at.timestamp(), at.to_rfc3339())
+}
+
+/**
+ * The reminder to a user joining the group at the start of the next shift
+ */
+pub fn reminder_message(user_group_id: &str, schedule: &str, shift_start: &DateTime<Tz>) -> String {
+    format!("Reminder: you're on call in <!subteam^{}> from {}, based on {}", user_group_id, slack_date(shift_start), schedule)
+}
+
+fn mention_users(user_ids: &[String]) -> String {
+    if user_ids.is_empty() {
+        return "nobody".to_string();
+    }
+
+    user_ids.iter().map(|id| format!("<@{}>", id)).collect::<Vec<String>>().join(", ")
+}
+
+impl Handoff {
+    fn next_handoff(&self) -> String {
+        self.next_handoff_at.as_ref().map(slack_date).unwrap_or("not scheduled".to_string())
+    }
+
+    fn placeholder_values(&self) -> BTreeMap<&'static str, String> {
+        BTreeMap::from([
+            ("user_group", format!("<!subteam^{}>", self.user_group_id)),
+            ("on_call", if self.on_call.is_empty() { "nobody".to_string() } else { self.on_call.join(", ") }),
+            ("incoming", mention_users(&self.incoming_user_ids)),
+            ("outgoing", mention_users(&self.outgoing_user_ids)),
+            ("schedule", self.schedule.clone()),
+            ("next_handoff", self.next_handoff()),
+            ("task_id", self.task_id.clone()),
+        ])
+    }
+
+    /**
+     * The direct message to a user joining the group
+     */
+    pub fn direct_message(&self) -> String {
+        render_template("You're now on call in {user_group} based on {schedule}, the next handoff is {next_handoff}", self)
+    }
+}
+
+lazy_static! {
+    static ref PLACEHOLDER: Regex = Regex::new(r"\{(\w+)\}").unwrap();
+}
+
+/**
+ * Check the placeholders of a template, before it's saved to the task
+ */
+pub fn validate_template(template: &str) -> Result<(), AppError> {
+    match PLACEHOLDER.captures_iter(template).map(|captures| captures[1].to_string()).find(|name| !PLACEHOLDERS.contains(&name.as_str())) {
+        Some(name) => Err(AppError::ScheduledTaskError(format!("Unknown placeholder {{{}}} in the message template, expecting any of: {}", name, PLACEHOLDERS.join(", ")))),
+        None => Ok(()),
+    }
+}
+
+/**
+ * Replace the placeholders like {incoming} with the values of the handoff, the unknown placeholders are kept as is
+ */
+pub fn render_template(template: &str, handoff: &Handoff) -> String {
+    replace_placeholders(template, &handoff.placeholder_values())
+}
+
+fn replace_placeholders(template: &str, values: &BTreeMap<&'static str, String>) -> String {
+    PLACEHOLDER.replace_all(template, |captures: &regex::Captures| {
+        values.get(&captures[1]).cloned().unwrap_or(captures[0].to_string())
+    }).to_string()
+}
+
+impl HandoffNotification {
+    pub fn render_text(&self, handoff: &Handoff) -> String {
+        render_template(self.template.as_deref().unwrap_or(DEFAULT_MESSAGE_TEMPLATE), handoff)
+    }
+
+    /**
+     * The channel topic with the managed part replaced, or None when the topic isn't managed or already up to date
+     */
+    pub fn render_topic(&self, current_topic: &str, handoff: &Handoff) -> Option<String> {
+        let template = self.topic_template.as_ref()?;
+
+        // Slack doesn't format the dates in the topics
+        let mut values = handoff.placeholder_values();
+        values.insert("next_handoff", handoff.next_handoff_at.map(|at| at.format("%a %-d %b %H:%M").to_string()).unwrap_or("not scheduled".to_string()));
+        let section = format!("{} {} {}", TOPIC_START_MARKER, replace_placeholders(template, &values), TOPIC_END_MARKER);
+
+        let topic = match (current_topic.find(TOPIC_START_MARKER), current_topic.find(TOPIC_END_MARKER)) {
+            (Some(start), Some(end)) if start < end => format!("{}{}{}", &current_topic[..start], section, &current_topic[end + TOPIC_END_MARKER.len()..]),
+            _ if current_topic.trim().is_empty() => section,
+            _ => format!("{} | {}", current_topic.trim_end(), section),
+        };
+
+        Some(topic).filter(|topic| topic != current_topic)
+    }
+
+    /**
+     * The Block Kit blocks of the message, None for the plain text format
+     */
+    pub fn render_blocks(&self, handoff: &Handoff) -> Option<Value> {
+        match self.format {
+            MessageFormat::Text => None,
+            MessageFormat::Blocks => Some(json!([
+                {
+                    "type": "section",
+                    "text": { "type": "mrkdwn", "text": self.render_text(handoff) },
+                },
+                {
+                    "type": "context",
+                    "elements": [
+                        { "type": "mrkdwn", "text": render_template("{schedule}, next handoff {next_handoff}, task `{task_id}`", handoff) },
+                    ],
+                },
+            ])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::handoff_notification::{reminder_message, render_template, validate_template, Handoff, HandoffNotification, MessageFormat};
+
+    fn handoff() -> Handoff {
+        Handoff {
+            task_id: "ocs-0001".to_string(),
+            user_group_id: "S123".to_string(),
+            schedule: "PagerDuty schedule P123".to_string(),
+            on_call: vec!["<@U2>".to_string(), "<@U3> (covering <@U4>)".to_string()],
+            incoming_user_ids: vec!["U2".to_string()],
+            outgoing_user_ids: vec![],
+            next_handoff_at: Some(chrono_tz::UTC.with_ymd_and_hms(2023, 4, 3, 9, 0, 0).unwrap()),
+        }
+    }
+
+    #[test]
+    fn render_default_and_custom_templates() {
+        assert_eq!(HandoffNotification::default().render_text(&handoff()), "Updated support user group <!subteam^S123> to: <@U2>, <@U3> (covering <@U4>)");
+
+        assert_eq!(
+            render_template("{incoming} took over {user_group} from {outgoing} until {next_handoff}, see <https://example.com/runbook|runbook> {unknown}", &handoff()),
+            "<@U2> took over <!subteam^S123> from nobody until <!date^1680512400^{date_short_pretty} {time}|2023-04-03T09:00:00+00:00>, see <https://example.com/runbook|runbook> {unknown}",
+        );
+
+        assert!(validate_template("{incoming} took over from {outgoing}").is_ok());
+        assert!(validate_template("{incoming} took over from {previous}").is_err());
+    }
+
+    #[test]
+    fn render_blocks_only_for_block_kit_format() {
+        assert_eq!(HandoffNotification::default().render_blocks(&handoff()), None);
+
+        let notification = HandoffNotification { format: MessageFormat::Blocks, ..Default::default() };
+        let blocks = notification.render_blocks(&handoff()).unwrap();
+        assert_eq!(blocks[0]["text"]["text"], notification.render_text(&handoff()));
+        assert_eq!(blocks.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn replace_the_managed_part_of_topic() {
+        let notification = HandoffNotification { topic_template: Some("On call: {on_call} until {next_handoff}".to_string()), ..Default::default() };
+        let section = "[on-call] On call: <@U2>, <@U3> (covering <@U4>) until Mon 3 Apr 09:00 [/on-call]";
+
+        assert_eq!(notification.render_topic("", &handoff()), Some(section.to_string()));
+        assert_eq!(notification.render_topic("Runbook: <https://example.com>", &handoff()), Some(format!("Runbook: <https://example.com> | {}", section)));
+        assert_eq!(
+            notification.render_topic("Runbook [on-call] On call: <@U1> [/on-call] #incidents", &handoff()),
+            Some(format!("Runbook {} #incidents", section)),
+        );
+
+        // Only written when the topic changes, and never without the template
+        assert_eq!(notification.render_topic(&format!("Runbook {}", section), &handoff()), None);
+        assert_eq!(HandoffNotification::default().render_topic("Runbook", &handoff()), None);
+    }
+
+    #[test]
+    fn render_reminder_in_the_timezone_of_reader() {
+        let shift_start = chrono_tz::Australia::Melbourne.with_ymd_and_hms(2023, 4, 3, 9, 0, 0).unwrap();
+
+        assert_eq!(
+            reminder_message("S123", "Support primary", &shift_start),
+            "Reminder: you're on call in <!subteam^S123> from <!date^1680476400^{date_short_pretty} {time}|2023-04-03T09:00:00+10:00>, based on Support primary",
+        );
+    }
+}
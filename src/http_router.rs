@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use aws_lambda_events::{encodings::Body, event::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse}, http::{HeaderMap, HeaderValue}, query_map::QueryMap};
+
+use crate::{errors::AppError, slack_handler::{handle_slack_command, handle_slack_install, handle_slack_interactivity, handle_slack_oauth, response}};
+
+/**
+ * A request independent of the transport, so the same handlers serve API Gateway and the standalone server
+ */
+#[derive(Debug, Default)]
+pub struct HttpRequest {
+    pub path: String,
+    pub headers: HeaderMap<HeaderValue>,
+    pub query: QueryMap,
+    pub body: Option<String>,
+}
+
+impl HttpRequest {
+    /**
+     * Parse the query parameters from a query string, e.g. code=123&state=abc
+     */
+    pub fn parse_query(query: &str) -> QueryMap {
+        let mut params: HashMap<String, Vec<String>> = HashMap::new();
+        for (key, value) in form_urlencoded::parse(query.as_bytes()).into_owned() {
+            params.entry(key).or_default().push(value);
+        }
+
+        QueryMap::from(params)
+    }
+}
+
+impl From<ApiGatewayProxyRequest> for HttpRequest {
+    fn from(event: ApiGatewayProxyRequest) -> HttpRequest {
+        HttpRequest {
+            path: event.path.unwrap_or_default(),
+            headers: event.headers,
+            query: event.query_string_parameters,
+            body: event.body,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: HeaderMap<HeaderValue>,
+    pub body: String,
+}
+
+impl From<HttpResponse> for ApiGatewayProxyResponse {
+    fn from(response: HttpResponse) -> ApiGatewayProxyResponse {
+        ApiGatewayProxyResponse {
+            status_code: response.status_code as i64,
+            headers: response.headers,
+            body: Some(Body::from(response.body)),
+            ..Default::default()
+        }
+    }
+}
+
+pub async fn route_request(env: &str, request: HttpRequest) -> Result<HttpResponse, AppError> {
+    match request.path.as_str() {
+        "/healthz" => Ok(response(200, "ok".to_string())),
+        "/slack/install" => {
+            handle_slack_install(env).await.inspect_err(|err| {
+                println!("Failed to process Slack install request. err: {:?}", err);
+            })
+        },
+        "/slack/oauth" => {
+            handle_slack_oauth(env, request.query).await.inspect_err(|err| {
+                println!("Failed to process Slack OAuth request. err: {:?}", err);
+            })
+        },
+        "/slack/command" => {
+            handle_slack_command(env, request.headers, request.body).await.inspect_err(|err| {
+                println!("Failed to process Slack command. err: {:?}", err);
+            })
+        },
+        "/slack/interactivity" => {
+            handle_slack_interactivity(env, request.headers, request.body).await.inspect_err(|err| {
+                println!("Failed to process Slack interaction. err: {:?}", err);
+            })
+        },
+        _ => {
+            println!("Ignored invalid request. path: {}", request.path);
+            Ok(response(400, "Invalid request".to_string()))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http_router::{route_request, HttpRequest};
+
+    #[tokio::test]
+    async fn route_health_check() {
+        let response = route_request("dev", HttpRequest { path: "/healthz".to_string(), ..Default::default() }).await.unwrap();
+        assert_eq!(response.status_code, 200);
+
+        let response = route_request("dev", HttpRequest { path: "/unknown".to_string(), ..Default::default() }).await.unwrap();
+        assert_eq!(response.status_code, 400);
+    }
+
+    #[test]
+    fn parse_query_parameters() {
+        let query = HttpRequest::parse_query("code=123&state=a%2Bb");
+
+        assert_eq!(query.first("code"), Some("123"));
+        assert_eq!(query.first("state"), Some("a+b"));
+        assert_eq!(query.first("missing"), None);
+    }
+}
@@ -1,3 +1,7 @@
+// AppError wraps the AWS SDK errors, which are large but rarely constructed
+#![allow(clippy::result_large_err)]
+
+pub mod aliases;
 pub mod base64;
 pub mod config;
 pub mod cron;
@@ -5,11 +9,18 @@ pub mod db;
 pub mod timestamp;
 pub mod encryptor;
 pub mod errors;
+pub mod handoff_notification;
 mod http_client;
+pub mod http_router;
 pub mod user_group_updater;
+pub mod rotations;
+pub mod migrations;
+pub mod overrides;
 pub mod scheduled_tasks;
+pub mod schedule_wizard;
 pub mod service_provider;
 pub mod secrets;
 pub mod slack_handler;
+pub mod slack_request_verifier;
 
 pub use http_client::build_http_client;
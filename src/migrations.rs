@@ -0,0 +1,73 @@
+use aws_config::{BehaviorVersion, SdkConfig};
+
+use crate::{config::Config, encryptor::Encryptor, errors::AppError, overrides::{OverridesDynamodb, OverridesRepository, ScheduleOverride}, scheduled_tasks::{is_short_task_id, ScheduledTasksDynamodb, ScheduledTasksRepository}, secrets::load_secrets};
+
+async fn build_scheduled_tasks_db(aws_config: &SdkConfig, config: &Config) -> Result<ScheduledTasksDynamodb, AppError> {
+    let secrets = load_secrets(aws_config, &config.secrets_source).await?;
+    let encryptor = Encryptor::new(&secrets.encryption_key);
+
+    Ok(ScheduledTasksDynamodb::new(aws_config, config.schedules_table_name.clone(), encryptor))
+}
+
+/**
+ * One-time migration of the tasks created with the colon joined task id to the short task ids.
+ * The old id is kept in the legacy_task_id attribute, so it can still be used in the commands.
+ * It's safe to run again, a task already migrated by a previous interrupted run is reused.
+ */
+pub async fn migrate_task_ids(env: &str) -> Result<usize, AppError> {
+    let config = Config::new(env)?;
+    let aws_config = ::aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let scheduled_tasks_db = build_scheduled_tasks_db(&aws_config, &config).await?;
+    let overrides_db = OverridesDynamodb::new(&aws_config, config.overrides_table_name);
+
+    let tasks = scheduled_tasks_db.list_scheduled_tasks().await?;
+    let (legacy_tasks, migrated_tasks): (Vec<_>, Vec<_>) = tasks.into_iter().partition(|t| !is_short_task_id(&t.task_id));
+    println!("Found {} tasks to migrate", legacy_tasks.len());
+
+    let mut migrated = 0;
+    for legacy_task in legacy_tasks {
+        let legacy_task_id = legacy_task.task_id.clone();
+        let existing_task = migrated_tasks.iter()
+            .find(|t| t.team == legacy_task.team && t.legacy_task_id.as_deref() == Some(legacy_task_id.as_str()));
+
+        let task = match existing_task {
+            Some(task) => task.clone(),
+            None => {
+                let mut task = legacy_task.clone();
+                task.legacy_task_id = Some(legacy_task_id.clone());
+                scheduled_tasks_db.create_scheduled_task(&mut task).await?;
+                task
+            },
+        };
+
+        for schedule_override in overrides_db.list_overrides(&task.team, &legacy_task_id).await? {
+            overrides_db.save_override(&ScheduleOverride {
+                task: ScheduleOverride::task_key(&task.team, &task.task_id),
+                task_id: task.task_id.clone(),
+                ..schedule_override.clone()
+            }).await?;
+            overrides_db.delete_override(&task.team, &legacy_task_id, &schedule_override.override_id).await?;
+        }
+
+        let (team_id, enterprise_id) = task.team.split_once(':').unwrap_or((&task.team, ""));
+        scheduled_tasks_db.delete_scheduled_task(team_id, enterprise_id, &legacy_task_id).await?;
+
+        println!("Migrated task {} to {}", legacy_task_id, task.task_id);
+        migrated += 1;
+    }
+
+    Ok(migrated)
+}
+
+/**
+ * Save the schedule bucket of the tasks created before the index on the next update time,
+ * the tasks without the bucket are not in the index so they are never triggered.
+ * It's run by `make deploy`, and only updates the tasks without a bucket, so it's cheap to run again.
+ */
+pub async fn backfill_schedule_buckets(env: &str) -> Result<usize, AppError> {
+    let config = Config::new(env)?;
+    let aws_config = ::aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let scheduled_tasks_db = build_scheduled_tasks_db(&aws_config, &config).await?;
+
+    scheduled_tasks_db.backfill_schedule_buckets().await
+}
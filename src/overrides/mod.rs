@@ -0,0 +1,11 @@
+mod overrides_dynamodb;
+mod overrides_in_memory;
+mod overrides_repository;
+mod overrides_sql;
+mod schedule_override;
+
+pub use overrides_dynamodb::OverridesDynamodb;
+pub use overrides_in_memory::OverridesInMemory;
+pub use overrides_repository::OverridesRepository;
+pub use overrides_sql::OverridesSql;
+pub use schedule_override::{apply_overrides, OnCallAssignment, ScheduleOverride};
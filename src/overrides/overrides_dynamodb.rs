@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use aws_config::SdkConfig;
+use aws_sdk_dynamodb::{Client, types::AttributeValue};
+
+use crate::errors::AppError;
+use crate::db::dynamodb_client::{get_attribute, get_list_attribute};
+
+use super::schedule_override::ScheduleOverride;
+use super::overrides_repository::OverridesRepository;
+
+// Keep the expired overrides for a week before DynamoDB removes them
+const EXPIRE_AFTER_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+pub struct OverridesDynamodb {
+    client: Client,
+    table_name: String,
+}
+
+impl OverridesDynamodb {
+    pub fn new(config: &SdkConfig, table_name: String) -> OverridesDynamodb {
+        OverridesDynamodb{ client: Client::new(config), table_name }
+    }
+
+    fn to_override(&self, item: &HashMap<String, AttributeValue>) -> ScheduleOverride {
+        ScheduleOverride {
+            task: get_attribute(item, "task"),
+            override_id: get_attribute(item, "override_id"),
+            team: get_attribute(item, "team"),
+            task_id: get_attribute(item, "task_id"),
+            replacement_user_ids: get_list_attribute(item, "replacement_user_ids"),
+            replaced_user_ids: get_list_attribute(item, "replaced_user_ids"),
+            start_timestamp_utc: get_attribute(item, "start_timestamp_utc").parse::<i64>().unwrap(),
+            end_timestamp_utc: get_attribute(item, "end_timestamp_utc").parse::<i64>().unwrap(),
+            start_time: get_attribute(item, "start_time"),
+            end_time: get_attribute(item, "end_time"),
+
+            created_by_user_id: get_attribute(item, "created_by_user_id"),
+            created_by_user_name: get_attribute(item, "created_by_user_name"),
+            created_at: get_attribute(item, "created_at"),
+        }
+    }
+}
+
+#[async_trait]
+impl OverridesRepository for OverridesDynamodb {
+    async fn save_override(&self, schedule_override: &ScheduleOverride) -> Result<(), AppError> {
+        let o = schedule_override.clone();
+        let to_list = |ids: Vec<String>| AttributeValue::L(ids.into_iter().map(AttributeValue::S).collect());
+
+        let builder = self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("task", AttributeValue::S(o.task))
+            .item("override_id", AttributeValue::S(o.override_id))
+            .item("team", AttributeValue::S(o.team))
+            .item("task_id", AttributeValue::S(o.task_id))
+            .item("replacement_user_ids", to_list(o.replacement_user_ids))
+            .item("replaced_user_ids", to_list(o.replaced_user_ids))
+            .item("start_timestamp_utc", AttributeValue::N(o.start_timestamp_utc.to_string()))
+            .item("end_timestamp_utc", AttributeValue::N(o.end_timestamp_utc.to_string()))
+            .item("start_time", AttributeValue::S(o.start_time))
+            .item("end_time", AttributeValue::S(o.end_time))
+            .item("expires_at", AttributeValue::N((o.end_timestamp_utc + EXPIRE_AFTER_SECONDS).to_string()))
+
+            .item("created_by_user_id", AttributeValue::S(o.created_by_user_id))
+            .item("created_by_user_name", AttributeValue::S(o.created_by_user_name))
+            .item("created_at", AttributeValue::S(o.created_at))
+        ;
+
+        println!("Saving override {} of task {}", schedule_override.override_id, schedule_override.task_id);
+        builder.send().await?;
+
+        Ok(())
+    }
+
+    async fn list_overrides(&self, team: &str, task_id: &str) -> Result<Vec<ScheduleOverride>, AppError> {
+        let items: Result<Vec<_>, _> = self.client
+            .query()
+            .table_name(&self.table_name)
+            .key_condition_expression("task = :task")
+            .expression_attribute_values(":task", AttributeValue::S(ScheduleOverride::task_key(team, task_id)))
+            .into_paginator()
+            .items()
+            .send()
+            .collect()
+            .await;
+
+        Ok(items?.iter().map(|item| self.to_override(item)).collect())
+    }
+
+    async fn delete_override(&self, team: &str, task_id: &str, override_id: &str) -> Result<(), AppError> {
+        let request = self.client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("task", AttributeValue::S(ScheduleOverride::task_key(team, task_id)))
+            .key("override_id", AttributeValue::S(override_id.to_string()));
+
+        println!("Deleting override {} of task {}", override_id, task_id);
+        request.send().await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use async_trait::async_trait;
+
+use crate::errors::AppError;
+
+use super::overrides_repository::OverridesRepository;
+use super::schedule_override::ScheduleOverride;
+
+/**
+ * Keep the overrides in memory, for running and testing without AWS.
+ * Unlike DynamoDB, the expired overrides are kept until they are deleted.
+ */
+#[derive(Default)]
+pub struct OverridesInMemory {
+    // Keyed by the task key and the override id
+    overrides: Mutex<BTreeMap<(String, String), ScheduleOverride>>,
+}
+
+impl OverridesInMemory {
+    pub fn new() -> OverridesInMemory {
+        OverridesInMemory::default()
+    }
+}
+
+#[async_trait]
+impl OverridesRepository for OverridesInMemory {
+    async fn save_override(&self, schedule_override: &ScheduleOverride) -> Result<(), AppError> {
+        let key = (schedule_override.task.clone(), schedule_override.override_id.clone());
+        self.overrides.lock().unwrap().insert(key, schedule_override.clone());
+
+        Ok(())
+    }
+
+    async fn list_overrides(&self, team: &str, task_id: &str) -> Result<Vec<ScheduleOverride>, AppError> {
+        let task = ScheduleOverride::task_key(team, task_id);
+
+        Ok(self.overrides.lock().unwrap().values().filter(|o| o.task == task).cloned().collect())
+    }
+
+    async fn delete_override(&self, team: &str, task_id: &str, override_id: &str) -> Result<(), AppError> {
+        self.overrides.lock().unwrap().remove(&(ScheduleOverride::task_key(team, task_id), override_id.to_string()));
+
+        Ok(())
+    }
+}
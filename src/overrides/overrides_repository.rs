@@ -0,0 +1,17 @@
+use async_trait::async_trait;
+
+use crate::errors::AppError;
+
+use super::schedule_override::ScheduleOverride;
+
+/**
+ * The storage of the overrides, grouped by the overridden task
+ */
+#[async_trait]
+pub trait OverridesRepository: Send + Sync {
+    async fn save_override(&self, schedule_override: &ScheduleOverride) -> Result<(), AppError>;
+
+    async fn list_overrides(&self, team: &str, task_id: &str) -> Result<Vec<ScheduleOverride>, AppError>;
+
+    async fn delete_override(&self, team: &str, task_id: &str, override_id: &str) -> Result<(), AppError>;
+}
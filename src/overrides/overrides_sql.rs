@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+use sqlx::{any::AnyRow, AnyPool, Row};
+
+use crate::errors::AppError;
+use crate::db::sql_client::{from_json_list, to_json_list};
+
+use super::overrides_repository::OverridesRepository;
+use super::schedule_override::ScheduleOverride;
+
+/**
+ * Unlike DynamoDB there is no TTL, the expired overrides are kept until they are deleted
+ */
+pub struct OverridesSql {
+    pool: AnyPool,
+}
+
+impl OverridesSql {
+    pub fn new(pool: AnyPool) -> OverridesSql {
+        OverridesSql { pool }
+    }
+
+    fn to_override(&self, row: &AnyRow) -> Result<ScheduleOverride, AppError> {
+        Ok(ScheduleOverride {
+            task: row.get("task"),
+            override_id: row.get("override_id"),
+            team: row.get("team"),
+            task_id: row.get("task_id"),
+            replacement_user_ids: from_json_list(&row.get::<String, _>("replacement_user_ids"))?,
+            replaced_user_ids: from_json_list(&row.get::<String, _>("replaced_user_ids"))?,
+            start_timestamp_utc: row.get("start_timestamp_utc"),
+            end_timestamp_utc: row.get("end_timestamp_utc"),
+            start_time: row.get("start_time"),
+            end_time: row.get("end_time"),
+
+            created_by_user_id: row.get("created_by_user_id"),
+            created_by_user_name: row.get("created_by_user_name"),
+            created_at: row.get("created_at"),
+        })
+    }
+}
+
+#[async_trait]
+impl OverridesRepository for OverridesSql {
+    async fn save_override(&self, schedule_override: &ScheduleOverride) -> Result<(), AppError> {
+        let o = schedule_override.clone();
+
+        println!("Saving override {} of task {}", schedule_override.override_id, schedule_override.task_id);
+        sqlx::query(r#"
+            INSERT INTO schedule_overrides (
+                task, override_id, team, task_id, replacement_user_ids, replaced_user_ids,
+                start_timestamp_utc, end_timestamp_utc, start_time, end_time,
+                created_by_user_id, created_by_user_name, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            ON CONFLICT (task, override_id) DO UPDATE SET
+                replacement_user_ids = excluded.replacement_user_ids,
+                replaced_user_ids = excluded.replaced_user_ids,
+                start_timestamp_utc = excluded.start_timestamp_utc,
+                end_timestamp_utc = excluded.end_timestamp_utc,
+                start_time = excluded.start_time,
+                end_time = excluded.end_time
+        "#)
+            .bind(o.task)
+            .bind(o.override_id)
+            .bind(o.team)
+            .bind(o.task_id)
+            .bind(to_json_list(&o.replacement_user_ids))
+            .bind(to_json_list(&o.replaced_user_ids))
+            .bind(o.start_timestamp_utc)
+            .bind(o.end_timestamp_utc)
+            .bind(o.start_time)
+            .bind(o.end_time)
+            .bind(o.created_by_user_id)
+            .bind(o.created_by_user_name)
+            .bind(o.created_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_overrides(&self, team: &str, task_id: &str) -> Result<Vec<ScheduleOverride>, AppError> {
+        let rows = sqlx::query("SELECT * FROM schedule_overrides WHERE task = $1 ORDER BY start_timestamp_utc")
+            .bind(ScheduleOverride::task_key(team, task_id))
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(|row| self.to_override(row)).collect()
+    }
+
+    async fn delete_override(&self, team: &str, task_id: &str, override_id: &str) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM schedule_overrides WHERE task = $1 AND override_id = $2")
+            .bind(ScheduleOverride::task_key(team, task_id))
+            .bind(override_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+
+/**
+ * Replace some of the on-call users of a task in a time range, e.g. Alice covers Bob on Tuesday
+ */
+#[derive(Debug, Clone)]
+pub struct ScheduleOverride {
+    pub task: String, // Partition Key, the team and task id of the overridden task
+    pub override_id: String, // Sort Key
+
+    pub team: String,
+    pub task_id: String,
+
+    // Slack user ids
+    pub replacement_user_ids: Vec<String>,
+    pub replaced_user_ids: Vec<String>,
+
+    pub start_timestamp_utc: i64,
+    pub end_timestamp_utc: i64,
+    pub start_time: String,
+    pub end_time: String,
+
+    pub created_by_user_id: String,
+    pub created_by_user_name: String,
+    pub created_at: String,
+}
+
+impl ScheduleOverride {
+    pub fn task_key(team: &str, task_id: &str) -> String {
+        format!("{}#{}", team, task_id)
+    }
+
+    pub fn is_active_at(&self, at: &DateTime<Utc>) -> bool {
+        self.start_timestamp_utc <= at.timestamp() && at.timestamp() < self.end_timestamp_utc
+    }
+}
+
+/**
+ * A Slack user on call after applying the overrides, with the original assignees the user is covering
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnCallAssignment {
+    pub slack_user_id: String,
+    pub covering_user_ids: Vec<String>,
+}
+
+impl OnCallAssignment {
+    pub fn to_slack_message(&self) -> String {
+        if self.covering_user_ids.is_empty() {
+            format!("<@{}>", self.slack_user_id)
+        } else {
+            let covering = self.covering_user_ids.iter().map(|id| format!("<@{}>", id)).collect::<Vec<String>>().join(", ");
+            format!("<@{}> (covering {})", self.slack_user_id, covering)
+        }
+    }
+}
+
+/**
+ * Replace the on-call users by the overrides active at the given time.
+ * An override only applies when the user it replaces is on call, so it keeps working after the schedule changes.
+ */
+pub fn apply_overrides(slack_user_ids: &[String], overrides: &[ScheduleOverride], at: &DateTime<Utc>) -> Vec<OnCallAssignment> {
+    let mut assignments: Vec<OnCallAssignment> = slack_user_ids.iter()
+        .map(|id| OnCallAssignment { slack_user_id: id.clone(), covering_user_ids: vec![] })
+        .collect();
+
+    for schedule_override in overrides.iter().filter(|o| o.is_active_at(at)) {
+        let replaced: Vec<OnCallAssignment> = assignments.iter()
+            .filter(|a| schedule_override.replaced_user_ids.contains(&a.slack_user_id))
+            .cloned()
+            .collect();
+        if replaced.is_empty() {
+            continue;
+        }
+
+        assignments.retain(|a| !schedule_override.replaced_user_ids.contains(&a.slack_user_id));
+
+        // Keep tracking the original assignees when overrides are chained
+        let covering_user_ids: Vec<String> = replaced.into_iter()
+            .flat_map(|a| if a.covering_user_ids.is_empty() { vec![a.slack_user_id] } else { a.covering_user_ids })
+            .collect();
+
+        for replacement in &schedule_override.replacement_user_ids {
+            match assignments.iter_mut().find(|a| &a.slack_user_id == replacement) {
+                Some(existing) => existing.covering_user_ids.extend(covering_user_ids.clone()),
+                None => assignments.push(OnCallAssignment { slack_user_id: replacement.clone(), covering_user_ids: covering_user_ids.clone() }),
+            }
+        }
+    }
+
+    assignments
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::overrides::schedule_override::{apply_overrides, OnCallAssignment, ScheduleOverride};
+
+    fn schedule_override(replacements: &[&str], replaced: &[&str], start: i64, end: i64) -> ScheduleOverride {
+        ScheduleOverride {
+            task: "T123:E123#support".to_string(),
+            override_id: format!("{}", start),
+            team: "T123:E123".to_string(),
+            task_id: "support".to_string(),
+            replacement_user_ids: replacements.iter().map(|id| id.to_string()).collect(),
+            replaced_user_ids: replaced.iter().map(|id| id.to_string()).collect(),
+            start_timestamp_utc: start,
+            end_timestamp_utc: end,
+            start_time: "".to_string(),
+            end_time: "".to_string(),
+            created_by_user_id: "U6HHTEST".to_string(),
+            created_by_user_name: "test-user".to_string(),
+            created_at: "".to_string(),
+        }
+    }
+
+    fn assignment(user_id: &str, covering: &[&str]) -> OnCallAssignment {
+        OnCallAssignment { slack_user_id: user_id.to_string(), covering_user_ids: covering.iter().map(|id| id.to_string()).collect() }
+    }
+
+    #[test]
+    fn apply_active_override() {
+        let at = Utc.with_ymd_and_hms(2023, 4, 4, 10, 0, 0).unwrap();
+        let on_call = vec!["BOB".to_string(), "CAROL".to_string()];
+        let overrides = vec![
+            schedule_override(&["ALICE"], &["BOB"], at.timestamp() - 3600, at.timestamp() + 3600),
+            schedule_override(&["DAVE"], &["CAROL"], at.timestamp() + 3600, at.timestamp() + 7200),
+        ];
+
+        let assignments = apply_overrides(&on_call, &overrides, &at);
+
+        assert_eq!(assignments, vec![assignment("CAROL", &[]), assignment("ALICE", &["BOB"])]);
+        assert_eq!(assignments[1].to_slack_message(), "<@ALICE> (covering <@BOB>)");
+    }
+
+    #[test]
+    fn ignore_override_when_replaced_user_is_not_on_call() {
+        let at = Utc.with_ymd_and_hms(2023, 4, 4, 10, 0, 0).unwrap();
+        let on_call = vec!["CAROL".to_string()];
+        let overrides = vec![schedule_override(&["ALICE"], &["BOB"], at.timestamp(), at.timestamp() + 3600)];
+
+        assert_eq!(apply_overrides(&on_call, &overrides, &at), vec![assignment("CAROL", &[])]);
+    }
+
+    #[test]
+    fn chained_overrides_keep_original_assignee() {
+        let at = Utc.with_ymd_and_hms(2023, 4, 4, 10, 0, 0).unwrap();
+        let on_call = vec!["BOB".to_string()];
+        let overrides = vec![
+            schedule_override(&["ALICE"], &["BOB"], at.timestamp() - 3600, at.timestamp() + 3600),
+            schedule_override(&["DAVE", "ERIN"], &["ALICE"], at.timestamp() - 60, at.timestamp() + 60),
+        ];
+
+        assert_eq!(apply_overrides(&on_call, &overrides, &at), vec![assignment("DAVE", &["BOB"]), assignment("ERIN", &["BOB"])]);
+    }
+}
@@ -0,0 +1,11 @@
+mod rotation;
+mod rotations_dynamodb;
+mod rotations_in_memory;
+mod rotations_repository;
+mod rotations_sql;
+
+pub use rotation::{Rotation, RotationCadence};
+pub use rotations_dynamodb::RotationsDynamodb;
+pub use rotations_in_memory::RotationsInMemory;
+pub use rotations_repository::RotationsRepository;
+pub use rotations_sql::RotationsSql;
@@ -0,0 +1,229 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use derive_more::Display;
+
+use crate::{cron::{count_schedules_between, get_next_schedule_from, is_valid_cron}, errors::AppError, service_provider::schedule_provider::{OnCallUser, ScheduleProvider}};
+
+/**
+ * When the next people of a rotation take over
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum RotationCadence {
+    #[display("daily")]
+    Daily,
+
+    #[display("weekly")]
+    Weekly,
+
+    // Hand over at every scheduled time of the cron, in the timezone of the rotation
+    #[display("{}", _0)]
+    Cron(String),
+}
+
+impl FromStr for RotationCadence {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "daily" => Ok(RotationCadence::Daily),
+            "weekly" => Ok(RotationCadence::Weekly),
+            _ if is_valid_cron(s) => Ok(RotationCadence::Cron(s.to_string())),
+            _ => Err(AppError::RotationError(format!("Unknown cadence: {}, expecting daily, weekly or a cron expression", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rotation {
+    pub team: String, // Partition Key
+    pub name: String, // Sort Key
+
+    // Slack user ids, in the order of taking the shifts
+    pub members: Vec<String>,
+    pub cadence: RotationCadence,
+    pub timezone: String,
+    pub start_at: String,
+    pub shift_size: usize,
+
+    pub created_by_user_id: String,
+    pub created_by_user_name: String,
+    pub created_at: String,
+    pub last_updated_at: String,
+}
+
+impl Rotation {
+    fn start(&self) -> Result<DateTime<Tz>, AppError> {
+        let timezone = Tz::from_str(&self.timezone)
+            .map_err(|_| AppError::RotationError(format!("Unknown timezone of rotation {}: {}", self.name, self.timezone)))?;
+
+        DateTime::parse_from_rfc3339(&self.start_at)
+            .map(|start| start.with_timezone(&timezone))
+            .map_err(|_| AppError::RotationError(format!("Invalid start time of rotation {}: {}", self.name, self.start_at)))
+    }
+
+    /**
+     * The number of handoffs since the start of the rotation, None if the rotation hasn't started yet
+     */
+    pub fn shift_index_at(&self, at: &DateTime<Utc>) -> Result<Option<usize>, AppError> {
+        let start = self.start()?;
+        let at = at.with_timezone(&start.timezone());
+        if at < start {
+            return Ok(None);
+        }
+
+        // Handoffs happen at the local time of the start, regardless of daylight saving changes
+        let mut days = (at.date_naive() - start.date_naive()).num_days();
+        if at.time() < start.time() {
+            days -= 1;
+        }
+
+        let index = match &self.cadence {
+            RotationCadence::Daily => days as usize,
+            RotationCadence::Weekly => (days / 7) as usize,
+            RotationCadence::Cron(cron) => count_schedules_between(cron, &start, &at),
+        };
+
+        Ok(Some(index))
+    }
+
+    /**
+     * Return the Slack user ids on shift at the given time
+     */
+    pub fn on_call_members_at(&self, at: &DateTime<Utc>) -> Result<Vec<String>, AppError> {
+        match self.shift_index_at(at)? {
+            Some(index) if !self.members.is_empty() => {
+                let shift_size = self.shift_size.clamp(1, self.members.len());
+                let first = (index * shift_size) % self.members.len();
+
+                Ok(self.members.iter().cycle().skip(first).take(shift_size).cloned().collect())
+            },
+            _ => Ok(vec![]),
+        }
+    }
+
+    pub fn next_handoff_after(&self, at: &DateTime<Utc>) -> Result<Option<DateTime<Tz>>, AppError> {
+        let start = self.start()?;
+        let index = match self.shift_index_at(at)? {
+            Some(index) => index,
+            None => return Ok(Some(start)),
+        };
+
+        let days = match &self.cadence {
+            RotationCadence::Daily => index + 1,
+            RotationCadence::Weekly => (index + 1) * 7,
+            RotationCadence::Cron(cron) => {
+                return Ok(get_next_schedule_from(cron, &at.with_timezone(&start.timezone())).map(|next| next.next_datetime))
+            },
+        };
+
+        let handoff = start.naive_local() + Duration::days(days as i64);
+        Ok(start.timezone().from_local_datetime(&handoff).earliest())
+    }
+}
+
+#[async_trait]
+impl ScheduleProvider for Rotation {
+    async fn get_on_call_users(&self, at: DateTime<Utc>) -> Result<Vec<OnCallUser>, AppError> {
+        Ok(self.on_call_members_at(&at)?.into_iter()
+            .map(|user_id| OnCallUser { name: user_id.clone(), email: "".to_string(), slack_user_id: Some(user_id) })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    use crate::errors::AppError;
+    use crate::rotations::rotation::{Rotation, RotationCadence};
+
+    fn rotation(cadence: &str, shift_size: usize) -> Rotation {
+        Rotation {
+            team: "T123:E123".to_string(),
+            name: "support".to_string(),
+            members: vec!["U1".to_string(), "U2".to_string(), "U3".to_string()],
+            cadence: RotationCadence::from_str(cadence).unwrap(),
+            timezone: "Australia/Melbourne".to_string(),
+            start_at: "2023-03-27T09:00:00+11:00".to_string(), // Monday
+            shift_size,
+
+            created_by_user_id: "U6HHTEST".to_string(),
+            created_by_user_name: "test-user".to_string(),
+            created_at: "2023-03-01T00:00:00Z".to_string(),
+            last_updated_at: "2023-03-01T00:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn daily_rotation_hands_over_at_local_start_time() {
+        let melbourne_tz = Tz::from_str("Australia/Melbourne").unwrap();
+        let rotation = rotation("daily", 1);
+
+        let before_start = melbourne_tz.with_ymd_and_hms(2023, 3, 27, 8, 59, 0).unwrap().with_timezone(&Utc);
+        assert!(rotation.on_call_members_at(&before_start).unwrap().is_empty());
+
+        let first_day = melbourne_tz.with_ymd_and_hms(2023, 3, 27, 9, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(rotation.on_call_members_at(&first_day).unwrap(), vec!["U1"]);
+
+        // Daylight saving ends on 2 April, the handoff stays at 9am local time
+        let before_handoff = melbourne_tz.with_ymd_and_hms(2023, 4, 3, 8, 59, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(rotation.on_call_members_at(&before_handoff).unwrap(), vec!["U1"]);
+        let after_handoff = melbourne_tz.with_ymd_and_hms(2023, 4, 3, 9, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(rotation.on_call_members_at(&after_handoff).unwrap(), vec!["U2"]);
+
+        assert_eq!(rotation.next_handoff_after(&before_handoff).unwrap(), Some(melbourne_tz.with_ymd_and_hms(2023, 4, 3, 9, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn weekly_rotation_with_multiple_people_on_shift() {
+        let melbourne_tz = Tz::from_str("Australia/Melbourne").unwrap();
+        let rotation = rotation("WEEKLY", 2);
+
+        let first_week = melbourne_tz.with_ymd_and_hms(2023, 4, 2, 12, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(rotation.on_call_members_at(&first_week).unwrap(), vec!["U1", "U2"]);
+
+        let second_week = melbourne_tz.with_ymd_and_hms(2023, 4, 3, 9, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(rotation.on_call_members_at(&second_week).unwrap(), vec!["U3", "U1"]);
+
+        let third_week = melbourne_tz.with_ymd_and_hms(2023, 4, 10, 9, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(rotation.on_call_members_at(&third_week).unwrap(), vec!["U2", "U3"]);
+    }
+
+    #[test]
+    fn cron_rotation_hands_over_on_weekdays() {
+        let melbourne_tz = Tz::from_str("Australia/Melbourne").unwrap();
+        let rotation = rotation("0 9 ? * MON-FRI *", 1);
+
+        let monday = melbourne_tz.with_ymd_and_hms(2023, 3, 27, 10, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(rotation.on_call_members_at(&monday).unwrap(), vec!["U1"]);
+
+        let tuesday = melbourne_tz.with_ymd_and_hms(2023, 3, 28, 10, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(rotation.on_call_members_at(&tuesday).unwrap(), vec!["U2"]);
+
+        // Friday's shift covers the weekend
+        let sunday = melbourne_tz.with_ymd_and_hms(2023, 4, 2, 10, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(rotation.on_call_members_at(&sunday).unwrap(), vec!["U2"]);
+        assert_eq!(rotation.next_handoff_after(&sunday).unwrap(), Some(melbourne_tz.with_ymd_and_hms(2023, 4, 3, 9, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn fail_on_invalid_start_time() {
+        let rotation = Rotation { start_at: "next monday".to_string(), ..rotation("daily", 1) };
+
+        assert!(matches!(rotation.on_call_members_at(&Utc::now()), Err(AppError::RotationError(_))));
+        assert!(matches!(rotation.next_handoff_after(&Utc::now()), Err(AppError::RotationError(_))));
+    }
+
+    #[test]
+    fn parse_cadence() {
+        assert_eq!(RotationCadence::from_str("Daily").unwrap(), RotationCadence::Daily);
+        assert_eq!(RotationCadence::from_str("0 9 ? * MON *").unwrap(), RotationCadence::Cron("0 9 ? * MON *".to_string()));
+        assert!(RotationCadence::from_str("fortnightly").is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use std::{collections::HashMap, str::FromStr};
+
+use async_trait::async_trait;
+use aws_config::SdkConfig;
+use aws_sdk_dynamodb::{Client, types::AttributeValue};
+
+use crate::errors::AppError;
+use crate::db::dynamodb_client::{get_attribute, get_list_attribute, get_required_attribute};
+
+use super::rotation::{Rotation, RotationCadence};
+use super::rotations_repository::RotationsRepository;
+
+pub struct RotationsDynamodb {
+    client: Client,
+    table_name: String,
+}
+
+impl RotationsDynamodb {
+    pub fn new(config: &SdkConfig, table_name: String) -> RotationsDynamodb {
+        RotationsDynamodb{ client: Client::new(config), table_name }
+    }
+
+    fn to_rotation(&self, item: &HashMap<String, AttributeValue>) -> Result<Rotation, AppError> {
+        Ok(Rotation {
+            team: get_attribute(item, "team"),
+            name: get_attribute(item, "name"),
+            members: get_list_attribute(item, "members"),
+            cadence: RotationCadence::from_str(&get_required_attribute(item, "cadence")?)?,
+            timezone: get_attribute(item, "timezone"),
+            start_at: get_attribute(item, "start_at"),
+            shift_size: get_required_attribute(item, "shift_size")?.parse::<usize>()
+                .map_err(|_| AppError::RotationError(format!("Invalid shift size of rotation: {}", get_attribute(item, "name"))))?,
+
+            created_by_user_id: get_attribute(item, "created_by_user_id"),
+            created_by_user_name: get_attribute(item, "created_by_user_name"),
+            created_at: get_attribute(item, "created_at"),
+            last_updated_at: get_attribute(item, "last_updated_at"),
+        })
+    }
+}
+
+#[async_trait]
+impl RotationsRepository for RotationsDynamodb {
+    async fn save_rotation(&self, rotation: &Rotation) -> Result<(), AppError> {
+        let r = rotation.clone();
+        let members = r.members.into_iter().map(AttributeValue::S).collect();
+
+        let builder = self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("team", AttributeValue::S(r.team))
+            .item("name", AttributeValue::S(r.name))
+            .item("members", AttributeValue::L(members))
+            .item("cadence", AttributeValue::S(r.cadence.to_string()))
+            .item("timezone", AttributeValue::S(r.timezone))
+            .item("start_at", AttributeValue::S(r.start_at))
+            .item("shift_size", AttributeValue::N(r.shift_size.to_string()))
+
+            .item("created_by_user_id", AttributeValue::S(r.created_by_user_id))
+            .item("created_by_user_name", AttributeValue::S(r.created_by_user_name))
+            .item("created_at", AttributeValue::S(r.created_at))
+            .item("last_updated_at", AttributeValue::S(r.last_updated_at))
+        ;
+
+        println!("Saving rotation {} with {} members", rotation.name, rotation.members.len());
+        builder.send().await?;
+
+        Ok(())
+    }
+
+    async fn get_rotation(&self, team: &str, name: &str) -> Result<Option<Rotation>, AppError> {
+        let output = self.client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("team", AttributeValue::S(team.to_string()))
+            .key("name", AttributeValue::S(name.to_string()))
+            .send()
+            .await?;
+
+        output.item.map(|item| self.to_rotation(&item)).transpose()
+    }
+}
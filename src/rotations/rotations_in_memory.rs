@@ -0,0 +1,36 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use async_trait::async_trait;
+
+use crate::errors::AppError;
+
+use super::rotation::Rotation;
+use super::rotations_repository::RotationsRepository;
+
+/**
+ * Keep the rotations in memory, for running and testing without AWS
+ */
+#[derive(Default)]
+pub struct RotationsInMemory {
+    // Keyed by the team and the name of the rotation
+    rotations: Mutex<BTreeMap<(String, String), Rotation>>,
+}
+
+impl RotationsInMemory {
+    pub fn new() -> RotationsInMemory {
+        RotationsInMemory::default()
+    }
+}
+
+#[async_trait]
+impl RotationsRepository for RotationsInMemory {
+    async fn save_rotation(&self, rotation: &Rotation) -> Result<(), AppError> {
+        self.rotations.lock().unwrap().insert((rotation.team.clone(), rotation.name.clone()), rotation.clone());
+
+        Ok(())
+    }
+
+    async fn get_rotation(&self, team: &str, name: &str) -> Result<Option<Rotation>, AppError> {
+        Ok(self.rotations.lock().unwrap().get(&(team.to_string(), name.to_string())).cloned())
+    }
+}
@@ -0,0 +1,15 @@
+use async_trait::async_trait;
+
+use crate::errors::AppError;
+
+use super::rotation::Rotation;
+
+/**
+ * The storage of the built-in rotations, which are unique by name in a team
+ */
+#[async_trait]
+pub trait RotationsRepository: Send + Sync {
+    async fn save_rotation(&self, rotation: &Rotation) -> Result<(), AppError>;
+
+    async fn get_rotation(&self, team: &str, name: &str) -> Result<Option<Rotation>, AppError>;
+}
@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use sqlx::{any::AnyRow, AnyPool, Row};
+
+use crate::errors::AppError;
+use crate::db::sql_client::{from_json_list, to_json_list};
+
+use super::rotation::{Rotation, RotationCadence};
+use super::rotations_repository::RotationsRepository;
+
+pub struct RotationsSql {
+    pool: AnyPool,
+}
+
+impl RotationsSql {
+    pub fn new(pool: AnyPool) -> RotationsSql {
+        RotationsSql { pool }
+    }
+
+    fn to_rotation(&self, row: &AnyRow) -> Result<Rotation, AppError> {
+        Ok(Rotation {
+            team: row.get("team"),
+            name: row.get("name"),
+            members: from_json_list(&row.get::<String, _>("members"))?,
+            cadence: RotationCadence::from_str(&row.get::<String, _>("cadence"))?,
+            timezone: row.get("timezone"),
+            start_at: row.get("start_at"),
+            shift_size: row.get::<i64, _>("shift_size") as usize,
+
+            created_by_user_id: row.get("created_by_user_id"),
+            created_by_user_name: row.get("created_by_user_name"),
+            created_at: row.get("created_at"),
+            last_updated_at: row.get("last_updated_at"),
+        })
+    }
+}
+
+#[async_trait]
+impl RotationsRepository for RotationsSql {
+    async fn save_rotation(&self, rotation: &Rotation) -> Result<(), AppError> {
+        let r = rotation.clone();
+
+        println!("Saving rotation {} with {} members", rotation.name, rotation.members.len());
+        sqlx::query(r#"
+            INSERT INTO rotations (team, name, members, cadence, timezone, start_at, shift_size, created_by_user_id, created_by_user_name, created_at, last_updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            ON CONFLICT (team, name) DO UPDATE SET
+                members = excluded.members,
+                cadence = excluded.cadence,
+                timezone = excluded.timezone,
+                start_at = excluded.start_at,
+                shift_size = excluded.shift_size,
+                last_updated_at = excluded.last_updated_at
+        "#)
+            .bind(r.team)
+            .bind(r.name)
+            .bind(to_json_list(&r.members))
+            .bind(r.cadence.to_string())
+            .bind(r.timezone)
+            .bind(r.start_at)
+            .bind(r.shift_size as i64)
+            .bind(r.created_by_user_id)
+            .bind(r.created_by_user_name)
+            .bind(r.created_at)
+            .bind(r.last_updated_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_rotation(&self, team: &str, name: &str) -> Result<Option<Rotation>, AppError> {
+        let row = sqlx::query("SELECT * FROM rotations WHERE team = $1 AND name = $2")
+            .bind(team.to_string())
+            .bind(name.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| self.to_rotation(&row)).transpose()
+    }
+}
@@ -0,0 +1,256 @@
+use std::{collections::HashMap, str::FromStr};
+
+use chrono::NaiveTime;
+use chrono_tz::{Tz, TZ_VARIANTS};
+use serde_json::{json, Value};
+
+use crate::{cron::is_valid_cron, service_provider::{pager_duty::PagerDutySchedule, slack::UserGroup}};
+
+pub const NEW_SCHEDULE_CALLBACK_ID: &str = "new_schedule";
+pub const TIMEZONE_ACTION_ID: &str = "timezone";
+
+// Slack allows up to 100 options in a select menu
+const MAX_SELECT_OPTIONS: usize = 100;
+const WEEKDAYS: [(&str, &str); 7] = [
+    ("MON", "Monday"), ("TUE", "Tuesday"), ("WED", "Wednesday"), ("THU", "Thursday"), ("FRI", "Friday"), ("SAT", "Saturday"), ("SUN", "Sunday"),
+];
+
+fn option(text: &str, value: &str) -> Value {
+    json!({ "text": { "type": "plain_text", "text": text }, "value": value })
+}
+
+fn input_block(block_id: &str, label: &str, element: Value, optional: bool) -> Value {
+    json!({
+        "type": "input",
+        "block_id": block_id,
+        "label": { "type": "plain_text", "text": label },
+        "element": element,
+        "optional": optional,
+    })
+}
+
+/**
+ * The modal to add a new schedule, see https://api.slack.com/reference/surfaces/views
+ *
+ * The channel of the slash command is kept in the private metadata, as it's not part of the view submission.
+ */
+pub fn new_schedule_view(user_groups: &[UserGroup], pagerduty_schedules: &[PagerDutySchedule], private_metadata: &str) -> Value {
+    let user_group_options: Vec<Value> = user_groups.iter()
+        .take(MAX_SELECT_OPTIONS)
+        .map(|g| option(&format!("@{} ({})", g.handle, g.name), &format!("{}|{}", g.id, g.handle)))
+        .collect();
+
+    // Fall back to input the schedule id if the schedules couldn't be listed, e.g. no PagerDuty api key setup yet
+    let pagerduty_schedule_element = if pagerduty_schedules.is_empty() {
+        json!({ "type": "plain_text_input", "action_id": "pagerduty_schedule", "placeholder": { "type": "plain_text", "text": "PagerDuty schedule id, e.g. P123ABC" } })
+    } else {
+        let options: Vec<Value> = pagerduty_schedules.iter().take(MAX_SELECT_OPTIONS).map(|s| option(&s.name, &s.id)).collect();
+        json!({ "type": "static_select", "action_id": "pagerduty_schedule", "options": options })
+    };
+
+    let weekday_options: Vec<Value> = WEEKDAYS.iter().map(|(value, text)| option(text, value)).collect();
+    let working_days: Vec<Value> = weekday_options[..5].to_vec();
+
+    json!({
+        "type": "modal",
+        "callback_id": NEW_SCHEDULE_CALLBACK_ID,
+        "private_metadata": private_metadata,
+        "title": { "type": "plain_text", "text": "New on-call schedule" },
+        "submit": { "type": "plain_text", "text": "Create" },
+        "close": { "type": "plain_text", "text": "Cancel" },
+        "blocks": [
+            input_block("user_group", "User group", json!({ "type": "static_select", "action_id": "user_group", "options": user_group_options }), false),
+            input_block("pagerduty_schedule", "PagerDuty schedule", pagerduty_schedule_element, false),
+            input_block("time", "Update at", json!({ "type": "timepicker", "action_id": "time", "initial_time": "09:00" }), false),
+            input_block("weekdays", "On", json!({ "type": "checkboxes", "action_id": "weekdays", "options": weekday_options, "initial_options": working_days }), false),
+            input_block("timezone", "Timezone", json!({
+                "type": "external_select",
+                "action_id": TIMEZONE_ACTION_ID,
+                "min_query_length": 2,
+                "initial_option": option("UTC", "UTC"),
+            }), false),
+            input_block("cron", "Cron", json!({
+                "type": "plain_text_input",
+                "action_id": "cron",
+                "placeholder": { "type": "plain_text", "text": "Replaces the time and days above, e.g. 0 9 ? * MON-FRI *" },
+            }), true),
+        ],
+    })
+}
+
+/**
+ * The modal opened straight away, since the trigger id expires in 3 seconds, and updated once the options are listed
+ */
+pub fn loading_view() -> Value {
+    json!({
+        "type": "modal",
+        "title": { "type": "plain_text", "text": "New on-call schedule" },
+        "close": { "type": "plain_text", "text": "Cancel" },
+        "blocks": [
+            { "type": "section", "text": { "type": "mrkdwn", "text": ":hourglass_flowing_sand: Loading the user groups and schedules..." } },
+        ],
+    })
+}
+
+/**
+ * Options of the timezone dropdown matching the text typed by the user
+ */
+pub fn timezone_options(query: &str) -> Value {
+    let query = query.to_ascii_lowercase();
+    let options: Vec<Value> = TZ_VARIANTS.iter()
+        .map(|tz| tz.name())
+        .filter(|name| name.to_ascii_lowercase().contains(&query))
+        .take(MAX_SELECT_OPTIONS)
+        .map(|name| option(name, name))
+        .collect();
+
+    json!({ "options": options })
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NewScheduleSubmission {
+    pub user_group_id: String,
+    pub user_group_handle: String,
+    pub pagerduty_schedule_id: String,
+    pub cron: String,
+    pub timezone: Tz,
+}
+
+fn selected_value<'a>(values: &'a Value, block_id: &str) -> Option<&'a str> {
+    let element = &values[block_id][block_id];
+    element["selected_option"]["value"].as_str()
+        .or(element["selected_time"].as_str())
+        .or(element["value"].as_str())
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+}
+
+/**
+ * Validate the state values of the submitted view, the errors are keyed by the block id to show in the modal
+ */
+pub fn parse_new_schedule_submission(values: &Value) -> Result<NewScheduleSubmission, HashMap<String, String>> {
+    let mut errors = HashMap::new();
+
+    let user_group = selected_value(values, "user_group").and_then(|value| value.split_once('|'));
+    if user_group.is_none() {
+        errors.insert("user_group".to_string(), "Please choose a user group".to_string());
+    }
+
+    let pagerduty_schedule_id = selected_value(values, "pagerduty_schedule");
+    if pagerduty_schedule_id.is_none() {
+        errors.insert("pagerduty_schedule".to_string(), "Please choose a PagerDuty schedule".to_string());
+    }
+
+    let timezone = selected_value(values, "timezone").and_then(|value| Tz::from_str(value).ok());
+    if timezone.is_none() {
+        errors.insert("timezone".to_string(), "Please choose a timezone".to_string());
+    }
+
+    let cron = match selected_value(values, "cron") {
+        Some(cron) if is_valid_cron(cron) => Some(cron.to_string()),
+        Some(_) => {
+            errors.insert("cron".to_string(), "Invalid cron expression, e.g. 0 9 ? * MON-FRI *".to_string());
+            None
+        },
+        None => weekly_cron(values, &mut errors),
+    };
+
+    match (user_group, pagerduty_schedule_id, cron, timezone) {
+        (Some((user_group_id, user_group_handle)), Some(pagerduty_schedule_id), Some(cron), Some(timezone)) if errors.is_empty() => Ok(NewScheduleSubmission {
+            user_group_id: user_group_id.to_string(),
+            user_group_handle: user_group_handle.to_string(),
+            pagerduty_schedule_id: pagerduty_schedule_id.to_string(),
+            cron,
+            timezone,
+        }),
+        _ => Err(errors),
+    }
+}
+
+// Build the cron expression from the time picker and the weekday checkboxes
+fn weekly_cron(values: &Value, errors: &mut HashMap<String, String>) -> Option<String> {
+    let time = selected_value(values, "time").and_then(|time| NaiveTime::parse_from_str(time, "%H:%M").ok());
+    if time.is_none() {
+        errors.insert("time".to_string(), "Please choose the time to update the user group".to_string());
+    }
+
+    let selected_days: Vec<&str> = values["weekdays"]["weekdays"]["selected_options"].as_array()
+        .map(|options| options.iter().filter_map(|o| o["value"].as_str()).collect())
+        .unwrap_or_default();
+    let weekdays: Vec<&str> = WEEKDAYS.iter().map(|(day, _)| *day).filter(|day| selected_days.contains(day)).collect();
+    if weekdays.is_empty() {
+        errors.insert("weekdays".to_string(), "Please choose at least one day".to_string());
+    }
+
+    time.filter(|_| !weekdays.is_empty())
+        .map(|time| format!("{} {} ? * {} *", time.format("%-M"), time.format("%-H"), weekdays.join(",")))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+    use serde_json::json;
+
+    use crate::schedule_wizard::{loading_view, new_schedule_view, parse_new_schedule_submission, timezone_options, NewScheduleSubmission};
+
+    fn submitted_values(cron: Option<&str>) -> serde_json::Value {
+        json!({
+            "user_group": { "user_group": { "type": "static_select", "selected_option": { "value": "S123|support" } } },
+            "pagerduty_schedule": { "pagerduty_schedule": { "type": "plain_text_input", "value": " P123ABC " } },
+            "time": { "time": { "type": "timepicker", "selected_time": "09:30" } },
+            "weekdays": { "weekdays": { "type": "checkboxes", "selected_options": [{ "value": "FRI" }, { "value": "MON" }] } },
+            "timezone": { "timezone": { "type": "external_select", "selected_option": { "value": "Australia/Melbourne" } } },
+            "cron": { "cron": { "type": "plain_text_input", "value": cron } },
+        })
+    }
+
+    #[test]
+    fn parse_submission_with_time_and_weekdays() {
+        let submission = parse_new_schedule_submission(&submitted_values(None)).unwrap();
+
+        assert_eq!(submission, NewScheduleSubmission {
+            user_group_id: "S123".to_string(),
+            user_group_handle: "support".to_string(),
+            pagerduty_schedule_id: "P123ABC".to_string(),
+            cron: "30 9 ? * MON,FRI *".to_string(),
+            timezone: Tz::Australia__Melbourne,
+        });
+    }
+
+    #[test]
+    fn parse_submission_with_cron() {
+        let submission = parse_new_schedule_submission(&submitted_values(Some("0 8 ? * TUE *"))).unwrap();
+        assert_eq!(submission.cron, "0 8 ? * TUE *");
+
+        let errors = parse_new_schedule_submission(&submitted_values(Some("every tuesday"))).unwrap_err();
+        assert!(errors.contains_key("cron"));
+    }
+
+    #[test]
+    fn reject_incomplete_submission() {
+        let mut values = submitted_values(None);
+        values["weekdays"]["weekdays"]["selected_options"] = json!([]);
+        values["user_group"]["user_group"]["selected_option"] = json!(null);
+
+        let errors = parse_new_schedule_submission(&values).unwrap_err();
+
+        let mut blocks: Vec<&String> = errors.keys().collect();
+        blocks.sort();
+        assert_eq!(blocks, vec!["user_group", "weekdays"]);
+    }
+
+    #[test]
+    fn build_view_and_timezone_options() {
+        let view = new_schedule_view(&[], &[], r#"{"channel_id":"C123"}"#);
+        assert_eq!(view["blocks"][1]["element"]["type"], "plain_text_input");
+        assert_eq!(view["private_metadata"], r#"{"channel_id":"C123"}"#);
+
+        // The loading view can't be submitted before it's updated with the inputs
+        let loading = loading_view();
+        assert_eq!(loading["title"], view["title"]);
+        assert!(loading.get("submit").is_none());
+
+        let options = timezone_options("melb");
+        assert_eq!(options["options"][0]["value"], "Australia/Melbourne");
+    }
+}
@@ -1,11 +1,21 @@
 mod scheduled_task;
 mod scheduled_tasks_dynamodb;
+mod scheduled_tasks_in_memory;
+mod scheduled_tasks_repository;
+mod scheduled_tasks_sql;
+mod scheduler;
 mod scheduler_event_bridge;
+mod scheduler_in_process;
 
 #[cfg(test)]
 mod scheduled_tasks_dynamodb_test;
 
-pub use scheduled_task::ScheduledTask;
+pub use scheduled_task::{generate_task_id, is_short_task_id, GuardRails, MissingUserPolicy, ScheduledTask, TaskTrigger, TASK_CLAIM_SECONDS};
 pub use scheduled_tasks_dynamodb::ScheduledTasksDynamodb;
+pub use scheduled_tasks_in_memory::ScheduledTasksInMemory;
+pub use scheduled_tasks_repository::ScheduledTasksRepository;
+pub use scheduled_tasks_sql::ScheduledTasksSql;
 
-pub use scheduler_event_bridge::EventBridgeScheduler;
+pub use scheduler::{build_scheduler, Scheduler};
+pub use scheduler_event_bridge::{EventBridgeScheduler, EventBridgeSchedule};
+pub use scheduler_in_process::InProcessScheduler;
@@ -1,15 +1,142 @@
-use chrono::{DateTime, Utc};
-use clap::Args;
+use std::{collections::BTreeSet, str::FromStr};
 
-use crate::{cron::{get_next_schedule_from, CronSchedule}, timestamp::get_timezone};
+use chrono::{DateTime, Duration, Utc};
+use derive_more::Display;
 
-#[derive(Debug, Args, Clone)]
+use crate::{errors::AppError, handoff_notification::HandoffNotification, cron::{count_schedules_between, get_next_schedule_from, one_off_schedule, CronSchedule}, service_provider::schedule_provider::{ScheduleProviderConfig, ScheduleProviderKind}, timestamp::get_timezone};
+
+const TASK_ID_PREFIX: &str = "ocs-";
+pub(super) const MAX_TASK_ID_ATTEMPTS: usize = 5;
+
+// Longer than the updater Lambda timeout
+pub const TASK_CLAIM_SECONDS: i64 = 15 * 60;
+
+// A run later than this after its scheduled time is recorded as missed
+const MISSED_RUN_GRACE_SECONDS: i64 = 15 * 60;
+
+/**
+ * Generate a short task id which is easy to type in Slack, the uniqueness is checked when saving the task
+ */
+pub fn generate_task_id() -> String {
+    format!("{}{:04x}", TASK_ID_PREFIX, rand::random::<u16>())
+}
+
+pub fn is_short_task_id(task_id: &str) -> bool {
+    task_id.starts_with(TASK_ID_PREFIX)
+}
+
+/**
+ * What to do when an on-call user of the schedule provider isn't found in Slack by email
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display)]
+pub enum MissingUserPolicy {
+    // Update the user group without the user, and warn in the channel
+    #[default]
+    #[display("skip")]
+    Skip,
+
+    // Fail the task without updating the user group
+    #[display("fail")]
+    Fail,
+
+    // Look up the email aliases of the workspace, and skip the user if there is no alias
+    #[display("alias")]
+    Alias,
+}
+
+impl FromStr for MissingUserPolicy {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "skip" => Ok(MissingUserPolicy::Skip),
+            "fail" => Ok(MissingUserPolicy::Fail),
+            "alias" => Ok(MissingUserPolicy::Alias),
+            _ => Err(AppError::ScheduledTaskError(format!("Unknown missing user policy: {}, expecting skip, fail or alias", s))),
+        }
+    }
+}
+
+/**
+ * What the task does when it's due, the reminders are sent some hours before updating the user group
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display)]
+pub enum TaskTrigger {
+    #[default]
+    #[display("update")]
+    UpdateUserGroup,
+
+    // Remind the users joining the group at the next update of their shift
+    #[display("reminder")]
+    Reminder,
+}
+
+impl FromStr for TaskTrigger {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "update" => Ok(TaskTrigger::UpdateUserGroup),
+            "reminder" => Ok(TaskTrigger::Reminder),
+            _ => Err(AppError::ScheduledTaskError(format!("Unknown task trigger: {}, expecting update or reminder", s))),
+        }
+    }
+}
+
+/**
+ * The limits of the updated user group, the update is refused when any of them is exceeded
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuardRails {
+    // Never empty the group by default, e.g. when nobody is on call in the schedule
+    pub min_group_size: usize,
+    pub max_group_size: Option<usize>,
+
+    // The most users added to and removed from the group in a run
+    pub max_churn: Option<usize>,
+}
+
+impl Default for GuardRails {
+    fn default() -> GuardRails {
+        GuardRails { min_group_size: 1, max_group_size: None, max_churn: None }
+    }
+}
+
+impl GuardRails {
+    /**
+     * The reason to refuse changing the user group from the current users to the new users, if any limit is exceeded
+     */
+    pub fn refusal_reason(&self, current_user_ids: &[String], new_user_ids: &[String]) -> Option<String> {
+        if new_user_ids.len() < self.min_group_size {
+            return Some(format!("{} users on call, fewer than the minimum group size {}", new_user_ids.len(), self.min_group_size));
+        }
+
+        if let Some(max_group_size) = self.max_group_size.filter(|max| new_user_ids.len() > *max) {
+            return Some(format!("{} users on call, more than the maximum group size {}", new_user_ids.len(), max_group_size));
+        }
+
+        let added = new_user_ids.iter().filter(|id| !current_user_ids.contains(id)).count();
+        let removed = current_user_ids.iter().filter(|id| !new_user_ids.contains(id)).count();
+        if let Some(max_churn) = self.max_churn.filter(|max| added + removed > *max) {
+            return Some(format!("{} users added and {} removed, more than the maximum churn {}", added, removed, max_churn));
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ScheduledTask {
     pub team: String, // Partition Key
-    pub task_id: String, // Sort Key
+    pub task_id: String, // Sort Key, a short id like ocs-7f3a
 
+    // The colon-joined task id used before the short ids, kept to find the migrated tasks
+    pub legacy_task_id: Option<String>,
+
+    // The next time the task is due, either to update the user group or to send the reminders
     pub next_update_timestamp_utc: i64,
     pub next_update_time: String,
+    pub next_trigger: TaskTrigger,
 
     pub team_id: String,
     pub team_domain: String,
@@ -21,10 +148,40 @@ pub struct ScheduledTask {
 
     pub user_group_id: String,
     pub user_group_handle: String,
-    pub pager_duty_schedule_id: String,
-    pub pager_duty_token: Option<String>,
+    pub provider_config: ScheduleProviderConfig,
     pub cron: String,
     pub timezone: String,
+    pub missing_user_policy: MissingUserPolicy,
+    pub guard_rails: GuardRails,
+    pub notification: HandoffNotification,
+
+    // A paused task doesn't update the user group, until it's resumed manually or at the resume time
+    pub paused: bool,
+    pub resume_timestamp_utc: Option<i64>,
+    pub resume_time: Option<String>,
+
+    // The scheduled runs missed by the updater for longer than the grace period, e.g. while the scheduler was down,
+    // and the due time of the latest late run
+    pub missed_runs: i64,
+    pub last_missed_time: Option<String>,
+
+    // The runs failed in a row since the latest successful run, and the time and error of the latest failure
+    pub failed_runs: i64,
+    pub last_failed_time: Option<String>,
+    pub last_error: Option<String>,
+
+    // Incremented on every claim and update of the next schedule, so overlapping updaters don't run the same task.
+    // A claim expires in case the updater crashes while running the task
+    pub version: i64,
+    pub claimed_until_timestamp_utc: Option<i64>,
+
+    // The members of the user group after the latest run, and the members before they last changed, sorted by the user id
+    pub member_ids: Vec<String>,
+    pub previous_member_ids: Vec<String>,
+    pub members_changed_time: Option<String>,
+
+    // The first handoff message, which the next handoff messages reply to when posting in the thread
+    pub notification_thread_ts: Option<String>,
     
     pub created_by_user_id: String,
     pub created_by_user_name: String,
@@ -33,8 +190,296 @@ pub struct ScheduledTask {
 }
 
 impl ScheduledTask {
+    pub fn provider(&self) -> ScheduleProviderKind {
+        self.provider_config.kind()
+    }
+
+    /**
+     * Whether the task is still paused at the given time
+     */
+    pub fn is_paused_at(&self, at: &DateTime<Utc>) -> bool {
+        self.paused && self.resume_timestamp_utc.map(|resume_at| at.timestamp() < resume_at).unwrap_or(true)
+    }
+
     pub fn calculate_next_schedule(&self, from_utc: &DateTime<Utc>) -> Option<CronSchedule> {
         let timezone = get_timezone(&self.timezone);
         get_next_schedule_from(&self.cron, &from_utc.with_timezone(&timezone))
     }
+
+    pub fn is_claimed_at(&self, at: &DateTime<Utc>) -> bool {
+        self.claimed_until_timestamp_utc.is_some_and(|claimed_until| at.timestamp() < claimed_until)
+    }
+
+    /**
+     * Record the scheduled updates between the due time of the task and the given time, which weren't run in the grace period
+     */
+    pub fn record_missed_runs(&mut self, at: &DateTime<Utc>) {
+        if self.next_update_timestamp_utc < 0 {
+            return;
+        }
+
+        let timezone = get_timezone(&self.timezone);
+        let Some(trigger_at) = DateTime::from_timestamp(self.next_update_timestamp_utc, 0) else {
+            return;
+        };
+
+        // The pending trigger may be the reminder before the update, only the updates of the user group are counted
+        let due_at = match self.next_trigger {
+            TaskTrigger::Reminder => match self.calculate_next_schedule(&trigger_at) {
+                Some(next_update) => next_update.next_datetime,
+                None => return,
+            },
+            TaskTrigger::UpdateUserGroup => trigger_at.with_timezone(&timezone),
+        };
+        let missed_until = *at - Duration::seconds(MISSED_RUN_GRACE_SECONDS);
+
+        let missed_runs = count_schedules_between(&self.cron, &(due_at - Duration::seconds(1)), &missed_until.with_timezone(&timezone));
+        if missed_runs > 0 {
+            println!("Task {} missed {} runs since {}", self.task_id, missed_runs, due_at.to_rfc3339());
+            self.missed_runs += missed_runs as i64;
+            self.last_missed_time = Some(due_at.to_rfc3339());
+        }
+    }
+
+    /**
+     * Record a failed run, the failed runs are counted until the next successful run
+     */
+    pub fn record_failure(&mut self, err: &AppError, at: &DateTime<Utc>) {
+        self.failed_runs += 1;
+        self.last_failed_time = Some(at.to_rfc3339());
+        self.last_error = Some(err.to_string());
+    }
+
+    /**
+     * Record the members of the user group after a run, the previous members are only replaced when the members change
+     */
+    pub fn record_members(&mut self, previous_member_ids: &BTreeSet<String>, member_ids: &BTreeSet<String>, at: &DateTime<Utc>) {
+        if previous_member_ids != member_ids {
+            self.previous_member_ids = previous_member_ids.iter().cloned().collect();
+            self.members_changed_time = Some(at.to_rfc3339());
+        }
+        self.member_ids = member_ids.iter().cloned().collect();
+    }
+
+    /**
+     * The first reminder after the given time, which is the reminder hours before a scheduled update of the user group
+     */
+    fn calculate_next_reminder(&self, from_utc: &DateTime<Utc>) -> Option<CronSchedule> {
+        let hours = Duration::hours(self.notification.remind_before_hours? as i64);
+        let next_update = self.calculate_next_schedule(&(*from_utc + hours))?;
+
+        Some(one_off_schedule(&(next_update.next_datetime - hours)))
+    }
+
+    /**
+     * The next trigger of the task after the given time, the reminder is only earlier when it's enabled
+     */
+    pub fn calculate_next_trigger(&self, from_utc: &DateTime<Utc>) -> Option<(TaskTrigger, CronSchedule)> {
+        let next_update = self.calculate_next_schedule(from_utc)?;
+
+        match self.calculate_next_reminder(from_utc) {
+            Some(reminder) if reminder.next_timestamp_utc < next_update.next_timestamp_utc => Some((TaskTrigger::Reminder, reminder)),
+            _ => Some((TaskTrigger::UpdateUserGroup, next_update)),
+        }
+    }
+
+    /**
+     * Move the next trigger of the task to the next scheduled time after the given time
+     */
+    pub fn set_next_schedule_from(&mut self, from_utc: &DateTime<Utc>) {
+        if let Some((next_trigger, next_schedule)) = self.calculate_next_trigger(from_utc) {
+            self.next_update_timestamp_utc = next_schedule.next_timestamp_utc;
+            self.next_update_time = next_schedule.next_datetime.to_rfc3339();
+            self.next_trigger = next_trigger;
+        } else {
+            self.next_update_timestamp_utc = -1;
+            self.next_update_time = "".to_string();
+            self.next_trigger = TaskTrigger::UpdateUserGroup;
+        }
+    }
+}
+
+/**
+ * A task posting to C123 of workspace T123 every weekday, for tests to override the fields they care about
+ */
+#[cfg(test)]
+impl Default for ScheduledTask {
+    fn default() -> Self {
+        ScheduledTask {
+            team: "T123:E123".to_string(),
+            task_id: "ocs-0001".to_string(),
+            legacy_task_id: None,
+            next_update_timestamp_utc: 0,
+            next_update_time: "".to_string(),
+            next_trigger: TaskTrigger::UpdateUserGroup,
+
+            team_id: "T123".to_string(),
+            team_domain: "test".to_string(),
+            channel_id: "C123".to_string(),
+            channel_name: "support".to_string(),
+            enterprise_id: "E123".to_string(),
+            enterprise_name: "test".to_string(),
+            is_enterprise_install: false,
+
+            user_group_id: "S123".to_string(),
+            user_group_handle: "support".to_string(),
+            provider_config: ScheduleProviderConfig::Rotation { name: "support".to_string() },
+            cron: "0 9 ? * MON-FRI *".to_string(),
+            timezone: "UTC".to_string(),
+            missing_user_policy: MissingUserPolicy::Skip,
+            guard_rails: GuardRails::default(),
+            notification: HandoffNotification::default(),
+
+            paused: false,
+            resume_timestamp_utc: None,
+            resume_time: None,
+            missed_runs: 0,
+            last_missed_time: None,
+            failed_runs: 0,
+            last_failed_time: None,
+            last_error: None,
+
+            version: 0,
+            claimed_until_timestamp_utc: None,
+
+            member_ids: vec![],
+            previous_member_ids: vec![],
+            members_changed_time: None,
+            notification_thread_ts: None,
+
+            created_by_user_id: "U6HHTEST".to_string(),
+            created_by_user_name: "test-user".to_string(),
+            created_at: "".to_string(),
+            last_updated_at: "".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::scheduled_tasks::{generate_task_id, is_short_task_id, GuardRails, MissingUserPolicy, ScheduledTask, TaskTrigger};
+
+    fn task(paused: bool, resume_timestamp_utc: Option<i64>) -> ScheduledTask {
+        ScheduledTask { task_id: "support".to_string(), paused, resume_timestamp_utc, ..Default::default() }
+    }
+
+    #[test]
+    fn paused_until_resume_time() {
+        let now = Utc.with_ymd_and_hms(2023, 4, 4, 10, 0, 0).unwrap();
+
+        assert!(!task(false, None).is_paused_at(&now));
+        assert!(task(true, None).is_paused_at(&now));
+        assert!(task(true, Some(now.timestamp() + 1)).is_paused_at(&now));
+        assert!(!task(true, Some(now.timestamp())).is_paused_at(&now));
+    }
+
+    #[test]
+    fn record_missed_runs() {
+        let mut late_task = task(false, None);
+        late_task.next_update_timestamp_utc = Utc.with_ymd_and_hms(2023, 4, 3, 9, 0, 0).unwrap().timestamp();
+        late_task.next_update_time = "2023-04-03T09:00:00+00:00".to_string();
+
+        late_task.record_missed_runs(&Utc.with_ymd_and_hms(2023, 4, 3, 9, 10, 0).unwrap());
+        assert_eq!(late_task.missed_runs, 0);
+
+        // Monday and Tuesday are missed when running on Wednesday
+        late_task.record_missed_runs(&Utc.with_ymd_and_hms(2023, 4, 5, 8, 0, 0).unwrap());
+        assert_eq!(late_task.missed_runs, 2);
+        assert_eq!(late_task.last_missed_time, Some("2023-04-03T09:00:00+00:00".to_string()));
+    }
+
+    #[test]
+    fn record_missed_runs_after_reminder() {
+        let mut late_task = task(false, None);
+        late_task.notification.remind_before_hours = Some(2);
+        late_task.next_trigger = TaskTrigger::Reminder;
+        late_task.next_update_timestamp_utc = Utc.with_ymd_and_hms(2023, 4, 3, 7, 0, 0).unwrap().timestamp();
+        late_task.next_update_time = "2023-04-03T07:00:00+00:00".to_string();
+
+        // The late reminder isn't a missed run, while the update after it is still to come
+        late_task.record_missed_runs(&Utc.with_ymd_and_hms(2023, 4, 3, 7, 30, 0).unwrap());
+        assert_eq!(late_task.missed_runs, 0);
+
+        // The updates on Monday and Tuesday are missed, instead of the reminder on Monday
+        late_task.record_missed_runs(&Utc.with_ymd_and_hms(2023, 4, 5, 8, 0, 0).unwrap());
+        assert_eq!(late_task.missed_runs, 2);
+        assert_eq!(late_task.last_missed_time, Some("2023-04-03T09:00:00+00:00".to_string()));
+    }
+
+    #[test]
+    fn parse_missing_user_policy() {
+        assert_eq!("skip".parse::<MissingUserPolicy>().unwrap(), MissingUserPolicy::Skip);
+        assert_eq!("FAIL".parse::<MissingUserPolicy>().unwrap(), MissingUserPolicy::Fail);
+        assert_eq!(MissingUserPolicy::Alias.to_string().parse::<MissingUserPolicy>().unwrap(), MissingUserPolicy::Alias);
+        assert!("ignore".parse::<MissingUserPolicy>().is_err());
+    }
+
+    #[test]
+    fn refuse_changes_beyond_guard_rails() {
+        let users = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<String>>();
+        let guard_rails = GuardRails { min_group_size: 1, max_group_size: Some(3), max_churn: Some(2) };
+
+        assert_eq!(guard_rails.refusal_reason(&users(&["U1", "U2"]), &users(&["U2", "U3"])), None);
+        assert_eq!(guard_rails.refusal_reason(&users(&["U1"]), &users(&[])), Some("0 users on call, fewer than the minimum group size 1".to_string()));
+        assert_eq!(guard_rails.refusal_reason(&users(&["U1"]), &users(&["U1", "U2", "U3", "U4"])), Some("4 users on call, more than the maximum group size 3".to_string()));
+        assert_eq!(guard_rails.refusal_reason(&users(&["U1", "U2"]), &users(&["U3", "U4"])), Some("2 users added and 2 removed, more than the maximum churn 2".to_string()));
+
+        // Only emptying the group is refused by default
+        assert_eq!(GuardRails::default().refusal_reason(&users(&["U1", "U2"]), &users(&["U3", "U4", "U5", "U6"])), None);
+        assert!(GuardRails::default().refusal_reason(&users(&["U1"]), &users(&[])).is_some());
+    }
+
+    #[test]
+    fn record_members_on_change() {
+        let users = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<BTreeSet<String>>();
+        let at = Utc.with_ymd_and_hms(2023, 4, 3, 9, 0, 0).unwrap();
+        let mut task = task(false, None);
+
+        task.record_members(&users(&["U2", "U1"]), &users(&["U3"]), &at);
+        assert_eq!(task.member_ids, vec!["U3"]);
+        assert_eq!(task.previous_member_ids, vec!["U1", "U2"]);
+        assert_eq!(task.members_changed_time, Some("2023-04-03T09:00:00+00:00".to_string()));
+
+        // The previous members are kept when nothing changed in the next run
+        task.record_members(&users(&["U3"]), &users(&["U3"]), &(at + chrono::Duration::days(1)));
+        assert_eq!(task.previous_member_ids, vec!["U1", "U2"]);
+        assert_eq!(task.members_changed_time, Some("2023-04-03T09:00:00+00:00".to_string()));
+    }
+
+    #[test]
+    fn trigger_reminders_before_updates() {
+        let mut task = task(false, None);
+        task.cron = "0 9 ? * MON *".to_string();
+        let monday = Utc.with_ymd_and_hms(2023, 4, 3, 9, 0, 0).unwrap();
+
+        task.set_next_schedule_from(&Utc.with_ymd_and_hms(2023, 4, 1, 0, 0, 0).unwrap());
+        assert_eq!((task.next_trigger, task.next_update_timestamp_utc), (TaskTrigger::UpdateUserGroup, monday.timestamp()));
+
+        task.notification.remind_before_hours = Some(12);
+        task.set_next_schedule_from(&Utc.with_ymd_and_hms(2023, 4, 1, 0, 0, 0).unwrap());
+        assert_eq!((task.next_trigger, task.next_update_time.as_str()), (TaskTrigger::Reminder, "2023-04-02T21:00:00+00:00"));
+
+        // The update follows the reminder, then the reminder of the next week
+        task.set_next_schedule_from(&Utc.with_ymd_and_hms(2023, 4, 2, 21, 0, 0).unwrap());
+        assert_eq!((task.next_trigger, task.next_update_timestamp_utc), (TaskTrigger::UpdateUserGroup, monday.timestamp()));
+
+        task.set_next_schedule_from(&monday);
+        assert_eq!((task.next_trigger, task.next_update_time.as_str()), (TaskTrigger::Reminder, "2023-04-09T21:00:00+00:00"));
+
+        assert_eq!("reminder".parse::<TaskTrigger>().unwrap(), TaskTrigger::Reminder);
+        assert_eq!(TaskTrigger::UpdateUserGroup.to_string().parse::<TaskTrigger>().unwrap(), TaskTrigger::UpdateUserGroup);
+    }
+
+    #[test]
+    fn generate_short_task_id() {
+        let task_id = generate_task_id();
+
+        assert_eq!(task_id.len(), 8);
+        assert!(is_short_task_id(&task_id));
+        assert!(!is_short_task_id("support:C123:support:S123:P123ABC"));
+    }
 }
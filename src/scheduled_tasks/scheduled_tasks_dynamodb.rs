@@ -1,10 +1,28 @@
+use std::{collections::HashMap, str::FromStr};
+
+use async_trait::async_trait;
 use aws_config::SdkConfig;
-use aws_sdk_dynamodb::{Client, types::AttributeValue};
+use chrono::{DateTime, Utc};
+use aws_sdk_dynamodb::{Client, operation::put_item::builders::PutItemFluentBuilder, types::AttributeValue};
+
+use crate::{errors::AppError, encryptor::Encryptor, service_provider::schedule_provider::ScheduleProviderConfig};
+use crate::db::dynamodb_client::{get_list_attribute, get_optional_attribute, get_required_attribute};
+
+use super::scheduled_task::{generate_task_id, GuardRails, MissingUserPolicy, ScheduledTask, TaskTrigger, MAX_TASK_ID_ATTEMPTS, TASK_CLAIM_SECONDS};
+use super::scheduled_tasks_repository::{skip_unreadable_tasks, ScheduledTasksRepository};
 
-use crate::{errors::AppError, encryptor::{Encryptor, EncryptedData}};
-use crate::db::dynamodb_client::{get_attribute, get_optional_attribute};
+// The tasks are spread over a few partitions of the index on the next update time, to avoid a hot partition
+const NEXT_UPDATE_INDEX: &str = "next_update_index";
+const SCHEDULE_BUCKETS: u32 = 4;
 
-use super::scheduled_task::ScheduledTask;
+fn string_list(values: &[String]) -> AttributeValue {
+    AttributeValue::L(values.iter().cloned().map(AttributeValue::S).collect())
+}
+
+fn schedule_bucket(task_id: &str) -> String {
+    let sum: u32 = task_id.bytes().map(u32::from).sum();
+    (sum % SCHEDULE_BUCKETS).to_string()
+}
 
 pub struct ScheduledTasksDynamodb {
     client: Client,
@@ -14,29 +32,30 @@ pub struct ScheduledTasksDynamodb {
 
 impl ScheduledTasksDynamodb {
     pub fn new(config: &SdkConfig, table_name: String, encryptor: Encryptor) -> ScheduledTasksDynamodb {
-        ScheduledTasksDynamodb{ client: Client::new(&config), table_name, encryptor }
+        ScheduledTasksDynamodb{ client: Client::new(config), table_name, encryptor }
     }
    
     fn team(&self, team_id: &str, workspace_id: &str) -> String {
         format!("{}:{}", team_id, workspace_id)
     }
 
-    pub async fn save_scheduled_task(&self, task: &ScheduledTask) -> Result<(), AppError> {
+    fn put_item_request(&self, task: &ScheduledTask) -> Result<PutItemFluentBuilder, AppError> {
         let t = task.clone();
 
-        let encrypted_pagerduty_token_json = t.pager_duty_token
-            .map(|token| self.encryptor.encrypt(&token).expect("Failed to encrypt PagerDuty api key"))
-            .map(|encrypted| serde_json::to_string(&encrypted).unwrap())
-            .unwrap_or_default()
-        ;
+        // The provider config may contain api keys, so it's always encrypted
+        let provider_config_json = serde_json::to_string(&t.provider_config).unwrap();
+        let encrypted_provider_config = self.encryptor.encrypt(&provider_config_json)?;
+        let encrypted_provider_config_json = serde_json::to_string(&encrypted_provider_config).unwrap();
 
-        let builder = self.client
+        let mut builder = self.client
             .put_item()
             .table_name(&self.table_name)
             .item("team", AttributeValue::S(t.team))
+            .item("schedule_bucket", AttributeValue::S(schedule_bucket(&t.task_id)))
             .item("task_id", AttributeValue::S(t.task_id))
             .item("next_update_timestamp_utc", AttributeValue::N(t.next_update_timestamp_utc.to_string()))
             .item("next_update_time", AttributeValue::S(t.next_update_time))
+            .item("next_trigger", AttributeValue::S(t.next_trigger.to_string()))
 
             .item("team_id", AttributeValue::S(t.team_id))
             .item("team_domain", AttributeValue::S(t.team_domain))
@@ -48,10 +67,19 @@ impl ScheduledTasksDynamodb {
 
             .item("user_group_id", AttributeValue::S(t.user_group_id))
             .item("user_group_handle", AttributeValue::S(t.user_group_handle))
-            .item("pager_duty_schedule_id", AttributeValue::S(t.pager_duty_schedule_id))
-            .item("pager_duty_token", AttributeValue::S(encrypted_pagerduty_token_json))
+            .item("provider", AttributeValue::S(t.provider_config.kind().to_string()))
+            .item("provider_config", AttributeValue::S(encrypted_provider_config_json))
             .item("cron", AttributeValue::S(t.cron))
             .item("timezone", AttributeValue::S(t.timezone))
+            .item("missing_user_policy", AttributeValue::S(t.missing_user_policy.to_string()))
+            .item("min_group_size", AttributeValue::N(t.guard_rails.min_group_size.to_string()))
+            .item("notification", AttributeValue::S(serde_json::to_string(&t.notification).unwrap()))
+            .item("paused", AttributeValue::S(t.paused.to_string()))
+            .item("missed_runs", AttributeValue::N(t.missed_runs.to_string()))
+            .item("failed_runs", AttributeValue::N(t.failed_runs.to_string()))
+            .item("version", AttributeValue::N(t.version.to_string()))
+            .item("member_ids", string_list(&t.member_ids))
+            .item("previous_member_ids", string_list(&t.previous_member_ids))
 
             .item("created_by_user_id", AttributeValue::S(t.created_by_user_id))
             .item("created_by_user_name", AttributeValue::S(t.created_by_user_name))
@@ -59,118 +87,438 @@ impl ScheduledTasksDynamodb {
             .item("last_updated_at", AttributeValue::S(t.last_updated_at))
         ;
 
+        if let (Some(resume_timestamp_utc), Some(resume_time)) = (t.resume_timestamp_utc, t.resume_time) {
+            builder = builder
+                .item("resume_timestamp_utc", AttributeValue::N(resume_timestamp_utc.to_string()))
+                .item("resume_time", AttributeValue::S(resume_time));
+        }
+
+        if let Some(legacy_task_id) = t.legacy_task_id {
+            builder = builder.item("legacy_task_id", AttributeValue::S(legacy_task_id));
+        }
+
+        if let Some(last_missed_time) = t.last_missed_time {
+            builder = builder.item("last_missed_time", AttributeValue::S(last_missed_time));
+        }
+
+        if let (Some(last_failed_time), Some(last_error)) = (t.last_failed_time, t.last_error) {
+            builder = builder
+                .item("last_failed_time", AttributeValue::S(last_failed_time))
+                .item("last_error", AttributeValue::S(last_error));
+        }
+
+        if let Some(max_group_size) = t.guard_rails.max_group_size {
+            builder = builder.item("max_group_size", AttributeValue::N(max_group_size.to_string()));
+        }
+
+        if let Some(max_churn) = t.guard_rails.max_churn {
+            builder = builder.item("max_churn", AttributeValue::N(max_churn.to_string()));
+        }
+
+        if let Some(notification_thread_ts) = t.notification_thread_ts {
+            builder = builder.item("notification_thread_ts", AttributeValue::S(notification_thread_ts));
+        }
+
+        if let Some(members_changed_time) = t.members_changed_time {
+            builder = builder.item("members_changed_time", AttributeValue::S(members_changed_time));
+        }
+
+        if let Some(claimed_until_timestamp_utc) = t.claimed_until_timestamp_utc {
+            builder = builder.item("claimed_until_timestamp_utc", AttributeValue::N(claimed_until_timestamp_utc.to_string()));
+        }
+
+        Ok(builder)
+    }
+
+    /**
+     * Save the schedule bucket of the tasks saved before the index on the next update time, which are never due without it.
+     * Only the bucket is set, without bumping the version, so it's safe to run on every deploy while the updater is running.
+     */
+    pub async fn backfill_schedule_buckets(&self) -> Result<usize, AppError> {
+        let items: Result<Vec<_>, _> = self.client
+            .scan()
+            .table_name(&self.table_name)
+            .filter_expression("attribute_not_exists(schedule_bucket)")
+            .projection_expression("team, task_id")
+            .into_paginator()
+            .items()
+            .send()
+            .collect()
+            .await;
+
+        let mut updated = 0;
+        for item in items? {
+            let task_id = get_required_attribute(&item, "task_id")?;
+            let result = self.client
+                .update_item()
+                .table_name(&self.table_name)
+                .key("team", AttributeValue::S(get_required_attribute(&item, "team")?))
+                .key("task_id", AttributeValue::S(task_id.clone()))
+                .condition_expression("attribute_exists(task_id)")
+                .update_expression("SET schedule_bucket = :bucket")
+                .expression_attribute_values(":bucket", AttributeValue::S(schedule_bucket(&task_id)))
+                .send()
+                .await;
+
+            match result {
+                Ok(_) => updated += 1,
+                Err(err) if err.as_service_error().is_some_and(|e| e.is_conditional_check_failed_exception()) => {
+                    println!("Skipped task {}, which is deleted since it's scanned", task_id);
+                },
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(updated)
+    }
+
+    fn to_scheduled_task(&self, item: &HashMap<String, AttributeValue>) -> Result<ScheduledTask, AppError> {
+        let provider_config = match get_optional_attribute(item, "provider_config") {
+            Some(encrypted_config_json) => {
+                let config_json = self.encryptor.decrypt_json(&encrypted_config_json)?;
+                serde_json::from_str(&config_json)
+                    .map_err(|err| AppError::StorageError(format!("Invalid provider config: {}", err)))?
+            },
+            None => self.legacy_provider_config(item)?,
+        };
+
+        Ok(ScheduledTask {
+            team: get_required_attribute(item, "team")?,
+            task_id: get_required_attribute(item, "task_id")?,
+            legacy_task_id: get_optional_attribute(item, "legacy_task_id"),
+            next_update_timestamp_utc: get_required_attribute(item, "next_update_timestamp_utc")?.parse::<i64>().map_err(AppError::ParseIntError)?,
+            next_update_time: get_required_attribute(item, "next_update_time")?,
+            next_trigger: get_optional_attribute(item, "next_trigger").and_then(|trigger| TaskTrigger::from_str(&trigger).ok()).unwrap_or_default(),
+
+            team_id: get_required_attribute(item, "team_id")?,
+            team_domain: get_required_attribute(item, "team_domain")?,
+            channel_id: get_required_attribute(item, "channel_id")?,
+            channel_name: get_required_attribute(item, "channel_name")?,
+            enterprise_id: get_required_attribute(item, "enterprise_id")?,
+            enterprise_name: get_required_attribute(item, "enterprise_name")?,
+            is_enterprise_install: get_required_attribute(item, "is_enterprise_install")?.eq_ignore_ascii_case("true"),
+
+            user_group_id: get_required_attribute(item, "user_group_id")?,
+            user_group_handle: get_required_attribute(item, "user_group_handle")?,
+            provider_config,
+            cron: get_required_attribute(item, "cron")?,
+            timezone: get_required_attribute(item, "timezone")?,
+            missing_user_policy: get_optional_attribute(item, "missing_user_policy").and_then(|policy| MissingUserPolicy::from_str(&policy).ok()).unwrap_or_default(),
+            guard_rails: GuardRails {
+                min_group_size: get_optional_attribute(item, "min_group_size").and_then(|min| min.parse::<usize>().ok()).unwrap_or(GuardRails::default().min_group_size),
+                max_group_size: get_optional_attribute(item, "max_group_size").and_then(|max| max.parse::<usize>().ok()),
+                max_churn: get_optional_attribute(item, "max_churn").and_then(|max| max.parse::<usize>().ok()),
+            },
+            notification: get_optional_attribute(item, "notification").and_then(|notification| serde_json::from_str(&notification).ok()).unwrap_or_default(),
+
+            paused: get_optional_attribute(item, "paused").map(|paused| paused.eq_ignore_ascii_case("true")).unwrap_or(false),
+            resume_timestamp_utc: get_optional_attribute(item, "resume_timestamp_utc").and_then(|timestamp| timestamp.parse::<i64>().ok()),
+            resume_time: get_optional_attribute(item, "resume_time"),
+
+            missed_runs: get_optional_attribute(item, "missed_runs").and_then(|missed_runs| missed_runs.parse::<i64>().ok()).unwrap_or(0),
+            last_missed_time: get_optional_attribute(item, "last_missed_time"),
+            failed_runs: get_optional_attribute(item, "failed_runs").and_then(|failed_runs| failed_runs.parse::<i64>().ok()).unwrap_or(0),
+            last_failed_time: get_optional_attribute(item, "last_failed_time"),
+            last_error: get_optional_attribute(item, "last_error"),
+
+            version: get_optional_attribute(item, "version").and_then(|version| version.parse::<i64>().ok()).unwrap_or(0),
+            claimed_until_timestamp_utc: get_optional_attribute(item, "claimed_until_timestamp_utc").and_then(|timestamp| timestamp.parse::<i64>().ok()),
+
+            member_ids: get_list_attribute(item, "member_ids"),
+            previous_member_ids: get_list_attribute(item, "previous_member_ids"),
+            members_changed_time: get_optional_attribute(item, "members_changed_time"),
+            notification_thread_ts: get_optional_attribute(item, "notification_thread_ts"),
+
+            created_by_user_id: get_required_attribute(item, "created_by_user_id")?,
+            created_by_user_name: get_required_attribute(item, "created_by_user_name")?,
+            created_at: get_required_attribute(item, "created_at")?,
+            last_updated_at: get_required_attribute(item, "last_updated_at")?,
+        })
+    }
+
+    /**
+     * Tasks saved before the schedule providers were introduced only have the PagerDuty attributes
+     */
+    fn legacy_provider_config(&self, item: &HashMap<String, AttributeValue>) -> Result<ScheduleProviderConfig, AppError> {
+        let api_token = match get_optional_attribute(item, "pager_duty_token") {
+            Some(encrypted_token_json) if !encrypted_token_json.is_empty() => Some(self.encryptor.decrypt_json(&encrypted_token_json)?),
+            _ => None,
+        };
+
+        Ok(ScheduleProviderConfig::PagerDuty {
+            schedule_id: get_required_attribute(item, "pager_duty_schedule_id")?,
+            api_token,
+        })
+    }
+}
+
+#[async_trait]
+impl ScheduledTasksRepository for ScheduledTasksDynamodb {
+    async fn save_scheduled_task(&self, task: &ScheduledTask) -> Result<(), AppError> {
         println!("Saving task {} with the next schedule at {}", task.task_id, task.next_update_time);
-        builder.send().await?;
-        
-        Ok(())
+        let saved_task = ScheduledTask { version: task.version + 1, ..task.clone() };
+        let result = self.put_item_request(&saved_task)?
+            .condition_expression("attribute_not_exists(task_id) OR attribute_not_exists(version) OR version=:version")
+            .expression_attribute_values(":version", AttributeValue::N(task.version.to_string()))
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) if err.as_service_error().is_some_and(|e| e.is_conditional_check_failed_exception()) => {
+                Err(AppError::ConcurrentUpdateError(format!("Version {} of task {} is outdated", task.version, task.task_id)))
+            },
+            Err(err) => Err(err.into()),
+        }
     }
 
-    pub async fn update_next_schedule(&self, task: &ScheduledTask) -> Result<(), AppError> {
+    /**
+     * Save a new task with a generated short task id, which is retried when the id is already taken in the team
+     */
+    async fn create_scheduled_task(&self, task: &mut ScheduledTask) -> Result<(), AppError> {
+        for _ in 0..MAX_TASK_ID_ATTEMPTS {
+            task.task_id = generate_task_id();
+
+            println!("Creating task {} with the next schedule at {}", task.task_id, task.next_update_time);
+            let result = self.put_item_request(task)?
+                .condition_expression("attribute_not_exists(task_id)")
+                .send()
+                .await;
+
+            match result {
+                Ok(_) => return Ok(()),
+                Err(err) if err.as_service_error().is_some_and(|e| e.is_conditional_check_failed_exception()) => {
+                    println!("Task id {} is taken, generating another one", task.task_id);
+                },
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Err(AppError::ScheduledTaskError(format!("Couldn't generate a unique task id in {} attempts", MAX_TASK_ID_ATTEMPTS)))
+    }
+
+    /**
+     * The tasks saved before the versions were introduced have no version, which is the same as version 0
+     */
+    async fn claim_scheduled_task(&self, task: &mut ScheduledTask, at: &DateTime<Utc>) -> Result<bool, AppError> {
+        let claimed_until_timestamp_utc = at.timestamp() + TASK_CLAIM_SECONDS;
+        let result = self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("team", AttributeValue::S(task.team.clone()))
+            .key("task_id", AttributeValue::S(task.task_id.clone()))
+            .update_expression("SET version=:next_version, claimed_until_timestamp_utc=:claimed_until_timestamp_utc")
+            .condition_expression("(attribute_not_exists(version) OR version=:version) AND (attribute_not_exists(claimed_until_timestamp_utc) OR claimed_until_timestamp_utc<=:now)")
+            .expression_attribute_values(":version", AttributeValue::N(task.version.to_string()))
+            .expression_attribute_values(":next_version", AttributeValue::N((task.version + 1).to_string()))
+            .expression_attribute_values(":claimed_until_timestamp_utc", AttributeValue::N(claimed_until_timestamp_utc.to_string()))
+            .expression_attribute_values(":now", AttributeValue::N(at.timestamp().to_string()))
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => {
+                task.version += 1;
+                task.claimed_until_timestamp_utc = Some(claimed_until_timestamp_utc);
+                Ok(true)
+            },
+            Err(err) if err.as_service_error().is_some_and(|e| e.is_conditional_check_failed_exception()) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn update_next_schedule(&self, task: &ScheduledTask) -> Result<(), AppError> {
         let t = task.clone();
-        let builder = self.client
+        let mut builder = self.client
             .update_item()
             .table_name(&self.table_name)
             .key("team", AttributeValue::S(t.team))
             .key("task_id", AttributeValue::S(t.task_id))
-            .update_expression("SET last_updated_at=:last_updated_at, next_update_time=:next_update_time, next_update_timestamp_utc=:next_update_timestamp_utc")
+            .condition_expression("attribute_exists(task_id) AND (attribute_not_exists(version) OR version=:version)")
+            .expression_attribute_values(":version", AttributeValue::N(t.version.to_string()))
+            .expression_attribute_values(":next_version", AttributeValue::N((t.version + 1).to_string()))
             .expression_attribute_values(":last_updated_at", AttributeValue::S(t.last_updated_at))
             .expression_attribute_values(":next_update_time", AttributeValue::S(t.next_update_time))
             .expression_attribute_values(":next_update_timestamp_utc", AttributeValue::N(t.next_update_timestamp_utc.to_string()))
-            
+            .expression_attribute_values(":next_trigger", AttributeValue::S(t.next_trigger.to_string()))
+            .expression_attribute_values(":missed_runs", AttributeValue::N(t.missed_runs.to_string()))
+            .expression_attribute_values(":failed_runs", AttributeValue::N(t.failed_runs.to_string()))
+            .expression_attribute_values(":member_ids", string_list(&t.member_ids))
+            .expression_attribute_values(":previous_member_ids", string_list(&t.previous_member_ids))
         ;
 
+        let mut set_expressions = vec![
+            "version=:next_version", "last_updated_at=:last_updated_at", "next_update_time=:next_update_time", "next_update_timestamp_utc=:next_update_timestamp_utc",
+            "next_trigger=:next_trigger", "missed_runs=:missed_runs", "failed_runs=:failed_runs", "member_ids=:member_ids", "previous_member_ids=:previous_member_ids",
+        ];
+        if let Some(last_missed_time) = t.last_missed_time {
+            set_expressions.push("last_missed_time=:last_missed_time");
+            builder = builder.expression_attribute_values(":last_missed_time", AttributeValue::S(last_missed_time));
+        }
+        if let (Some(last_failed_time), Some(last_error)) = (t.last_failed_time, t.last_error) {
+            set_expressions.push("last_failed_time=:last_failed_time");
+            set_expressions.push("last_error=:last_error");
+            builder = builder
+                .expression_attribute_values(":last_failed_time", AttributeValue::S(last_failed_time))
+                .expression_attribute_values(":last_error", AttributeValue::S(last_error));
+        }
+        if let Some(members_changed_time) = t.members_changed_time {
+            set_expressions.push("members_changed_time=:members_changed_time");
+            builder = builder.expression_attribute_values(":members_changed_time", AttributeValue::S(members_changed_time));
+        }
+        if let Some(notification_thread_ts) = t.notification_thread_ts {
+            set_expressions.push("notification_thread_ts=:notification_thread_ts");
+            builder = builder.expression_attribute_values(":notification_thread_ts", AttributeValue::S(notification_thread_ts));
+        }
+        builder = builder.update_expression(format!("SET {} REMOVE claimed_until_timestamp_utc", set_expressions.join(", ")));
+
         println!("Updating next schedule of task {} to {}", task.task_id, task.next_update_time);
-        builder.send().await?;
-        
-        Ok(())
+        match builder.send().await {
+            Ok(_) => Ok(()),
+            Err(err) if err.as_service_error().is_some_and(|e| e.is_conditional_check_failed_exception()) => {
+                Err(AppError::ConcurrentUpdateError(format!("Version {} of task {} is outdated", task.version, task.task_id)))
+            },
+            Err(err) => Err(err.into()),
+        }
     }
-   
-    pub async fn list_scheduled_tasks_in_workspace(&self, _workspace_id: &String, _workspace_name: &String) -> Result<(), AppError> {
-        // let stream = self.client
-        //     .query()
-        //     .table_name(&self.table_name)
-        //     .into_paginator()
-        //     .items()
-        //     .send();
-
-        // stream
-        //     .flat_map(|item| {
-        //         let id = item
-        //                     .get("id")
-        //                     .and_then(|attr| attr.s.as_ref().map(|s| s.clone()))
-        //                     .unwrap_or_default();
-
-        //         // ScheduledTask {
-                
-        //         // }
-        //     })
-        //     .collect()
-        //     .await?;
-
-        // println!("Items in table:");
-        // for item in items {
-        //     println!("   {:?}", item);
-        // }
-            
-        Ok(())
+
+    async fn update_paused(&self, task: &mut ScheduledTask) -> Result<(), AppError> {
+        let t = task.clone();
+        let mut builder = self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("team", AttributeValue::S(t.team))
+            .key("task_id", AttributeValue::S(t.task_id))
+            .condition_expression("attribute_exists(task_id) AND (attribute_not_exists(version) OR version=:version)")
+            .expression_attribute_values(":version", AttributeValue::N(t.version.to_string()))
+            .expression_attribute_values(":next_version", AttributeValue::N((t.version + 1).to_string()))
+            .expression_attribute_values(":paused", AttributeValue::S(t.paused.to_string()))
+            .expression_attribute_values(":last_updated_at", AttributeValue::S(t.last_updated_at))
+            .expression_attribute_values(":next_update_time", AttributeValue::S(t.next_update_time))
+            .expression_attribute_values(":next_update_timestamp_utc", AttributeValue::N(t.next_update_timestamp_utc.to_string()))
+            .expression_attribute_values(":next_trigger", AttributeValue::S(t.next_trigger.to_string()))
+        ;
+
+        let update_expression = "SET version=:next_version, paused=:paused, last_updated_at=:last_updated_at, next_update_time=:next_update_time, next_update_timestamp_utc=:next_update_timestamp_utc, next_trigger=:next_trigger";
+        builder = match (t.resume_timestamp_utc, t.resume_time) {
+            (Some(resume_timestamp_utc), Some(resume_time)) => builder
+                .update_expression(format!("{}, resume_timestamp_utc=:resume_timestamp_utc, resume_time=:resume_time", update_expression))
+                .expression_attribute_values(":resume_timestamp_utc", AttributeValue::N(resume_timestamp_utc.to_string()))
+                .expression_attribute_values(":resume_time", AttributeValue::S(resume_time)),
+            _ => builder.update_expression(format!("{} REMOVE resume_timestamp_utc, resume_time", update_expression)),
+        };
+
+        println!("Updating task {} to paused: {}, resume at: {:?}", task.task_id, task.paused, task.resume_time);
+        match builder.send().await {
+            Ok(_) => {
+                task.version += 1;
+                Ok(())
+            },
+            Err(err) if err.as_service_error().is_some_and(|e| e.is_conditional_check_failed_exception()) => {
+                Err(AppError::ConcurrentUpdateError(format!("Version {} of task {} is outdated", task.version, task.task_id)))
+            },
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /**
+     * List the tasks of a Slack workspace, by querying the team partition, so the tasks of other workspaces are never read
+     */
+    async fn list_scheduled_tasks_in_workspace(&self, team_id: &str, workspace_id: &str) -> Result<Vec<ScheduledTask>, AppError> {
+        let items: Result<Vec<_>, _> = self.client
+            .query()
+            .table_name(&self.table_name)
+            .key_condition_expression("team = :team")
+            .expression_attribute_values(":team", AttributeValue::S(self.team(team_id, workspace_id)))
+            .into_paginator()
+            .items()
+            .send()
+            .collect()
+            .await;
+
+        Ok(skip_unreadable_tasks(items?.iter().map(|item| self.to_scheduled_task(item))))
     }
 
-    pub async fn list_scheduled_tasks(&self) -> Result<Vec<ScheduledTask>, AppError> {
-        let scan_output = self.client
+    async fn list_scheduled_tasks(&self) -> Result<Vec<ScheduledTask>, AppError> {
+        let items: Result<Vec<_>, _> = self.client
             .scan()
             .table_name(&self.table_name)
+            .into_paginator()
+            .items()
             .send()
-            .await?;
-
-        let items: Vec<ScheduledTask> = scan_output.items.unwrap_or_else(Vec::new)
-            .into_iter()
-            .map(|item| {
-                let pager_duty_token = get_optional_attribute(&item, "pager_duty_token").map(|encrypted_token_json| 
-                    if encrypted_token_json.is_empty() {
-                        None
-                    } else {
-                        let encrypted_token: EncryptedData = serde_json::from_str(&encrypted_token_json).expect("couldn't parse encrypted pagerduty token json");
-
-                        Some(self.encryptor.decrypt(&encrypted_token).expect("failed to decrypt pagerduty token"))
-                    }
-                ).flatten();
-
-                // let encrypted_pagerduty_token: EncryptedData = serde_json::from_str(&pagerduty_token_json).expect("couldn't parse encrypted pagerduty token json");
-                // let pager_duty_token = self.encryptor.decrypt(&encrypted_pagerduty_token).expect("failed to decrypt pagerduty token");
-
-                ScheduledTask {
-                    team: get_attribute(&item, "team"),
-                    task_id: get_attribute(&item, "task_id"),
-                    next_update_timestamp_utc: get_attribute(&item, "next_update_timestamp_utc").parse::<i64>().unwrap(),
-                    next_update_time: get_attribute(&item, "next_update_time"),
-
-                    team_id: get_attribute(&item, "team_id"),
-                    team_domain: get_attribute(&item, "team_domain"),
-                    channel_id: get_attribute(&item, "channel_id"),
-                    channel_name: get_attribute(&item, "channel_name"),
-                    enterprise_id: get_attribute(&item, "enterprise_id"),
-                    enterprise_name: get_attribute(&item, "enterprise_name"),
-                    is_enterprise_install: get_attribute(&item, "is_enterprise_install").eq_ignore_ascii_case("true"),
-
-                    user_group_id: get_attribute(&item, "user_group_id"),
-                    user_group_handle: get_attribute(&item, "user_group_handle"),
-                    pager_duty_schedule_id: get_attribute(&item, "pager_duty_schedule_id"),
-                    pager_duty_token,
-                    cron: get_attribute(&item, "cron"),
-                    timezone: get_attribute(&item, "timezone"),
-
-                    created_by_user_id: get_attribute(&item, "created_by_user_id"),
-                    created_by_user_name: get_attribute(&item, "created_by_user_name"),
-                    created_at: get_attribute(&item, "created_at"),
-                    last_updated_at: get_attribute(&item, "last_updated_at"),
+            .collect()
+            .await;
+
+        Ok(skip_unreadable_tasks(items?.iter().map(|item| self.to_scheduled_task(item))))
+    }
+
+    /**
+     * List the tasks with the next update time up to the given time, from the index of all the schedule buckets
+     */
+    async fn list_due_scheduled_tasks(&self, at: &DateTime<Utc>) -> Result<Vec<ScheduledTask>, AppError> {
+        let mut tasks = vec![];
+        for bucket in 0..SCHEDULE_BUCKETS {
+            let items: Result<Vec<_>, _> = self.client
+                .query()
+                .table_name(&self.table_name)
+                .index_name(NEXT_UPDATE_INDEX)
+                .key_condition_expression("schedule_bucket = :bucket AND next_update_timestamp_utc BETWEEN :from AND :until")
+                .expression_attribute_values(":bucket", AttributeValue::S(bucket.to_string()))
+                // The tasks without a next schedule have a negative timestamp
+                .expression_attribute_values(":from", AttributeValue::N("0".to_string()))
+                .expression_attribute_values(":until", AttributeValue::N(at.timestamp().to_string()))
+                .into_paginator()
+                .items()
+                .send()
+                .collect()
+                .await;
+
+            tasks.extend(skip_unreadable_tasks(items?.iter().map(|item| self.to_scheduled_task(item))));
+        }
+
+        Ok(tasks)
+    }
+
+    /**
+     * Find the task scheduled next after the given time, skipping the tasks paused without a resume time
+     */
+    async fn get_next_scheduled_task(&self, after: &DateTime<Utc>) -> Result<Option<ScheduledTask>, AppError> {
+        let mut next_task: Option<ScheduledTask> = None;
+        for bucket in 0..SCHEDULE_BUCKETS {
+            let mut stream = self.client
+                .query()
+                .table_name(&self.table_name)
+                .index_name(NEXT_UPDATE_INDEX)
+                .key_condition_expression("schedule_bucket = :bucket AND next_update_timestamp_utc > :after")
+                .expression_attribute_values(":bucket", AttributeValue::S(bucket.to_string()))
+                .expression_attribute_values(":after", AttributeValue::N(after.timestamp().to_string()))
+                .into_paginator()
+                .items()
+                .send();
+
+            while let Some(item) = stream.next().await {
+                let task = match self.to_scheduled_task(&item?) {
+                    Ok(task) => task,
+                    Err(err) => {
+                        println!("Skipped unreadable scheduled task, error: {:?}", err);
+                        continue;
+                    },
+                };
+                if task.paused && task.resume_timestamp_utc.is_none() {
+                    continue;
+                }
+
+                if next_task.as_ref().map(|t| task.next_update_timestamp_utc < t.next_update_timestamp_utc).unwrap_or(true) {
+                    next_task = Some(task);
                 }
-            })
-            .collect();
+                break;
+            }
+        }
 
-        Ok(items)
+        Ok(next_task)
     }
 
-    pub async fn delete_scheduled_task(&self, team_id: &str, workspace_id: &str, task_id: &str) -> Result<(), AppError> {
+    async fn delete_scheduled_task(&self, team_id: &str, workspace_id: &str, task_id: &str) -> Result<(), AppError> {
         let request = self.client
             .delete_item()
             .key("team", AttributeValue::S(self.team(team_id, workspace_id)))
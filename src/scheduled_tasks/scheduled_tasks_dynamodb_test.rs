@@ -1,6 +1,7 @@
-use crate::{errors::AppError, scheduled_tasks::ScheduledTask, secrets::SecretsClient, encryptor::Encryptor};
+use crate::{errors::AppError, scheduled_tasks::ScheduledTask, secrets::SecretsClient, encryptor::Encryptor, service_provider::schedule_provider::ScheduleProviderConfig};
 
 use super::scheduled_tasks_dynamodb::ScheduledTasksDynamodb;
+use super::scheduled_tasks_repository::ScheduledTasksRepository;
 use aws_config::BehaviorVersion;
 use chrono::Utc;
 
@@ -21,26 +22,13 @@ async fn save_scheduled_task_with_pagerduty_token_to_db() -> Result<(), AppError
         task_id: "task_id".to_string(),
         next_update_timestamp_utc: Utc::now().timestamp(),
         next_update_time: Utc::now().timestamp().to_string(),
-
-        team_id: format!("workspace_id"),
-        team_domain: "team_domain".to_string(),
-        channel_id: "channel_id".to_string(),
-        channel_name: "channel_name".to_string(),
-        enterprise_id: "enterprise_id".to_string(),
-        enterprise_name: "enterprise_name".to_string(),
-        is_enterprise_install: false,
-
-        user_group_id: "user_group_id".to_string(),
-        user_group_handle: "user_group_handle".to_string(),
-        pager_duty_schedule_id: "pager_duty_schedule_id".to_string(),
-        pager_duty_token: None,
-        cron: "cron".to_string(),
-        timezone: "timezone".to_string(),
-
-        created_by_user_id: "U6HHP84N9".to_string(),
-        created_by_user_name: "test-user".to_string(),
+        provider_config: ScheduleProviderConfig::PagerDuty {
+            schedule_id: "pager_duty_schedule_id".to_string(),
+            api_token: None,
+        },
         created_at: Utc::now().to_rfc3339(),
         last_updated_at: Utc::now().to_rfc3339(),
+        ..Default::default()
     };
     
     let db = create_db().await?;
@@ -56,26 +44,13 @@ async fn save_scheduled_task_without_pagerduty_token_to_db() -> Result<(), AppEr
         task_id: "task_id".to_string(),
         next_update_timestamp_utc: Utc::now().timestamp(),
         next_update_time: Utc::now().timestamp().to_string(),
-
-        team_id: format!("workspace_id"),
-        team_domain: "team_domain".to_string(),
-        channel_id: "channel_id".to_string(),
-        channel_name: "channel_name".to_string(),
-        enterprise_id: "enterprise_id".to_string(),
-        enterprise_name: "enterprise_name".to_string(),
-        is_enterprise_install: false,
-
-        user_group_id: "user_group_id".to_string(),
-        user_group_handle: "user_group_handle".to_string(),
-        pager_duty_schedule_id: "pager_duty_schedule_id".to_string(),
-        pager_duty_token: Some("pager_duty_token".to_string()),
-        cron: "cron".to_string(),
-        timezone: "timezone".to_string(),
-
-        created_by_user_id: "U6HHP84N9".to_string(),
-        created_by_user_name: "test-user".to_string(),
+        provider_config: ScheduleProviderConfig::PagerDuty {
+            schedule_id: "pager_duty_schedule_id".to_string(),
+            api_token: Some("pager_duty_token".to_string()),
+        },
         created_at: Utc::now().to_rfc3339(),
         last_updated_at: Utc::now().to_rfc3339(),
+        ..Default::default()
     };
     
     let db = create_db().await?;
@@ -96,3 +71,27 @@ async fn list_scheduled_task_to_db() -> Result<(), AppError> {
     
     Ok(())
 }
+
+#[tokio::test]
+async fn list_due_scheduled_tasks_from_index() -> Result<(), AppError> {
+    let db = create_db().await?;
+    let now = Utc::now();
+    let tasks = db.list_due_scheduled_tasks(&now).await?;
+
+    assert!(tasks.iter().all(|t| t.next_update_timestamp_utc <= now.timestamp()));
+    println!("Due tasks: {:?}", tasks.iter().map(|t| &t.task_id).collect::<Vec<_>>());
+    println!("Next task: {:?}", db.get_next_scheduled_task(&now).await?.map(|t| t.task_id));
+    
+    Ok(())
+}
+
+#[tokio::test]
+async fn backfill_schedule_buckets_once() -> Result<(), AppError> {
+    let db = create_db().await?;
+    println!("Saved the schedule bucket of {} tasks", db.backfill_schedule_buckets().await?);
+
+    // Every task has a bucket after the first run
+    assert_eq!(db.backfill_schedule_buckets().await?, 0);
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::{collections::{btree_map::Entry, BTreeMap}, sync::Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::errors::AppError;
+
+use super::scheduled_task::{generate_task_id, ScheduledTask, MAX_TASK_ID_ATTEMPTS, TASK_CLAIM_SECONDS};
+use super::scheduled_tasks_repository::ScheduledTasksRepository;
+
+/**
+ * Keep the tasks in memory, for running and testing without AWS
+ */
+#[derive(Default)]
+pub struct ScheduledTasksInMemory {
+    // Keyed by the team and task id
+    tasks: Mutex<BTreeMap<(String, String), ScheduledTask>>,
+}
+
+impl ScheduledTasksInMemory {
+    pub fn new() -> ScheduledTasksInMemory {
+        ScheduledTasksInMemory::default()
+    }
+}
+
+#[async_trait]
+impl ScheduledTasksRepository for ScheduledTasksInMemory {
+    async fn save_scheduled_task(&self, task: &ScheduledTask) -> Result<(), AppError> {
+        let mut tasks = self.tasks.lock().unwrap();
+        let key = (task.team.clone(), task.task_id.clone());
+        if tasks.get(&key).is_some_and(|existing| existing.version != task.version) {
+            return Err(AppError::ConcurrentUpdateError(format!("Version {} of task {} is outdated", task.version, task.task_id)));
+        }
+
+        tasks.insert(key, ScheduledTask { version: task.version + 1, ..task.clone() });
+        Ok(())
+    }
+
+    async fn create_scheduled_task(&self, task: &mut ScheduledTask) -> Result<(), AppError> {
+        let mut tasks = self.tasks.lock().unwrap();
+        for _ in 0..MAX_TASK_ID_ATTEMPTS {
+            task.task_id = generate_task_id();
+
+            if let Entry::Vacant(entry) = tasks.entry((task.team.clone(), task.task_id.clone())) {
+                entry.insert(task.clone());
+                return Ok(());
+            }
+        }
+
+        Err(AppError::ScheduledTaskError(format!("Couldn't generate a unique task id in {} attempts", MAX_TASK_ID_ATTEMPTS)))
+    }
+
+    async fn claim_scheduled_task(&self, task: &mut ScheduledTask, at: &DateTime<Utc>) -> Result<bool, AppError> {
+        let mut tasks = self.tasks.lock().unwrap();
+        match tasks.get_mut(&(task.team.clone(), task.task_id.clone())) {
+            Some(existing) if existing.version == task.version && !existing.is_claimed_at(at) => {
+                existing.version += 1;
+                existing.claimed_until_timestamp_utc = Some(at.timestamp() + TASK_CLAIM_SECONDS);
+
+                task.version = existing.version;
+                task.claimed_until_timestamp_utc = existing.claimed_until_timestamp_utc;
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+
+    async fn update_next_schedule(&self, task: &ScheduledTask) -> Result<(), AppError> {
+        let mut tasks = self.tasks.lock().unwrap();
+        match tasks.get_mut(&(task.team.clone(), task.task_id.clone())) {
+            Some(existing) if existing.version == task.version => {
+                existing.version += 1;
+                existing.claimed_until_timestamp_utc = None;
+                existing.last_updated_at = task.last_updated_at.clone();
+                existing.next_update_time = task.next_update_time.clone();
+                existing.next_update_timestamp_utc = task.next_update_timestamp_utc;
+                existing.next_trigger = task.next_trigger;
+                existing.missed_runs = task.missed_runs;
+                existing.last_missed_time = task.last_missed_time.clone();
+                existing.failed_runs = task.failed_runs;
+                existing.last_failed_time = task.last_failed_time.clone();
+                existing.last_error = task.last_error.clone();
+                existing.member_ids = task.member_ids.clone();
+                existing.previous_member_ids = task.previous_member_ids.clone();
+                existing.members_changed_time = task.members_changed_time.clone();
+                existing.notification_thread_ts = task.notification_thread_ts.clone();
+                Ok(())
+            },
+            _ => Err(AppError::ConcurrentUpdateError(format!("Version {} of task {} is outdated", task.version, task.task_id))),
+        }
+    }
+
+    async fn update_paused(&self, task: &mut ScheduledTask) -> Result<(), AppError> {
+        let mut tasks = self.tasks.lock().unwrap();
+        match tasks.get_mut(&(task.team.clone(), task.task_id.clone())) {
+            Some(existing) if existing.version == task.version => {
+                existing.version += 1;
+                existing.paused = task.paused;
+                existing.resume_timestamp_utc = task.resume_timestamp_utc;
+                existing.resume_time = task.resume_time.clone();
+                existing.last_updated_at = task.last_updated_at.clone();
+                existing.next_update_time = task.next_update_time.clone();
+                existing.next_update_timestamp_utc = task.next_update_timestamp_utc;
+                existing.next_trigger = task.next_trigger;
+
+                task.version = existing.version;
+                Ok(())
+            },
+            _ => Err(AppError::ConcurrentUpdateError(format!("Version {} of task {} is outdated", task.version, task.task_id))),
+        }
+    }
+
+    async fn list_scheduled_tasks_in_workspace(&self, team_id: &str, workspace_id: &str) -> Result<Vec<ScheduledTask>, AppError> {
+        let team = format!("{}:{}", team_id, workspace_id);
+
+        Ok(self.tasks.lock().unwrap().values().filter(|t| t.team == team).cloned().collect())
+    }
+
+    async fn list_scheduled_tasks(&self) -> Result<Vec<ScheduledTask>, AppError> {
+        Ok(self.tasks.lock().unwrap().values().cloned().collect())
+    }
+
+    async fn list_due_scheduled_tasks(&self, at: &DateTime<Utc>) -> Result<Vec<ScheduledTask>, AppError> {
+        Ok(self.tasks.lock().unwrap().values()
+            .filter(|t| 0 <= t.next_update_timestamp_utc && t.next_update_timestamp_utc <= at.timestamp())
+            .cloned()
+            .collect())
+    }
+
+    async fn get_next_scheduled_task(&self, after: &DateTime<Utc>) -> Result<Option<ScheduledTask>, AppError> {
+        Ok(self.tasks.lock().unwrap().values()
+            .filter(|t| t.next_update_timestamp_utc > after.timestamp())
+            .filter(|t| !t.paused || t.resume_timestamp_utc.is_some())
+            .min_by_key(|t| t.next_update_timestamp_utc)
+            .cloned())
+    }
+
+    async fn delete_scheduled_task(&self, team_id: &str, workspace_id: &str, task_id: &str) -> Result<(), AppError> {
+        self.tasks.lock().unwrap().remove(&(format!("{}:{}", team_id, workspace_id), task_id.to_string()));
+
+        Ok(())
+    }
+}
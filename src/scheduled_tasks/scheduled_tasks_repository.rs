@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::errors::AppError;
+
+use super::scheduled_task::ScheduledTask;
+
+/**
+ * Skip the tasks which couldn't be read, e.g. failed to decrypt, so they don't stop the other tasks from running
+ */
+pub(super) fn skip_unreadable_tasks(tasks: impl IntoIterator<Item = Result<ScheduledTask, AppError>>) -> Vec<ScheduledTask> {
+    tasks.into_iter()
+        .filter_map(|task| task.inspect_err(|err| println!("Skipped unreadable scheduled task, error: {:?}", err)).ok())
+        .collect()
+}
+
+/**
+ * The storage of the scheduled tasks, the team is the Slack team and enterprise id joined by a colon
+ */
+#[async_trait]
+pub trait ScheduledTasksRepository: Send + Sync {
+    /**
+     * Save the whole task and bump its version, unless the stored task is changed since it's read.
+     * Return ConcurrentUpdateError when the stored version is different.
+     */
+    async fn save_scheduled_task(&self, task: &ScheduledTask) -> Result<(), AppError>;
+
+    /**
+     * Save a new task with a generated short task id, which is unique in the team
+     */
+    async fn create_scheduled_task(&self, task: &mut ScheduledTask) -> Result<(), AppError>;
+
+    /**
+     * Claim a due task until the claim expires, only one updater gets the claim of the same task version.
+     * Return false when the task is changed or claimed by another updater.
+     */
+    async fn claim_scheduled_task(&self, task: &mut ScheduledTask, at: &DateTime<Utc>) -> Result<bool, AppError>;
+
+    /**
+     * Update the next schedule and release the claim, unless the task is changed since it's read or claimed
+     */
+    async fn update_next_schedule(&self, task: &ScheduledTask) -> Result<(), AppError>;
+
+    /**
+     * Update the paused state and the next schedule, then bump the version of the given task.
+     * Return ConcurrentUpdateError when the task is changed since it's read, e.g. claimed by the updater, or deleted.
+     */
+    async fn update_paused(&self, task: &mut ScheduledTask) -> Result<(), AppError>;
+
+    async fn list_scheduled_tasks_in_workspace(&self, team_id: &str, workspace_id: &str) -> Result<Vec<ScheduledTask>, AppError>;
+
+    async fn list_scheduled_tasks(&self) -> Result<Vec<ScheduledTask>, AppError>;
+
+    /**
+     * List the tasks with the next update time up to the given time
+     */
+    async fn list_due_scheduled_tasks(&self, at: &DateTime<Utc>) -> Result<Vec<ScheduledTask>, AppError>;
+
+    /**
+     * Find the task scheduled next after the given time, skipping the tasks paused without a resume time
+     */
+    async fn get_next_scheduled_task(&self, after: &DateTime<Utc>) -> Result<Option<ScheduledTask>, AppError>;
+
+    async fn delete_scheduled_task(&self, team_id: &str, workspace_id: &str, task_id: &str) -> Result<(), AppError>;
+}
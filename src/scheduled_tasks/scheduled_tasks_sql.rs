@@ -0,0 +1,345 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use sqlx::{any::AnyRow, query::Query, Any, AnyPool, Row};
+
+use crate::{encryptor::Encryptor, errors::AppError};
+use crate::db::sql_client::{from_json_list, to_json_list};
+
+use super::scheduled_task::{generate_task_id, GuardRails, MissingUserPolicy, ScheduledTask, TaskTrigger, MAX_TASK_ID_ATTEMPTS, TASK_CLAIM_SECONDS};
+use super::scheduled_tasks_repository::{skip_unreadable_tasks, ScheduledTasksRepository};
+
+const INSERT_TASK: &str = r#"
+    INSERT INTO scheduled_tasks (
+        team, task_id, legacy_task_id, next_update_timestamp_utc, next_update_time, next_trigger,
+        team_id, team_domain, channel_id, channel_name, enterprise_id, enterprise_name, is_enterprise_install,
+        user_group_id, user_group_handle, provider, provider_config, cron, timezone, missing_user_policy, min_group_size, max_group_size, max_churn, notification,
+        paused, resume_timestamp_utc, resume_time, missed_runs, last_missed_time, failed_runs, last_failed_time, last_error, version, claimed_until_timestamp_utc,
+        member_ids, previous_member_ids, members_changed_time, notification_thread_ts,
+        created_by_user_id, created_by_user_name, created_at, last_updated_at
+    ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38, $39, $40, $41, $42)
+"#;
+
+pub struct ScheduledTasksSql {
+    pool: AnyPool,
+    encryptor: Encryptor,
+}
+
+impl ScheduledTasksSql {
+    pub fn new(pool: AnyPool, encryptor: Encryptor) -> ScheduledTasksSql {
+        ScheduledTasksSql { pool, encryptor }
+    }
+
+    fn bind_task<'q>(&self, query: Query<'q, Any, <Any as sqlx::Database>::Arguments<'q>>, task: &ScheduledTask) -> Result<Query<'q, Any, <Any as sqlx::Database>::Arguments<'q>>, AppError> {
+        let t = task.clone();
+
+        // The provider config may contain api keys, so it's always encrypted
+        let provider_config_json = serde_json::to_string(&t.provider_config).unwrap();
+
+        Ok(query
+            .bind(t.team)
+            .bind(t.task_id)
+            .bind(t.legacy_task_id)
+            .bind(t.next_update_timestamp_utc)
+            .bind(t.next_update_time)
+            .bind(t.next_trigger.to_string())
+            .bind(t.team_id)
+            .bind(t.team_domain)
+            .bind(t.channel_id)
+            .bind(t.channel_name)
+            .bind(t.enterprise_id)
+            .bind(t.enterprise_name)
+            .bind(t.is_enterprise_install as i64)
+            .bind(t.user_group_id)
+            .bind(t.user_group_handle)
+            .bind(t.provider_config.kind().to_string())
+            .bind(self.encryptor.encrypt_to_json(&provider_config_json)?)
+            .bind(t.cron)
+            .bind(t.timezone)
+            .bind(t.missing_user_policy.to_string())
+            .bind(t.guard_rails.min_group_size as i64)
+            .bind(t.guard_rails.max_group_size.map(|max| max as i64))
+            .bind(t.guard_rails.max_churn.map(|max| max as i64))
+            .bind(serde_json::to_string(&t.notification).unwrap())
+            .bind(t.paused as i64)
+            .bind(t.resume_timestamp_utc)
+            .bind(t.resume_time)
+            .bind(t.missed_runs)
+            .bind(t.last_missed_time)
+            .bind(t.failed_runs)
+            .bind(t.last_failed_time)
+            .bind(t.last_error)
+            .bind(t.version)
+            .bind(t.claimed_until_timestamp_utc)
+            .bind(to_json_list(&t.member_ids))
+            .bind(to_json_list(&t.previous_member_ids))
+            .bind(t.members_changed_time)
+            .bind(t.notification_thread_ts)
+            .bind(t.created_by_user_id)
+            .bind(t.created_by_user_name)
+            .bind(t.created_at)
+            .bind(t.last_updated_at))
+    }
+
+    fn to_scheduled_task(&self, row: &AnyRow) -> Result<ScheduledTask, AppError> {
+        let task_id: String = row.try_get("task_id")?;
+        let provider_config_json = self.encryptor.decrypt_json(&row.try_get::<String, _>("provider_config")?)
+            .map_err(|err| AppError::StorageError(format!("Couldn't decrypt provider config of task {}, error: {:?}", task_id, err)))?;
+        let provider_config = serde_json::from_str(&provider_config_json)
+            .map_err(|err| AppError::StorageError(format!("Invalid provider config of task {}: {}", task_id, err)))?;
+
+        Ok(ScheduledTask {
+            team: row.try_get("team")?,
+            task_id,
+            legacy_task_id: row.try_get("legacy_task_id")?,
+            next_update_timestamp_utc: row.try_get("next_update_timestamp_utc")?,
+            next_update_time: row.try_get("next_update_time")?,
+            next_trigger: TaskTrigger::from_str(&row.try_get::<String, _>("next_trigger")?).unwrap_or_default(),
+
+            team_id: row.try_get("team_id")?,
+            team_domain: row.try_get("team_domain")?,
+            channel_id: row.try_get("channel_id")?,
+            channel_name: row.try_get("channel_name")?,
+            enterprise_id: row.try_get("enterprise_id")?,
+            enterprise_name: row.try_get("enterprise_name")?,
+            is_enterprise_install: row.try_get::<i64, _>("is_enterprise_install")? != 0,
+
+            user_group_id: row.try_get("user_group_id")?,
+            user_group_handle: row.try_get("user_group_handle")?,
+            provider_config,
+            cron: row.try_get("cron")?,
+            timezone: row.try_get("timezone")?,
+            missing_user_policy: MissingUserPolicy::from_str(&row.try_get::<String, _>("missing_user_policy")?).unwrap_or_default(),
+            guard_rails: GuardRails {
+                min_group_size: row.try_get::<i64, _>("min_group_size")? as usize,
+                max_group_size: row.get::<Option<i64>, _>("max_group_size").map(|max| max as usize),
+                max_churn: row.get::<Option<i64>, _>("max_churn").map(|max| max as usize),
+            },
+            notification: serde_json::from_str(&row.try_get::<String, _>("notification")?).unwrap_or_default(),
+
+            paused: row.try_get::<i64, _>("paused")? != 0,
+            resume_timestamp_utc: row.try_get("resume_timestamp_utc")?,
+            resume_time: row.try_get("resume_time")?,
+
+            missed_runs: row.try_get("missed_runs")?,
+            last_missed_time: row.try_get("last_missed_time")?,
+            failed_runs: row.try_get("failed_runs")?,
+            last_failed_time: row.try_get("last_failed_time")?,
+            last_error: row.try_get("last_error")?,
+
+            version: row.try_get("version")?,
+            claimed_until_timestamp_utc: row.try_get("claimed_until_timestamp_utc")?,
+
+            member_ids: from_json_list(&row.try_get::<String, _>("member_ids")?)?,
+            previous_member_ids: from_json_list(&row.try_get::<String, _>("previous_member_ids")?)?,
+            members_changed_time: row.try_get("members_changed_time")?,
+            notification_thread_ts: row.try_get("notification_thread_ts")?,
+
+            created_by_user_id: row.try_get("created_by_user_id")?,
+            created_by_user_name: row.try_get("created_by_user_name")?,
+            created_at: row.try_get("created_at")?,
+            last_updated_at: row.try_get("last_updated_at")?,
+        })
+    }
+}
+
+#[async_trait]
+impl ScheduledTasksRepository for ScheduledTasksSql {
+    async fn save_scheduled_task(&self, task: &ScheduledTask) -> Result<(), AppError> {
+        println!("Saving task {} with the next schedule at {}", task.task_id, task.next_update_time);
+
+        let mut transaction = self.pool.begin().await?;
+        let deleted = sqlx::query("DELETE FROM scheduled_tasks WHERE team = $1 AND task_id = $2 AND version = $3")
+            .bind(task.team.clone())
+            .bind(task.task_id.clone())
+            .bind(task.version)
+            .execute(&mut *transaction)
+            .await?;
+
+        // Nothing is deleted for a new task, or a task changed since it's read
+        if deleted.rows_affected() == 0 {
+            let existing = sqlx::query("SELECT version FROM scheduled_tasks WHERE team = $1 AND task_id = $2")
+                .bind(task.team.clone())
+                .bind(task.task_id.clone())
+                .fetch_optional(&mut *transaction)
+                .await?;
+
+            if existing.is_some() {
+                return Err(AppError::ConcurrentUpdateError(format!("Version {} of task {} is outdated", task.version, task.task_id)));
+            }
+        }
+
+        let saved_task = ScheduledTask { version: task.version + 1, ..task.clone() };
+        self.bind_task(sqlx::query(INSERT_TASK), &saved_task)?.execute(&mut *transaction).await?;
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn create_scheduled_task(&self, task: &mut ScheduledTask) -> Result<(), AppError> {
+        let statement = format!("{} ON CONFLICT (team, task_id) DO NOTHING", INSERT_TASK);
+        for _ in 0..MAX_TASK_ID_ATTEMPTS {
+            task.task_id = generate_task_id();
+
+            println!("Creating task {} with the next schedule at {}", task.task_id, task.next_update_time);
+            let result = self.bind_task(sqlx::query(&statement), task)?.execute(&self.pool).await?;
+            if result.rows_affected() > 0 {
+                return Ok(());
+            }
+
+            println!("Task id {} is taken, generating another one", task.task_id);
+        }
+
+        Err(AppError::ScheduledTaskError(format!("Couldn't generate a unique task id in {} attempts", MAX_TASK_ID_ATTEMPTS)))
+    }
+
+    async fn claim_scheduled_task(&self, task: &mut ScheduledTask, at: &DateTime<Utc>) -> Result<bool, AppError> {
+        let claimed_until_timestamp_utc = at.timestamp() + TASK_CLAIM_SECONDS;
+        let result = sqlx::query(r#"
+            UPDATE scheduled_tasks
+            SET version = $1, claimed_until_timestamp_utc = $2
+            WHERE team = $3 AND task_id = $4 AND version = $5 AND (claimed_until_timestamp_utc IS NULL OR claimed_until_timestamp_utc <= $6)
+        "#)
+            .bind(task.version + 1)
+            .bind(claimed_until_timestamp_utc)
+            .bind(task.team.clone())
+            .bind(task.task_id.clone())
+            .bind(task.version)
+            .bind(at.timestamp())
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        task.version += 1;
+        task.claimed_until_timestamp_utc = Some(claimed_until_timestamp_utc);
+        Ok(true)
+    }
+
+    async fn update_next_schedule(&self, task: &ScheduledTask) -> Result<(), AppError> {
+        println!("Updating next schedule of task {} to {}", task.task_id, task.next_update_time);
+        let result = sqlx::query(r#"
+            UPDATE scheduled_tasks
+            SET version = $1, claimed_until_timestamp_utc = NULL, last_updated_at = $2, next_update_time = $3, next_update_timestamp_utc = $4, next_trigger = $5, missed_runs = $6, last_missed_time = $7,
+                failed_runs = $8, last_failed_time = $9, last_error = $10,
+                member_ids = $11, previous_member_ids = $12, members_changed_time = $13, notification_thread_ts = $14
+            WHERE team = $15 AND task_id = $16 AND version = $17
+        "#)
+            .bind(task.version + 1)
+            .bind(task.last_updated_at.clone())
+            .bind(task.next_update_time.clone())
+            .bind(task.next_update_timestamp_utc)
+            .bind(task.next_trigger.to_string())
+            .bind(task.missed_runs)
+            .bind(task.last_missed_time.clone())
+            .bind(task.failed_runs)
+            .bind(task.last_failed_time.clone())
+            .bind(task.last_error.clone())
+            .bind(to_json_list(&task.member_ids))
+            .bind(to_json_list(&task.previous_member_ids))
+            .bind(task.members_changed_time.clone())
+            .bind(task.notification_thread_ts.clone())
+            .bind(task.team.clone())
+            .bind(task.task_id.clone())
+            .bind(task.version)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::ConcurrentUpdateError(format!("Version {} of task {} is outdated", task.version, task.task_id)));
+        }
+
+        Ok(())
+    }
+
+    async fn update_paused(&self, task: &mut ScheduledTask) -> Result<(), AppError> {
+        println!("Updating task {} to paused: {}, resume at: {:?}", task.task_id, task.paused, task.resume_time);
+        let result = sqlx::query(r#"
+            UPDATE scheduled_tasks
+            SET version = $1, paused = $2, resume_timestamp_utc = $3, resume_time = $4, last_updated_at = $5, next_update_time = $6, next_update_timestamp_utc = $7, next_trigger = $8
+            WHERE team = $9 AND task_id = $10 AND version = $11
+        "#)
+            .bind(task.version + 1)
+            .bind(task.paused as i64)
+            .bind(task.resume_timestamp_utc)
+            .bind(task.resume_time.clone())
+            .bind(task.last_updated_at.clone())
+            .bind(task.next_update_time.clone())
+            .bind(task.next_update_timestamp_utc)
+            .bind(task.next_trigger.to_string())
+            .bind(task.team.clone())
+            .bind(task.task_id.clone())
+            .bind(task.version)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::ConcurrentUpdateError(format!("Version {} of task {} is outdated", task.version, task.task_id)));
+        }
+
+        task.version += 1;
+        Ok(())
+    }
+
+    async fn list_scheduled_tasks_in_workspace(&self, team_id: &str, workspace_id: &str) -> Result<Vec<ScheduledTask>, AppError> {
+        let rows = sqlx::query("SELECT * FROM scheduled_tasks WHERE team = $1 ORDER BY task_id")
+            .bind(format!("{}:{}", team_id, workspace_id))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(skip_unreadable_tasks(rows.iter().map(|row| self.to_scheduled_task(row))))
+    }
+
+    async fn list_scheduled_tasks(&self) -> Result<Vec<ScheduledTask>, AppError> {
+        let rows = sqlx::query("SELECT * FROM scheduled_tasks")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(skip_unreadable_tasks(rows.iter().map(|row| self.to_scheduled_task(row))))
+    }
+
+    async fn list_due_scheduled_tasks(&self, at: &DateTime<Utc>) -> Result<Vec<ScheduledTask>, AppError> {
+        // The tasks without a next schedule have a negative timestamp
+        let rows = sqlx::query("SELECT * FROM scheduled_tasks WHERE next_update_timestamp_utc BETWEEN 0 AND $1")
+            .bind(at.timestamp())
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(skip_unreadable_tasks(rows.iter().map(|row| self.to_scheduled_task(row))))
+    }
+
+    async fn get_next_scheduled_task(&self, after: &DateTime<Utc>) -> Result<Option<ScheduledTask>, AppError> {
+        let mut rows = sqlx::query(r#"
+            SELECT * FROM scheduled_tasks
+            WHERE next_update_timestamp_utc > $1 AND (paused = 0 OR resume_timestamp_utc IS NOT NULL)
+            ORDER BY next_update_timestamp_utc
+        "#)
+            .bind(after.timestamp())
+            .fetch(&self.pool);
+
+        // An unreadable task is skipped, so the scheduler is still armed for the next readable one
+        while let Some(row) = rows.try_next().await? {
+            match self.to_scheduled_task(&row) {
+                Ok(task) => return Ok(Some(task)),
+                Err(err) => println!("Skipped unreadable scheduled task, error: {:?}", err),
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn delete_scheduled_task(&self, team_id: &str, workspace_id: &str, task_id: &str) -> Result<(), AppError> {
+        println!("Deleting scheduled task {}", task_id);
+        sqlx::query("DELETE FROM scheduled_tasks WHERE team = $1 AND task_id = $2")
+            .bind(format!("{}:{}", team_id, workspace_id))
+            .bind(task_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+use std::{env, sync::Arc};
+
+use async_trait::async_trait;
+use aws_config::SdkConfig;
+
+use crate::{config::{Config, SchedulerBackend}, cron::CronSchedule, errors::AppError};
+
+use super::{EventBridgeScheduler, InProcessScheduler};
+
+/**
+ * Triggers the user group updater at the next scheduled time of the tasks
+ */
+#[async_trait]
+pub trait Scheduler: Send + Sync {
+    /**
+     * Trigger the updater at the given time, unless it's already triggered earlier
+     */
+    async fn update_next_schedule(&self, next_task_schedule: &CronSchedule) -> Result<(), AppError>;
+}
+
+pub fn build_scheduler(aws_config: &SdkConfig, config: &Config) -> Result<Arc<dyn Scheduler>, AppError> {
+    match config.scheduler_backend {
+        SchedulerBackend::EventBridge => {
+            let lambda_arn = env::var("UPDATE_USER_GROUP_LAMBDA")?;
+            let lambda_role = env::var("UPDATE_USER_GROUP_LAMBDA_ROLE")?;
+            Ok(Arc::new(EventBridgeScheduler::new(aws_config, config.schedule_name_prefix.clone(), lambda_arn, lambda_role)))
+        },
+        SchedulerBackend::InProcess => Ok(InProcessScheduler::shared()),
+    }
+}
@@ -1,7 +1,9 @@
+use async_trait::async_trait;
 use aws_config::SdkConfig;
 use chrono::Utc;
 use tokio_stream::{self as stream, StreamExt as TokioStreamExt};
 use crate::{errors::AppError, cron::CronSchedule};
+use super::Scheduler;
 use aws_sdk_scheduler::{Client, types::{FlexibleTimeWindow, Target}, operation::get_schedule::GetScheduleOutput};
 
 pub struct EventBridgeScheduler {
@@ -26,52 +28,13 @@ pub struct EventBridgeSchedule {
 impl EventBridgeScheduler {
     pub fn new(config: &SdkConfig, name_prefix: String, lambda_arn: String, lambda_role: String) -> EventBridgeScheduler {
         EventBridgeScheduler {
-            client: Client::new(&config),
+            client: Client::new(config),
             name_prefix,
             lambda_arn,
             lambda_role,
         }
     }
     
-    pub async fn update_next_schedule(&self, next_task_schedule: &CronSchedule) -> Result<(), AppError> {
-        println!("Updating next schedule to: {:?}", next_task_schedule);
-
-        let mut current_schedules: Vec<_> = self.list_schedules()
-            .await?
-            .iter()
-            .map(|s| self.convert_to_schedule(s)).collect();
-
-        current_schedules.sort_by(|a, b| a.next_scheduled_timestamp_utc.cmp(&b.next_scheduled_timestamp_utc));
-
-        println!("Found existing schedules: {:?}", current_schedules);
-        
-        let next_schedule = self.get_next_schedule(&current_schedules, next_task_schedule.next_timestamp_utc);
-        let mut next_schedule_timestamp = next_schedule.as_ref().and_then(|s| s.next_scheduled_timestamp_utc).unwrap_or(i64::MAX);
-        println!("Found the next schedule at time: {:?}", next_schedule_timestamp);
-
-        if next_task_schedule.next_timestamp_utc < next_schedule_timestamp {
-            println!("Updating next schedule to: {}", next_task_schedule.next_datetime.format("%FT%T"));
-            self.client
-                .create_schedule()
-                .name(format!("{}{}", self.name_prefix, next_task_schedule.next_timestamp_utc))
-                .description("{datetime: <readable date time using original timezone>, datetime_utc, original_cron }")
-                .schedule_expression(format!("at({})", next_task_schedule.next_datetime.format("%FT%T")))
-                .schedule_expression_timezone(format!("{}", next_task_schedule.timezone))
-                .flexible_time_window(FlexibleTimeWindow::builder().mode(aws_sdk_scheduler::types::FlexibleTimeWindowMode::Off).build().unwrap())
-                .target(Target::builder().arn(&self.lambda_arn).role_arn(&self.lambda_role).build().unwrap())
-                .send()
-                .await?;
-            next_schedule_timestamp = next_task_schedule.next_timestamp_utc;
-        } else {
-            println!("Keep the next schedule unchanged: {}", next_schedule.map(|s| format!("{} {}", s.expression.unwrap(), s.next_scheduled_timestamp_utc.unwrap())).unwrap());
-        }
-
-        // clean up schedules to keep only the earliest
-        self.cleanup_schedules(current_schedules, next_schedule_timestamp).await?;
-        
-        Ok(())
-    }
-    
     fn get_next_schedule(&self, schedules: &Vec<EventBridgeSchedule>, before: i64) -> Option<EventBridgeSchedule>
     {
         let now = Utc::now().timestamp();
@@ -142,19 +105,61 @@ impl EventBridgeScheduler {
 
         // let schedule_summaries = TokioStreamExt::collect::<Result<Vec<_>, _>>(paginator).await?;
         
-        let schedules: Vec<GetScheduleOutput> = stream::iter(schedule_summaries?).then(|schedule| async move {
-            let x = self.client.get_schedule()
-                .name(schedule.name().expect("name doesn't exists"))
+        stream::iter(schedule_summaries?).then(|schedule| async move {
+            let name = schedule.name().ok_or(AppError::UnexpectedError("Schedule without name".to_string()))?;
+            let details = self.client.get_schedule()
+                .name(name)
                 .send()
-                .await.expect("Failed to get schedule details");
-            x
-        }).collect().await;
+                .await?;
 
-        Ok(schedules)
+            Ok(details)
+        }).collect().await
     }
 }
 
 
+#[async_trait]
+impl Scheduler for EventBridgeScheduler {
+    async fn update_next_schedule(&self, next_task_schedule: &CronSchedule) -> Result<(), AppError> {
+        println!("Updating next schedule to: {:?}", next_task_schedule);
+
+        let mut current_schedules: Vec<_> = self.list_schedules()
+            .await?
+            .iter()
+            .map(|s| self.convert_to_schedule(s)).collect();
+
+        current_schedules.sort_by_key(|a| a.next_scheduled_timestamp_utc);
+
+        println!("Found existing schedules: {:?}", current_schedules);
+        
+        let next_schedule = self.get_next_schedule(&current_schedules, next_task_schedule.next_timestamp_utc);
+        let mut next_schedule_timestamp = next_schedule.as_ref().and_then(|s| s.next_scheduled_timestamp_utc).unwrap_or(i64::MAX);
+        println!("Found the next schedule at time: {:?}", next_schedule_timestamp);
+
+        if next_task_schedule.next_timestamp_utc < next_schedule_timestamp {
+            println!("Updating next schedule to: {}", next_task_schedule.next_datetime.format("%FT%T"));
+            self.client
+                .create_schedule()
+                .name(format!("{}{}", self.name_prefix, next_task_schedule.next_timestamp_utc))
+                .description("{datetime: <readable date time using original timezone>, datetime_utc, original_cron }")
+                .schedule_expression(format!("at({})", next_task_schedule.next_datetime.format("%FT%T")))
+                .schedule_expression_timezone(format!("{}", next_task_schedule.timezone))
+                .flexible_time_window(FlexibleTimeWindow::builder().mode(aws_sdk_scheduler::types::FlexibleTimeWindowMode::Off).build().unwrap())
+                .target(Target::builder().arn(&self.lambda_arn).role_arn(&self.lambda_role).build().unwrap())
+                .send()
+                .await?;
+            next_schedule_timestamp = next_task_schedule.next_timestamp_utc;
+        } else {
+            println!("Keep the next schedule unchanged: {}", next_schedule.map(|s| format!("{} {}", s.expression.unwrap(), s.next_scheduled_timestamp_utc.unwrap())).unwrap());
+        }
+
+        // clean up schedules to keep only the earliest
+        self.cleanup_schedules(current_schedules, next_schedule_timestamp).await?;
+        
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use aws_config::BehaviorVersion;
@@ -162,7 +167,7 @@ mod tests {
     use chrono_tz::Tz;
     use std::str::FromStr;
 
-    use crate::{scheduled_tasks::{scheduler_event_bridge::EventBridgeScheduler, ScheduledTask}, errors::AppError, cron::get_next_schedule_from};
+    use crate::{scheduled_tasks::{scheduler_event_bridge::EventBridgeScheduler, ScheduledTask, Scheduler}, errors::AppError, cron::get_next_schedule_from};
 
     #[tokio::test]
     async fn test_update_next_schedule() -> Result<(), AppError>{
@@ -173,30 +178,11 @@ mod tests {
         let scheduler = EventBridgeScheduler::new(&config, scheduler_name_prefix.to_string(), lambda_arn.to_string(), lambda_role_arn.to_string());
 
         let task = ScheduledTask {
-            team: "".to_string(),
-            task_id: "".to_string(),
             next_update_timestamp_utc: Utc::now().timestamp(),
             next_update_time: Utc::now().to_rfc3339().to_string(),
-
-            team_id: "".to_string(),
-            team_domain: "".to_string(),
-            channel_id: "".to_string(),
-            channel_name: "".to_string(),
-            enterprise_id: "".to_string(),
-            enterprise_name: "".to_string(),
-            is_enterprise_install: false,
-
-            user_group_id: "".to_string(),
-            user_group_handle: "".to_string(),
-            pager_duty_schedule_id: "".to_string(),
-            pager_duty_token: None,
             cron: "0 5 ? * MON-FRI *".to_string(),
             timezone: "Australia/Melbourne".to_string(),
-
-            created_by_user_id: "U6HHTEST".to_string(),
-            created_by_user_name: "test-user".to_string(),
-            created_at: Utc::now().to_rfc3339(),
-            last_updated_at: Utc::now().to_rfc3339(),
+            ..Default::default()
         };
 
         let timezone = Tz::from_str(&task.timezone).unwrap();
@@ -0,0 +1,120 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use lazy_static::lazy_static;
+use tokio::{sync::Notify, time::{sleep, Duration}};
+
+use crate::{cron::CronSchedule, errors::AppError};
+
+use super::Scheduler;
+
+// Re-check the tasks at least daily, even when nothing is armed
+const MAX_IDLE_SECONDS: i64 = 24 * 60 * 60;
+
+lazy_static! {
+    static ref SHARED_SCHEDULER: Arc<InProcessScheduler> = Arc::new(InProcessScheduler::new());
+}
+
+/**
+ * A tokio timer for the standalone server, which sleeps until the earliest armed time.
+ * It's due right after starting, so the tasks missed while the server was stopped are caught up.
+ */
+pub struct InProcessScheduler {
+    next_timestamp_utc: Mutex<i64>,
+    changed: Notify,
+}
+
+impl Default for InProcessScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InProcessScheduler {
+    pub fn new() -> InProcessScheduler {
+        InProcessScheduler {
+            next_timestamp_utc: Mutex::new(0),
+            changed: Notify::new(),
+        }
+    }
+
+    /**
+     * The scheduler shared by the Slack handlers and the updater loop in the same process
+     */
+    pub fn shared() -> Arc<InProcessScheduler> {
+        SHARED_SCHEDULER.clone()
+    }
+
+    /**
+     * Sleep until the armed time, waking early when an earlier time is armed.
+     * The armed time is consumed, the updater arms the next one after running the due tasks.
+     */
+    pub async fn wait_until_due(&self) {
+        loop {
+            let now = Utc::now().timestamp();
+            let next_timestamp_utc = {
+                let mut next_timestamp_utc = self.next_timestamp_utc.lock().unwrap();
+                if *next_timestamp_utc <= now {
+                    *next_timestamp_utc = i64::MAX;
+                    return;
+                }
+                *next_timestamp_utc
+            };
+
+            let seconds = (next_timestamp_utc - now).min(MAX_IDLE_SECONDS);
+            tokio::select! {
+                _ = sleep(Duration::from_secs(seconds as u64)) => {
+                    if seconds == MAX_IDLE_SECONDS {
+                        return;
+                    }
+                },
+                _ = self.changed.notified() => {},
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Scheduler for InProcessScheduler {
+    async fn update_next_schedule(&self, next_task_schedule: &CronSchedule) -> Result<(), AppError> {
+        let mut next_timestamp_utc = self.next_timestamp_utc.lock().unwrap();
+        if next_task_schedule.next_timestamp_utc < *next_timestamp_utc {
+            println!("Updating next schedule to: {}", next_task_schedule.next_datetime.format("%FT%T"));
+            *next_timestamp_utc = next_task_schedule.next_timestamp_utc;
+            self.changed.notify_one();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Utc;
+    use tokio::time::{timeout, Duration};
+
+    use crate::cron::get_next_schedule_from;
+    use crate::scheduled_tasks::{InProcessScheduler, Scheduler};
+
+    #[tokio::test]
+    async fn catch_up_on_start_and_wake_early() {
+        let scheduler = Arc::new(InProcessScheduler::new());
+        timeout(Duration::from_millis(100), scheduler.wait_until_due()).await.expect("Should be due right after starting");
+
+        let waiting = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.wait_until_due().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        let mut next_schedule = get_next_schedule_from("0 9 ? * MON-FRI *", &Utc::now().with_timezone(&chrono_tz::UTC)).unwrap();
+        next_schedule.next_timestamp_utc = Utc::now().timestamp();
+        scheduler.update_next_schedule(&next_schedule).await.unwrap();
+
+        timeout(Duration::from_millis(100), waiting).await.expect("Should wake up when an earlier time is armed").unwrap();
+    }
+}
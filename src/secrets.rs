@@ -1,9 +1,11 @@
 
+use std::{env, fs};
+
 use aws_config::SdkConfig;
 use aws_sdk_secretsmanager::Client;
 use serde_derive::{Serialize, Deserialize};
 
-use crate::errors::AppError;
+use crate::{config::SecretsSource, errors::AppError};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Secrets {
@@ -11,6 +13,42 @@ pub struct Secrets {
     pub slack_client_id: String,
     pub slack_client_secret: String,
     pub slack_signing_secret: String,
+
+    // Additional signing secrets accepted while rotating the signing secret
+    #[serde(default)]
+    pub previous_slack_signing_secrets: Vec<String>,
+}
+
+impl Secrets {
+    pub fn from_env() -> Result<Secrets, AppError> {
+        let required = |name: &str| env::var(name).map_err(|_| AppError::SecretsError(format!("{} is required for the env secrets source", name)));
+
+        Ok(Secrets {
+            encryption_key: required("ENCRYPTION_KEY")?,
+            slack_client_id: required("SLACK_CLIENT_ID")?,
+            slack_client_secret: required("SLACK_CLIENT_SECRET")?,
+            slack_signing_secret: required("SLACK_SIGNING_SECRET")?,
+            previous_slack_signing_secrets: env::var("PREVIOUS_SLACK_SIGNING_SECRETS").unwrap_or_default()
+                .split(',')
+                .map(|secret| secret.trim().to_string())
+                .filter(|secret| !secret.is_empty())
+                .collect(),
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<Secrets, AppError> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| AppError::SecretsError(format!("Failed to read secrets file {}, error: {}", path, e)))?;
+
+        serde_json::from_str(&json)
+            .map_err(|e| AppError::SecretsError(format!("Failed to parse secrets file {}, error: {}", path, e)))
+    }
+
+    pub fn slack_signing_secrets(&self) -> Vec<String> {
+        let mut signing_secrets = vec![self.slack_signing_secret.clone()];
+        signing_secrets.extend(self.previous_slack_signing_secrets.iter().cloned());
+        signing_secrets
+    }
 }
 
 pub struct SecretsClient {
@@ -19,7 +57,7 @@ pub struct SecretsClient {
 
 impl SecretsClient {
     pub fn new(config: &SdkConfig) -> SecretsClient {
-        SecretsClient{ client: Client::new(&config) }
+        SecretsClient{ client: Client::new(config) }
     }
    
     pub async fn get_secret(&self, name: &str) -> Result<Secrets, AppError> {
@@ -31,9 +69,21 @@ impl SecretsClient {
             .send()
             .await?;
 
-        let secrets_value = result.secret_string().expect(format!("Couldn't get secret value for {}", name).as_str());
-        let secrets: Secrets = serde_json::from_str(&secrets_value).expect("couldn't parse json");
-        Ok(secrets)
+        let secrets_value = result.secret_string()
+            .ok_or_else(|| AppError::SecretsError(format!("Secret {} has no string value", name)))?;
+        serde_json::from_str(secrets_value)
+            .map_err(|e| AppError::SecretsError(format!("Failed to parse secret {}, error: {}", name, e)))
+    }
+}
+
+/**
+ * Read the secrets from the source in the config, the AWS config is only used for Secrets Manager
+ */
+pub async fn load_secrets(aws_config: &SdkConfig, source: &SecretsSource) -> Result<Secrets, AppError> {
+    match source {
+        SecretsSource::SecretsManager { secret_name } => SecretsClient::new(aws_config).get_secret(secret_name).await,
+        SecretsSource::Env => Secrets::from_env(),
+        SecretsSource::File { path } => Secrets::from_file(path),
     }
 }
 
@@ -41,7 +91,7 @@ impl SecretsClient {
 mod tests {
     use aws_config::BehaviorVersion;
 
-    use crate::{secrets::SecretsClient, errors::AppError};
+    use crate::{secrets::{Secrets, SecretsClient}, errors::AppError};
 
     #[tokio::test]
     async fn encrypt_decrypt_string() -> Result<(), AppError>{
@@ -53,4 +103,20 @@ mod tests {
         assert_eq!(encryption_key.encryption_key.len(), 32);
         Ok(())
     }
+
+    #[test]
+    fn read_secrets_from_file() {
+        let path = std::env::temp_dir().join("on-call-support-secrets-test.json");
+        std::fs::write(&path, r#"{
+            "encryption_key": "plain text key which should be s",
+            "slack_client_id": "client-id",
+            "slack_client_secret": "client-secret",
+            "slack_signing_secret": "signing-secret"
+        }"#).unwrap();
+
+        let secrets = Secrets::from_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(secrets.slack_signing_secrets(), vec!["signing-secret"]);
+
+        assert!(matches!(Secrets::from_file("/nonexistent/secrets.json"), Err(AppError::SecretsError(_))));
+    }
 }
@@ -0,0 +1,543 @@
+use std::{collections::{HashMap, HashSet}, str::FromStr, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Days, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::Client;
+use rrule::{RRule, RRuleSet, Unvalidated};
+
+use crate::errors::AppError;
+use super::schedule_provider::{OnCallUser, ScheduleProvider};
+
+/**
+ * On-call rotation kept in an iCalendar (ICS) feed, e.g. a shared Google or Outlook calendar.
+ *
+ * The attendees of the events active at a given time are on call. Events without attendees
+ * fall back to the email addresses in their summary, e.g. "On call: alice@example.com".
+ */
+pub struct Ical {
+    http_client: Arc<Client>,
+    source: IcalSource,
+    default_timezone: Tz,
+}
+
+pub enum IcalSource {
+    Url(String),
+    Inline(String),
+}
+
+impl Ical {
+    /**
+     * Floating times and all-day events in the calendar are in the `default_timezone`
+     */
+    pub fn new(http_client: Arc<Client>, source: IcalSource, default_timezone: Tz) -> Ical {
+        Ical { http_client, source, default_timezone }
+    }
+
+    async fn load_calendar(&self) -> Result<String, AppError> {
+        match &self.source {
+            IcalSource::Inline(content) => Ok(content.clone()),
+            IcalSource::Url(url) => {
+                let url = match url.strip_prefix("webcal://") {
+                    Some(address) => format!("https://{}", address),
+                    None => url.clone(),
+                };
+
+                let response = self.http_client.get(&url).send().await?;
+
+                match response.error_for_status() {
+                    Ok(res) => Ok(res.text().await?),
+                    Err(err) => {
+                        println!("Error: {:?}", err);
+                        Err(AppError::IcalError(err.to_string()))
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ScheduleProvider for Ical {
+    async fn get_on_call_users(&self, at: DateTime<Utc>) -> Result<Vec<OnCallUser>, AppError> {
+        let content = self.load_calendar().await?;
+        let calendar = IcalCalendar::parse(&content, self.default_timezone)?;
+
+        calendar.on_call_users_at(at)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Property {
+    name: String,
+    params: HashMap<String, String>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Property> {
+        // The value starts after the first colon which is not quoted in a parameter
+        let mut in_quotes = false;
+        let separator = line.char_indices().find(|(_, c)| {
+            if *c == '"' {
+                in_quotes = !in_quotes;
+            }
+            *c == ':' && !in_quotes
+        })?.0;
+
+        let mut parts = line[..separator].split(';');
+        let name = parts.next()?.to_ascii_uppercase();
+        let params = parts
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| (key.to_ascii_uppercase(), value.trim_matches('"').to_string()))
+            .collect();
+
+        Some(Property { name, params, value: line[separator + 1..].to_string() })
+    }
+
+    fn is_date(&self) -> bool {
+        self.params.get("VALUE").is_some_and(|v| v.eq_ignore_ascii_case("DATE")) || !self.value.contains('T')
+    }
+}
+
+#[derive(Debug, Clone)]
+struct IcalEvent {
+    uid: String,
+    start: DateTime<Tz>,
+    duration: EventDuration,
+    rrule: Option<String>,
+    rdates: Vec<DateTime<Tz>>,
+    exdates: Vec<DateTime<Tz>>,
+    recurrence_id: Option<DateTime<Tz>>,
+    attendees: Vec<OnCallUser>,
+    summary: String,
+}
+
+/**
+ * Whole days are nominal, so an all-day event still ends at midnight after a daylight saving change
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct EventDuration {
+    days: u64,
+    seconds: i64,
+}
+
+impl EventDuration {
+    fn end_of(&self, start: &DateTime<Tz>) -> DateTime<Tz> {
+        let end = start.checked_add_days(Days::new(self.days)).unwrap_or(*start);
+        end + Duration::seconds(self.seconds)
+    }
+
+    /**
+     * Parse ISO 8601 durations used by iCalendar, e.g. P1D, PT8H30M, P1W
+     */
+    fn parse(value: &str) -> Option<EventDuration> {
+        lazy_static! {
+            static ref DURATION: Regex = Regex::new(r"^([+-])?P(?:(\d+)W)?(?:(\d+)D)?(?:T(?:(\d+)H)?(?:(\d+)M)?(?:(\d+)S)?)?$").unwrap();
+        }
+
+        let captures = DURATION.captures(value.trim())?;
+        if captures.get(1).is_some_and(|sign| sign.as_str() == "-") {
+            return None;
+        }
+
+        let number = |index: usize| captures.get(index).and_then(|m| m.as_str().parse::<i64>().ok()).unwrap_or_default();
+
+        Some(EventDuration {
+            days: (number(2) * 7 + number(3)) as u64,
+            seconds: number(4) * 3600 + number(5) * 60 + number(6),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct IcalCalendar {
+    events: Vec<IcalEvent>,
+}
+
+impl IcalCalendar {
+    pub fn parse(content: &str, default_timezone: Tz) -> Result<IcalCalendar, AppError> {
+        let mut events = vec![];
+        let mut current_event: Option<Vec<Property>> = None;
+
+        for line in unfold_lines(content) {
+            let Some(property) = Property::parse(&line) else {
+                continue;
+            };
+
+            match (property.name.as_str(), property.value.to_ascii_uppercase().as_str()) {
+                ("BEGIN", "VEVENT") => current_event = Some(vec![]),
+                ("END", "VEVENT") => {
+                    if let Some(properties) = current_event.take() {
+                        if let Some(event) = IcalCalendar::parse_event(&properties, default_timezone)? {
+                            events.push(event);
+                        }
+                    }
+                },
+                _ => {
+                    if let Some(properties) = current_event.as_mut() {
+                        properties.push(property);
+                    }
+                }
+            }
+        }
+
+        Ok(IcalCalendar { events })
+    }
+
+    fn parse_event(properties: &[Property], default_timezone: Tz) -> Result<Option<IcalEvent>, AppError> {
+        let find = |name: &str| properties.iter().find(|p| p.name == name);
+
+        if find("STATUS").is_some_and(|status| status.value.eq_ignore_ascii_case("CANCELLED")) {
+            return Ok(None);
+        }
+
+        let Some(dtstart) = find("DTSTART") else {
+            return Ok(None);
+        };
+
+        let start = parse_date_time(dtstart, default_timezone)?;
+        let is_all_day = dtstart.is_date();
+
+        let duration = if let Some(dtend) = find("DTEND") {
+            let end = parse_date_time(dtend, default_timezone)?;
+            if is_all_day {
+                EventDuration { days: (end.date_naive() - start.date_naive()).num_days().max(0) as u64, seconds: 0 }
+            } else {
+                EventDuration { days: 0, seconds: (end - start).num_seconds().max(0) }
+            }
+        } else if let Some(duration) = find("DURATION") {
+            EventDuration::parse(&duration.value)
+                .ok_or(AppError::IcalError(format!("Invalid duration: {}", duration.value)))?
+        } else if is_all_day {
+            EventDuration { days: 1, seconds: 0 }
+        } else {
+            EventDuration::default()
+        };
+
+        let parse_dates = |name: &str| -> Result<Vec<DateTime<Tz>>, AppError> {
+            let mut dates = vec![];
+            for property in properties.iter().filter(|p| p.name == name) {
+                for value in property.value.split(',') {
+                    let date = Property { value: value.to_string(), ..property.clone() };
+                    dates.push(parse_date_time(&date, default_timezone)?);
+                }
+            }
+            Ok(dates)
+        };
+
+        let attendees = properties.iter()
+            .filter(|p| p.name == "ATTENDEE")
+            .filter(|p| !p.params.get("PARTSTAT").is_some_and(|status| status.eq_ignore_ascii_case("DECLINED")))
+            .filter_map(|p| {
+                let email = p.value.get(..7)
+                    .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+                    .map(|_| p.value[7..].to_string())?;
+
+                Some(OnCallUser { name: p.params.get("CN").cloned().unwrap_or(email.clone()), email, slack_user_id: None })
+            })
+            .collect();
+
+        Ok(Some(IcalEvent {
+            uid: find("UID").map(|p| p.value.clone()).unwrap_or_default(),
+            start,
+            duration,
+            rrule: find("RRULE").map(|p| p.value.clone()),
+            rdates: parse_dates("RDATE")?,
+            exdates: parse_dates("EXDATE")?,
+            recurrence_id: find("RECURRENCE-ID").map(|p| parse_date_time(p, default_timezone)).transpose()?,
+            attendees,
+            summary: find("SUMMARY").map(|p| unescape_text(&p.value)).unwrap_or_default(),
+        }))
+    }
+
+    /**
+     * Return the users of all events which are active at the given time, i.e. start <= at < end
+     */
+    pub fn on_call_users_at(&self, at: DateTime<Utc>) -> Result<Vec<OnCallUser>, AppError> {
+        // Occurrences moved or changed individually replace the occurrence of the recurring event
+        let overridden: HashSet<(&str, i64)> = self.events.iter()
+            .filter_map(|event| event.recurrence_id.map(|id| (event.uid.as_str(), id.timestamp())))
+            .collect();
+
+        let mut users: Vec<OnCallUser> = vec![];
+        for event in &self.events {
+            for occurrence in event.occurrences_at(at)? {
+                if event.recurrence_id.is_none() && overridden.contains(&(event.uid.as_str(), occurrence.timestamp())) {
+                    continue;
+                }
+
+                for user in event.on_call_users() {
+                    if !users.iter().any(|u| u.email.eq_ignore_ascii_case(&user.email)) {
+                        users.push(user);
+                    }
+                }
+            }
+        }
+
+        Ok(users)
+    }
+}
+
+impl IcalEvent {
+    fn occurrences_at(&self, at: DateTime<Utc>) -> Result<Vec<DateTime<Tz>>, AppError> {
+        let is_active = |start: &DateTime<Tz>| *start <= at && at < self.duration.end_of(start);
+
+        let rrule = match (&self.rrule, &self.recurrence_id) {
+            (Some(rrule), None) => rrule,
+            _ => return Ok(std::iter::once(self.start).chain(self.rdates.iter().cloned()).filter(is_active).collect()),
+        };
+
+        let to_rrule_tz = |date: &DateTime<Tz>| date.with_timezone(&rrule::Tz::Tz(date.timezone()));
+        let dt_start = to_rrule_tz(&self.start);
+
+        let rrule: RRule<Unvalidated> = rrule.parse()
+            .map_err(|err| AppError::IcalError(format!("Invalid RRULE in event {}: {}", self.summary, err)))?;
+        let rrule = rrule.validate(dt_start)
+            .map_err(|err| AppError::IcalError(format!("Invalid RRULE in event {}: {}", self.summary, err)))?;
+
+        let mut rrule_set = RRuleSet::new(dt_start).rrule(rrule);
+        for rdate in &self.rdates {
+            rrule_set = rrule_set.rdate(to_rrule_tz(rdate));
+        }
+        for exdate in &self.exdates {
+            rrule_set = rrule_set.exdate(to_rrule_tz(exdate));
+        }
+
+        // Occurrences are sorted, the ones starting after `at` can't be active
+        let occurrences = rrule_set.into_iter()
+            .map(|occurrence| occurrence.with_timezone(&self.start.timezone()))
+            .take_while(|occurrence| *occurrence <= at)
+            .filter(is_active)
+            .collect();
+
+        Ok(occurrences)
+    }
+
+    fn on_call_users(&self) -> Vec<OnCallUser> {
+        lazy_static! {
+            static ref EMAIL: Regex = Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap();
+        }
+
+        if !self.attendees.is_empty() {
+            return self.attendees.clone();
+        }
+
+        EMAIL.find_iter(&self.summary)
+            .map(|email| OnCallUser { name: email.as_str().to_string(), email: email.as_str().to_string(), slack_user_id: None })
+            .collect()
+    }
+}
+
+/**
+ * Long lines are folded by inserting a line break followed by a space or tab
+ */
+fn unfold_lines(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+
+    for line in content.lines() {
+        match (line.strip_prefix(' ').or(line.strip_prefix('\t')), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    lines
+}
+
+fn unescape_text(value: &str) -> String {
+    value.replace("\\n", "\n").replace("\\N", "\n").replace("\\,", ",").replace("\\;", ";").replace("\\\\", "\\")
+}
+
+/**
+ * Parse DATE or DATE-TIME values, which are either in UTC, in the timezone of the TZID parameter or floating
+ */
+fn parse_date_time(property: &Property, default_timezone: Tz) -> Result<DateTime<Tz>, AppError> {
+    let value = property.value.trim();
+    let invalid_date = || AppError::IcalError(format!("Invalid date in {}: {}", property.name, value));
+
+    // Unknown timezone names, e.g. Windows timezones from Outlook, fall back to the default timezone
+    let timezone = property.params.get("TZID")
+        .and_then(|tzid| Tz::from_str(tzid.trim_start_matches('/')).ok())
+        .unwrap_or(default_timezone);
+
+    if property.is_date() {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid_date())?;
+        return local_to_timezone(&date.and_hms_opt(0, 0, 0).ok_or_else(invalid_date)?, timezone).ok_or_else(invalid_date);
+    }
+
+    if let Some(utc_value) = value.strip_suffix('Z') {
+        let date_time = NaiveDateTime::parse_from_str(utc_value, "%Y%m%dT%H%M%S").map_err(|_| invalid_date())?;
+        return Ok(Utc.from_utc_datetime(&date_time).with_timezone(&timezone));
+    }
+
+    let date_time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid_date())?;
+    local_to_timezone(&date_time, timezone).ok_or_else(invalid_date)
+}
+
+fn local_to_timezone(date_time: &NaiveDateTime, timezone: Tz) -> Option<DateTime<Tz>> {
+    // Times skipped by daylight saving are moved forward by an hour
+    timezone.from_local_datetime(date_time).earliest()
+        .or_else(|| timezone.from_local_datetime(&(*date_time + Duration::hours(1))).earliest())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    use crate::service_provider::ical::{EventDuration, IcalCalendar};
+
+    fn emails_at(calendar: &IcalCalendar, at: chrono::DateTime<Utc>) -> Vec<String> {
+        calendar.on_call_users_at(at).unwrap().into_iter().map(|u| u.email).collect()
+    }
+
+    const WEEKLY_ROTATION: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Google Inc//Google Calendar 70.9054//EN\r
+BEGIN:VTIMEZONE\r
+TZID:Australia/Melbourne\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:alice-rotation@example.com\r
+DTSTART;TZID=Australia/Melbourne:20240101T090000\r
+DTEND;TZID=Australia/Melbourne:20240108T090000\r
+RRULE:FREQ=WEEKLY;INTERVAL=2\r
+EXDATE;TZID=Australia/Melbourne:20240129T090000\r
+SUMMARY:Support rotation\r
+ATTENDEE;CN=Alice Smith;ROLE=REQ-PARTICIPANT;PARTSTAT=ACCEPTED:mailto:alice@\r
+ example.com\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:bob-rotation@example.com\r
+DTSTART;TZID=Australia/Melbourne:20240108T090000\r
+DURATION:P1W\r
+RRULE:FREQ=WEEKLY;INTERVAL=2;UNTIL=20240401T000000Z\r
+SUMMARY:On call: bob@example.com\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn expand_weekly_rotation() {
+        let calendar = IcalCalendar::parse(WEEKLY_ROTATION, Tz::UTC).unwrap();
+        let melbourne = Tz::from_str("Australia/Melbourne").unwrap();
+
+        // first week is Alice's
+        let at = melbourne.with_ymd_and_hms(2024, 1, 3, 12, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(emails_at(&calendar, at), vec!["alice@example.com"]);
+
+        // handover happens at 9am Melbourne time
+        let at = melbourne.with_ymd_and_hms(2024, 1, 8, 8, 59, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(emails_at(&calendar, at), vec!["alice@example.com"]);
+        let at = melbourne.with_ymd_and_hms(2024, 1, 8, 9, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(emails_at(&calendar, at), vec!["bob@example.com"]);
+
+        // Alice is on call again two weeks later
+        let at = melbourne.with_ymd_and_hms(2024, 1, 16, 9, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(emails_at(&calendar, at), vec!["alice@example.com"]);
+    }
+
+    #[test]
+    fn skip_excluded_occurrences() {
+        let calendar = IcalCalendar::parse(WEEKLY_ROTATION, Tz::UTC).unwrap();
+        let melbourne = Tz::from_str("Australia/Melbourne").unwrap();
+
+        let at = melbourne.with_ymd_and_hms(2024, 1, 30, 9, 0, 0).unwrap().with_timezone(&Utc);
+        assert!(emails_at(&calendar, at).is_empty());
+    }
+
+    #[test]
+    fn stop_recurring_after_until() {
+        let calendar = IcalCalendar::parse(WEEKLY_ROTATION, Tz::UTC).unwrap();
+        let melbourne = Tz::from_str("Australia/Melbourne").unwrap();
+
+        let at = melbourne.with_ymd_and_hms(2024, 3, 20, 9, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(emails_at(&calendar, at), vec!["bob@example.com"]);
+
+        let at = melbourne.with_ymd_and_hms(2024, 4, 17, 9, 0, 0).unwrap().with_timezone(&Utc);
+        assert!(emails_at(&calendar, at).is_empty());
+    }
+
+    #[test]
+    fn keep_local_time_across_daylight_saving() {
+        let calendar = IcalCalendar::parse(WEEKLY_ROTATION, Tz::UTC).unwrap();
+        let melbourne = Tz::from_str("Australia/Melbourne").unwrap();
+
+        // Daylight saving ends in Melbourne on 7 April 2024, handover is still at 9am local time
+        let at = melbourne.with_ymd_and_hms(2024, 4, 8, 8, 59, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(emails_at(&calendar, at), vec!["bob@example.com"]);
+        let at = melbourne.with_ymd_and_hms(2024, 4, 8, 9, 0, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(emails_at(&calendar, at), vec!["alice@example.com"]);
+    }
+
+    #[test]
+    fn replace_overridden_occurrence() {
+        let content = "BEGIN:VCALENDAR
+BEGIN:VEVENT
+UID:daily
+DTSTART:20240101T000000Z
+DTEND:20240102T000000Z
+RRULE:FREQ=DAILY
+ATTENDEE;CN=Alice:mailto:alice@example.com
+END:VEVENT
+BEGIN:VEVENT
+UID:daily
+RECURRENCE-ID:20240103T000000Z
+DTSTART:20240103T000000Z
+DTEND:20240104T000000Z
+ATTENDEE;CN=Carol:mailto:carol@example.com
+ATTENDEE;CN=Dave;PARTSTAT=DECLINED:mailto:dave@example.com
+END:VEVENT
+END:VCALENDAR
+";
+        let calendar = IcalCalendar::parse(content, Tz::UTC).unwrap();
+
+        assert_eq!(emails_at(&calendar, Utc.with_ymd_and_hms(2024, 1, 2, 12, 0, 0).unwrap()), vec!["alice@example.com"]);
+        assert_eq!(emails_at(&calendar, Utc.with_ymd_and_hms(2024, 1, 3, 12, 0, 0).unwrap()), vec!["carol@example.com"]);
+
+        let users = calendar.on_call_users_at(Utc.with_ymd_and_hms(2024, 1, 3, 12, 0, 0).unwrap()).unwrap();
+        assert_eq!(users[0].name, "Carol");
+    }
+
+    #[test]
+    fn all_day_events_use_default_timezone() {
+        let content = "BEGIN:VCALENDAR
+BEGIN:VEVENT
+UID:weekend
+DTSTART;VALUE=DATE:20240106
+DTEND;VALUE=DATE:20240108
+RRULE:FREQ=WEEKLY;BYDAY=SA
+SUMMARY:Weekend support erin@example.com
+END:VEVENT
+BEGIN:VEVENT
+UID:cancelled
+DTSTART;VALUE=DATE:20240106
+STATUS:CANCELLED
+SUMMARY:frank@example.com
+END:VEVENT
+END:VCALENDAR
+";
+        let melbourne = Tz::from_str("Australia/Melbourne").unwrap();
+        let calendar = IcalCalendar::parse(content, melbourne).unwrap();
+
+        let at = melbourne.with_ymd_and_hms(2024, 1, 14, 23, 59, 0).unwrap().with_timezone(&Utc);
+        assert_eq!(emails_at(&calendar, at), vec!["erin@example.com"]);
+
+        let at = melbourne.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap().with_timezone(&Utc);
+        assert!(emails_at(&calendar, at).is_empty());
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(EventDuration::parse("P1W"), Some(EventDuration { days: 7, seconds: 0 }));
+        assert_eq!(EventDuration::parse("P1DT8H30M"), Some(EventDuration { days: 1, seconds: 8 * 3600 + 30 * 60 }));
+        assert_eq!(EventDuration::parse("PT15M"), Some(EventDuration { days: 0, seconds: 900 }));
+        assert_eq!(EventDuration::parse("-PT15M"), None);
+        assert_eq!(EventDuration::parse("1 hour"), None);
+    }
+}
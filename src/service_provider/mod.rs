@@ -1,2 +1,5 @@
+pub mod ical;
+pub mod opsgenie;
 pub mod pager_duty;
+pub mod schedule_provider;
 pub mod slack;
@@ -0,0 +1,205 @@
+use std::{str::FromStr, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc, SecondsFormat};
+use derive_more::Display;
+use futures::{stream, StreamExt, TryStreamExt};
+use reqwest::{Client, Url};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::errors::AppError;
+use super::schedule_provider::{OnCallUser, ScheduleProvider};
+
+// Limit the concurrent requests of the users on call, to stay in the Opsgenie rate limits
+const MAX_CONCURRENT_USER_REQUESTS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum OpsgenieRegion {
+    #[default]
+    #[display("us")]
+    Us,
+    #[display("eu")]
+    Eu,
+}
+
+impl OpsgenieRegion {
+    pub fn api_base_url(&self) -> &'static str {
+        match self {
+            OpsgenieRegion::Us => "https://api.opsgenie.com",
+            OpsgenieRegion::Eu => "https://api.eu.opsgenie.com",
+        }
+    }
+
+    /**
+     * Build the url of an API, the path segments are percent-encoded, e.g. usernames with `+` or `/`
+     */
+    pub fn api_url(&self, path_segments: &[&str]) -> Result<Url, AppError> {
+        let mut url = Url::parse(self.api_base_url()).map_err(|err| AppError::OpsgenieError(err.to_string()))?;
+        url.path_segments_mut()
+            .map_err(|_| AppError::OpsgenieError(format!("Invalid base url: {}", self.api_base_url())))?
+            .extend(path_segments);
+
+        Ok(url)
+    }
+}
+
+impl FromStr for OpsgenieRegion {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "us" => Ok(OpsgenieRegion::Us),
+            "eu" => Ok(OpsgenieRegion::Eu),
+            _ => Err(AppError::OpsgenieError(format!("Unknown Opsgenie region: {}, expecting us or eu", s))),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpsgenieResponse<T> {
+    data: T,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OnCallsResponse {
+    #[serde(default)]
+    on_call_participants: Vec<OnCallParticipant>,
+}
+
+impl OnCallsResponse {
+    // Participants can also be teams or escalations, only users can join the Slack user group
+    fn usernames(self) -> Vec<String> {
+        self.on_call_participants.into_iter()
+            .filter(|participant| participant.participant_type == "user")
+            .map(|participant| participant.name)
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OnCallParticipant {
+    name: String,
+    #[serde(rename = "type")]
+    participant_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpsgenieUser {
+    username: String,
+    full_name: Option<String>,
+}
+
+pub struct Opsgenie {
+    http_client: Arc<Client>,
+    api_key: String,
+    region: OpsgenieRegion,
+    schedule_id: String,
+}
+
+impl Opsgenie {
+    pub fn new(http_client: Arc<Client>, api_key: String, region: OpsgenieRegion, schedule_id: String) -> Opsgenie {
+        Opsgenie { http_client, api_key, region, schedule_id }
+    }
+
+    /**
+     * Return the usernames (emails) of the users on call at the given time
+     */
+    pub async fn get_on_call_usernames(&self, at: DateTime<Utc>) -> Result<Vec<String>, AppError> {
+        let url = self.region.api_url(&["v2", "schedules", &self.schedule_id, "on-calls"])?;
+        let date = at.to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        let response: OnCallsResponse = self.send_request(&url, &[("scheduleIdentifierType", "id"), ("date", date.as_str())]).await?;
+
+        Ok(response.usernames())
+    }
+
+    pub async fn get_user(&self, username: &str) -> Result<OnCallUser, AppError> {
+        let url = self.region.api_url(&["v2", "users", username])?;
+        let user: OpsgenieUser = self.send_request(&url, &[]).await?;
+
+        Ok(OnCallUser {
+            name: user.full_name.unwrap_or(user.username.clone()),
+            email: user.username,
+            slack_user_id: None,
+        })
+    }
+
+    async fn send_request<T>(&self, url: &Url, params: &[(&str, &str)]) -> Result<T, AppError>
+    where
+        T: for<'a> serde::Deserialize<'a>,
+    {
+        let response = self.http_client
+            .get(url.clone())
+            .header("Authorization", format!("GenieKey {}", &self.api_key))
+            .query(params)
+            .send()
+            .await?;
+
+        match response.error_for_status() {
+            Ok(res) => {
+                let opsgenie_response: OpsgenieResponse<T> = res.json().await?;
+                Ok(opsgenie_response.data)
+            }
+
+            Err(err) => {
+                println!("Error: {:?}", err);
+                Err(AppError::OpsgenieError(err.to_string()))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ScheduleProvider for Opsgenie {
+    async fn get_on_call_users(&self, at: DateTime<Utc>) -> Result<Vec<OnCallUser>, AppError> {
+        let usernames = self.get_on_call_usernames(at).await?;
+
+        stream::iter(usernames)
+            .map(|username| async move { self.get_user(&username).await })
+            .buffered(MAX_CONCURRENT_USER_REQUESTS)
+            .try_collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::service_provider::opsgenie::{OnCallsResponse, OpsgenieRegion, OpsgenieResponse};
+
+    #[test]
+    fn parse_region() {
+        assert_eq!(OpsgenieRegion::from_str("EU").unwrap(), OpsgenieRegion::Eu);
+        assert_eq!(OpsgenieRegion::from_str("us").unwrap().api_base_url(), "https://api.opsgenie.com");
+        assert!(OpsgenieRegion::from_str("au").is_err());
+    }
+
+    #[test]
+    fn encode_path_segments_of_api_url() {
+        let url = OpsgenieRegion::Eu.api_url(&["v2", "users", "john+oncall/ops@opsgenie.com"]).unwrap();
+        assert_eq!(url.as_str(), "https://api.eu.opsgenie.com/v2/users/john+oncall%2Fops@opsgenie.com");
+    }
+
+    #[test]
+    fn parse_on_calls_response() {
+        let json = r#"{
+            "data": {
+                "_parent": { "id": "d875alp4-9b4e-4219-alp3-0c26936d18de", "name": "ScheduleName", "enabled": true },
+                "onCallParticipants": [
+                    { "id": "b3ec3f4e-6a8d-4e0e-8d3f-0c26936d18de", "name": "john.doe@opsgenie.com", "type": "user" },
+                    { "id": "c569c016-alp9-4e20-8a28-bd5dc33b798e", "name": "TeamName", "type": "team" }
+                ]
+            },
+            "took": 0.1,
+            "requestId": "3ce6b8e0-8a4c-11e8-8e7f-0c26936d18de"
+        }"#;
+
+        let response: OpsgenieResponse<OnCallsResponse> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(response.data.usernames(), vec!["john.doe@opsgenie.com"]);
+    }
+}
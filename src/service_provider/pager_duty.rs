@@ -1,10 +1,18 @@
 use std::sync::Arc;
 
+use async_trait::async_trait;
 use chrono::{DateTime, Utc, Duration};
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde_derive::Deserialize;
 
 use crate::errors::AppError;
+use super::schedule_provider::{OnCallUser, ScheduleProvider, Shift};
+
+const PAGERDUTY_API_URL: &str = "https://api.pagerduty.com";
+
+// The maximum page size of the PagerDuty list endpoints
+const PAGE_SIZE: usize = 100;
 
 #[derive(Debug, Deserialize)]
 pub struct PagerDutyUser {
@@ -17,14 +25,49 @@ pub struct PagerDutyUsersResponse {
     pub users: Vec<PagerDutyUser>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PagerDutyReference {
+    pub summary: String,
+}
+
+/**
+ * An on-call entry of the schedule, the start is empty for the users always on call
+ */
+#[derive(Debug, Deserialize)]
+pub struct PagerDutyOnCall {
+    pub user: PagerDutyUser,
+    pub schedule: Option<PagerDutyReference>,
+    pub start: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PagerDutyOnCallsResponse {
+    oncalls: Vec<PagerDutyOnCall>,
+    #[serde(default)]
+    more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PagerDutySchedule {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PagerDutySchedulesResponse {
+    schedules: Vec<PagerDutySchedule>,
+    #[serde(default)]
+    more: bool,
+}
+
 pub struct PagerDuty {
-    http_client: Arc<Box<Client>>,
+    http_client: Arc<Client>,
     api_token: String,
     schedule_id: String,
 }
 
 impl PagerDuty {
-    pub fn new(http_client: Arc<Box<Client>>, api_token: String, schedule_id: String) -> PagerDuty {
+    pub fn new(http_client: Arc<Client>, api_token: String, schedule_id: String) -> PagerDuty {
         PagerDuty { http_client, api_token, schedule_id }
     }
 
@@ -32,7 +75,25 @@ impl PagerDuty {
         date_time.format("%Y-%m-%d %H:%M:%S").to_string()
     }
 
-    pub async fn get_on_call_users(&self, from: DateTime<Utc>) -> Result<Vec<PagerDutyUser>, AppError>{
+    /**
+     * List the on-call entries of the schedule overlapping the time range, with the shifts starting in the range
+     */
+    pub async fn get_schedule_on_calls(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<PagerDutyOnCall>, AppError> {
+        let since = self.format_datetime(&since);
+        let until = self.format_datetime(&until);
+
+        let query = [
+            ("schedule_ids[]", self.schedule_id.as_str()),
+            ("include[]", "users"),
+            ("time_zone", "UTC"),
+            ("since", since.as_str()),
+            ("until", until.as_str()),
+        ];
+
+        list_all_pages(&self.http_client, &self.api_token, &format!("{}/oncalls", PAGERDUTY_API_URL), &query, |page: PagerDutyOnCallsResponse| (page.oncalls, page.more)).await
+    }
+
+    pub async fn get_schedule_users(&self, from: DateTime<Utc>) -> Result<Vec<PagerDutyUser>, AppError>{
         let url = format!(
             "https://api.pagerduty.com/schedules/{}/users",
             &self.schedule_id
@@ -62,3 +123,138 @@ impl PagerDuty {
         }
     }
 }
+
+/**
+ * The users of the earliest shift starting after the given time, the shifts which already started are skipped
+ */
+pub fn next_shift(on_calls: Vec<PagerDutyOnCall>, after: DateTime<Utc>) -> Option<Shift> {
+    let starting: Vec<(DateTime<Utc>, PagerDutyOnCall)> = on_calls.into_iter()
+        .filter_map(|on_call| {
+            let start = DateTime::parse_from_rfc3339(on_call.start.as_deref()?).ok()?.with_timezone(&Utc);
+            Some((start, on_call))
+        })
+        .filter(|(start, _)| *start > after)
+        .collect();
+
+    let start = starting.iter().map(|(start, _)| *start).min()?;
+    let on_calls: Vec<PagerDutyOnCall> = starting.into_iter().filter(|(s, _)| *s == start).map(|(_, on_call)| on_call).collect();
+
+    Some(Shift {
+        schedule_name: on_calls.iter().find_map(|on_call| on_call.schedule.as_ref().map(|schedule| schedule.summary.clone())),
+        users: on_calls.into_iter().map(|on_call| OnCallUser { name: on_call.user.name, email: on_call.user.email, slack_user_id: None }).collect(),
+        start,
+    })
+}
+
+/**
+ * List the schedules visible to the api token, e.g. to choose one in the new schedule wizard
+ */
+pub async fn list_pagerduty_schedules(http_client: &Client, api_token: &str) -> Result<Vec<PagerDutySchedule>, AppError> {
+    list_all_pages(http_client, api_token, &format!("{}/schedules", PAGERDUTY_API_URL), &[], |page: PagerDutySchedulesResponse| (page.schedules, page.more)).await
+}
+
+/**
+ * Request the pages of a PagerDuty list endpoint from the offset after the previous page, while there are more items
+ */
+async fn list_all_pages<R, T>(http_client: &Client, api_token: &str, url: &str, query: &[(&str, &str)], into_items: impl Fn(R) -> (Vec<T>, bool)) -> Result<Vec<T>, AppError>
+where
+    R: DeserializeOwned,
+{
+    let mut items = vec![];
+    loop {
+        let response = http_client
+            .get(url)
+            .header("Authorization", format!("Token token={}", api_token))
+            .query(query)
+            .query(&[("limit", PAGE_SIZE), ("offset", items.len())])
+            .send()
+            .await?;
+
+        let page: R = match response.error_for_status() {
+            Ok(res) => res.json().await?,
+            Err(err) => {
+                println!("Error: {:?}", err);
+                return Err(AppError::PagerDutyError(err.to_string()));
+            }
+        };
+
+        let (page_items, more) = into_items(page);
+        let last_page = !more || page_items.is_empty();
+        items.extend(page_items);
+        if last_page {
+            return Ok(items);
+        }
+    }
+}
+
+#[async_trait]
+impl ScheduleProvider for PagerDuty {
+    async fn get_on_call_users(&self, at: DateTime<Utc>) -> Result<Vec<OnCallUser>, AppError> {
+        let users = self.get_schedule_users(at).await?;
+
+        Ok(users.into_iter().map(|user| OnCallUser { name: user.name, email: user.email, slack_user_id: None }).collect())
+    }
+
+    async fn get_next_shift(&self, after: DateTime<Utc>, until: DateTime<Utc>) -> Result<Option<Shift>, AppError> {
+        let on_calls = self.get_schedule_on_calls(after, until).await?;
+
+        Ok(next_shift(on_calls, after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::{extract::Query, routing::get, Json, Router};
+    use chrono::{TimeZone, Utc};
+    use reqwest::Client;
+    use serde_json::{json, Value};
+
+    use crate::service_provider::pager_duty::{list_all_pages, next_shift, PagerDutyOnCall, PagerDutyReference, PagerDutySchedulesResponse, PagerDutyUser, PAGE_SIZE};
+
+    fn on_call(name: &str, start: Option<&str>) -> PagerDutyOnCall {
+        PagerDutyOnCall {
+            user: PagerDutyUser { name: name.to_string(), email: format!("{}@example.com", name) },
+            schedule: Some(PagerDutyReference { summary: "Support primary".to_string() }),
+            start: start.map(|start| start.to_string()),
+        }
+    }
+
+    #[test]
+    fn find_the_next_shift_after_the_current_one() {
+        let after = Utc.with_ymd_and_hms(2023, 4, 2, 21, 0, 0).unwrap();
+        let on_calls = vec![
+            on_call("alice", Some("2023-03-27T09:00:00Z")),
+            on_call("bob", Some("2023-04-03T09:00:00Z")),
+            on_call("carol", Some("2023-04-03T09:00:00Z")),
+            on_call("dave", Some("2023-04-03T12:00:00Z")),
+            on_call("erin", None),
+        ];
+
+        let shift = next_shift(on_calls, after).unwrap();
+        assert_eq!(shift.start, Utc.with_ymd_and_hms(2023, 4, 3, 9, 0, 0).unwrap());
+        assert_eq!(shift.users.iter().map(|user| user.name.as_str()).collect::<Vec<&str>>(), vec!["bob", "carol"]);
+        assert_eq!(shift.schedule_name, Some("Support primary".to_string()));
+
+        assert_eq!(next_shift(vec![on_call("alice", Some("2023-03-27T09:00:00Z"))], after), None);
+    }
+
+    #[tokio::test]
+    async fn list_all_pages_until_no_more() {
+        // 250 schedules served in pages by the offset and limit
+        let app = Router::new().route("/schedules", get(|Query(query): Query<HashMap<String, usize>>| async move {
+            let (offset, limit) = (query["offset"], query["limit"]);
+            let schedules: Vec<Value> = (offset..250.min(offset + limit)).map(|i| json!({ "id": format!("P{}", i), "name": format!("Schedule {}", i) })).collect();
+            Json(json!({ "schedules": schedules, "more": offset + limit < 250 }))
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/schedules", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let schedules = list_all_pages(&Client::new(), "token", &url, &[], |page: PagerDutySchedulesResponse| (page.schedules, page.more)).await.unwrap();
+        assert_eq!(schedules.len(), 250);
+        assert_eq!(schedules[PAGE_SIZE].id, format!("P{}", PAGE_SIZE));
+        assert_eq!(schedules[249].name, "Schedule 249");
+    }
+}
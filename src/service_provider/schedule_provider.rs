@@ -0,0 +1,147 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::errors::AppError;
+use super::opsgenie::OpsgenieRegion;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Display)]
+#[display("{} <{}>", name, email)]
+pub struct OnCallUser {
+    pub name: String,
+    pub email: String,
+
+    // Set when the provider already knows the Slack user, e.g. the built-in rotations
+    pub slack_user_id: Option<String>,
+}
+
+/**
+ * The users on call from the start of a shift
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shift {
+    pub users: Vec<OnCallUser>,
+    pub start: DateTime<Utc>,
+
+    // The name of the schedule in the provider, if it's known
+    pub schedule_name: Option<String>,
+}
+
+/**
+ * A source of on-call rotations, e.g. PagerDuty
+ */
+#[async_trait]
+pub trait ScheduleProvider: Send + Sync {
+    /**
+     * Return the users who are on call at the given time
+     */
+    async fn get_on_call_users(&self, at: DateTime<Utc>) -> Result<Vec<OnCallUser>, AppError>;
+
+    /**
+     * Return the earliest shift starting after the given time, up to the until time.
+     * By default it's the users on call at the until time, which is the next update of the user group.
+     */
+    async fn get_next_shift(&self, _after: DateTime<Utc>, until: DateTime<Utc>) -> Result<Option<Shift>, AppError> {
+        let users = self.get_on_call_users(until).await?;
+
+        Ok(Some(Shift { users, start: until, schedule_name: None }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum ScheduleProviderKind {
+    #[display("pagerduty")]
+    PagerDuty,
+
+    #[display("opsgenie")]
+    Opsgenie,
+
+    #[display("ical")]
+    Ical,
+
+    #[display("rotation")]
+    Rotation,
+}
+
+/**
+ * Provider specific settings of a scheduled task. It's persisted as JSON, tagged by the provider kind.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Display)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum ScheduleProviderConfig {
+    #[serde(rename = "pagerduty")]
+    #[display("PagerDuty schedule {}", schedule_id)]
+    PagerDuty {
+        schedule_id: String,
+
+        // Falls back to the PagerDuty token of the Slack installation if not set
+        api_token: Option<String>,
+    },
+
+    #[serde(rename = "opsgenie")]
+    #[display("Opsgenie schedule {}", schedule_id)]
+    Opsgenie {
+        schedule_id: String,
+
+        // Falls back to the Opsgenie api key and region of the Slack installation if not set
+        api_key: Option<String>,
+        region: Option<OpsgenieRegion>,
+    },
+
+    // Either downloaded from the url or the calendar content stored on the task
+    #[serde(rename = "ical")]
+    #[display("iCalendar {}", url.as_deref().unwrap_or("stored on the task"))]
+    Ical {
+        url: Option<String>,
+        calendar: Option<String>,
+    },
+
+    // The built-in rotation with the same name in the Slack workspace of the task
+    #[serde(rename = "rotation")]
+    #[display("rotation {}", name)]
+    Rotation {
+        name: String,
+    },
+}
+
+impl ScheduleProviderConfig {
+    pub fn kind(&self) -> ScheduleProviderKind {
+        match self {
+            ScheduleProviderConfig::PagerDuty { .. } => ScheduleProviderKind::PagerDuty,
+            ScheduleProviderConfig::Opsgenie { .. } => ScheduleProviderKind::Opsgenie,
+            ScheduleProviderConfig::Ical { .. } => ScheduleProviderKind::Ical,
+            ScheduleProviderConfig::Rotation { .. } => ScheduleProviderKind::Rotation,
+        }
+    }
+
+    /**
+     * The id of the schedule in the provider
+     */
+    pub fn schedule_id(&self) -> &str {
+        match self {
+            ScheduleProviderConfig::PagerDuty { schedule_id, .. } => schedule_id,
+            ScheduleProviderConfig::Opsgenie { schedule_id, .. } => schedule_id,
+            ScheduleProviderConfig::Ical { url, .. } => url.as_deref().unwrap_or("ical"),
+            ScheduleProviderConfig::Rotation { name } => name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::service_provider::schedule_provider::{ScheduleProviderConfig, ScheduleProviderKind};
+
+    #[test]
+    fn serialize_provider_config_with_kind_tag() {
+        let config = ScheduleProviderConfig::PagerDuty { schedule_id: "P123ABC".to_string(), api_token: None };
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(json, r#"{"provider":"pagerduty","schedule_id":"P123ABC","api_token":null}"#);
+
+        let deserialized: ScheduleProviderConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, config);
+        assert_eq!(deserialized.kind(), ScheduleProviderKind::PagerDuty);
+        assert_eq!(deserialized.kind().to_string(), "pagerduty");
+    }
+}
@@ -25,6 +25,11 @@ struct UserLookupResponse {
     user: Option<User>,
 }
 
+#[derive(Deserialize, Debug)]
+struct PostMessageResponse {
+    ts: String,
+}
+
 #[derive(Deserialize, Debug)]
 struct ChannelResponse {
     channel: Option<Channel>,
@@ -36,12 +41,18 @@ struct UserGroupUsersResponse {
 }
 
 #[derive(Deserialize, Debug)]
-struct UserGroupsResponse {
-    usergroups: Option<Vec<UserGroup>>,
+struct ViewResponse {
+    view: OpenedView,
 }
 
 #[derive(Deserialize, Debug)]
-struct PostMessageResponse {
+struct OpenedView {
+    id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct UserGroupsResponse {
+    usergroups: Option<Vec<UserGroup>>,
 }
 
 #[derive(Deserialize, Debug, Display)]
@@ -51,6 +62,14 @@ pub struct Channel {
     pub is_channel: bool,
     pub is_group: bool,
     pub is_private: bool,
+
+    #[serde(default)]
+    pub topic: Option<ChannelTopic>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChannelTopic {
+    pub value: String,
 }
 
 #[derive(Deserialize, Debug, Display)]
@@ -70,12 +89,12 @@ pub struct UserGroup {
 }
 
 pub struct Slack {
-    http_client: Arc<Box<Client>>,
+    http_client: Arc<Client>,
     api_token: String,
 }
 
 impl Slack {
-    pub fn new(http_client: Arc<Box<Client>>, api_token: String) -> Slack {
+    pub fn new(http_client: Arc<Client>, api_token: String) -> Slack {
         Slack{ http_client, api_token}
     }
     
@@ -88,6 +107,51 @@ impl Slack {
         self.send_request::<_, ()>("chat.postMessage", Method::POST, None, Some(&payload)).await
     }
     
+    /**
+     * Post a message with the optional Block Kit blocks, as a reply when the thread is given, and return the ts of the message
+     */
+    pub async fn post_message(&self, channel_id: &str, text: &str, blocks: Option<&Value>, thread_ts: Option<&str>) -> Result<String, AppError> {
+        let mut payload = json!({
+            "channel": channel_id,
+            "text": text,
+        });
+        if let Some(blocks) = blocks {
+            payload["blocks"] = blocks.clone();
+        }
+        if let Some(thread_ts) = thread_ts {
+            payload["thread_ts"] = json!(thread_ts);
+        }
+
+        let response: PostMessageResponse = self.send_request::<_, ()>("chat.postMessage", Method::POST, None, Some(&payload)).await?;
+
+        Ok(response.ts)
+    }
+
+    /**
+     * Open a modal and return the id of the view, which can be updated after the trigger id expires
+     */
+    pub async fn open_view(&self, trigger_id: &str, view: &Value) -> Result<String, AppError> {
+        let payload = json!({
+            "trigger_id": trigger_id,
+            "view": view,
+        });
+
+        let response: ViewResponse = self.send_request::<_, ()>("views.open", Method::POST, None, Some(&payload)).await?;
+
+        Ok(response.view.id)
+    }
+
+    pub async fn update_view(&self, view_id: &str, view: &Value) -> Result<(), AppError> {
+        let payload = json!({
+            "view_id": view_id,
+            "view": view,
+        });
+
+        self.send_request::<EmptyResponse, ()>("views.update", Method::POST, None, Some(&payload)).await?;
+
+        Ok(())
+    }
+
     pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, AppError> {
         let params = json!({
             "email": email,
@@ -145,6 +209,15 @@ impl Slack {
         Ok(())
     }
 
+    pub async fn get_channel(&self, channel_id: &str) -> Result<Option<Channel>, AppError> {
+        let params = json!({
+            "channel": channel_id,
+        });
+
+        let response: ChannelResponse = self.send_request("conversations.info", Method::GET, Some(&params), None).await?;
+        Ok(response.channel)
+    }
+
     pub async fn update_channel_topic(&self, channel_id: &str, topic: &str) -> Result<Option<Channel>, AppError> {
         let payload = json!({
             "channel": channel_id,
@@ -233,6 +306,18 @@ pub struct SlackOauthResponse {
     pub is_enterprise_install: bool,
 }
 
+pub const SLACK_BOT_SCOPES: [&str; 9] = [
+    "app_mentions:read",
+    "channels:read",
+    "channels:write.topic",
+    "chat:write",
+    "commands",
+    "usergroups:read",
+    "usergroups:write",
+    "users:read",
+    "users:read.email",
+];
+
 pub async fn swap_slack_access_token(http_client: &Client, temp_token: &str, slack_client_id: &str, slack_client_secret: &str) -> Result<SlackOauthResponse, AppError> {
     println!("Swap slack access token");
     let params = json!({
@@ -1,17 +1,22 @@
-use std::{collections::HashMap, env};
-use aws_config::BehaviorVersion;
-use aws_lambda_events::{event::apigw::ApiGatewayProxyResponse, encodings::Body, http::{HeaderMap, HeaderValue}, query_map::QueryMap};
+use std::{collections::HashMap, sync::Arc};
+use aws_config::SdkConfig;
+use aws_lambda_events::{http::{HeaderMap, HeaderValue}, query_map::QueryMap};
 
-use chrono::{Local, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
 use chrono_tz::Tz;
 use std::str::FromStr;
-use crate::{scheduled_tasks::{ScheduledTask, ScheduledTasksDynamodb, EventBridgeScheduler}, cron::get_next_schedule_from, secrets::SecretsClient, encryptor::Encryptor, errors::AppError, build_http_client, service_provider::slack::swap_slack_access_token, db::{SlackInstallation, SlackInstallationsDynamoDb}, config::Config};
+use crate::{handoff_notification::{validate_template, HandoffNotification, MessageFormat, PostMode}, aliases::{EmailAlias, EmailAliasesRepository}, scheduled_tasks::{build_scheduler, GuardRails, MissingUserPolicy, ScheduledTask, ScheduledTasksRepository, TaskTrigger}, cron::{get_next_schedule_from, is_valid_cron}, secrets::load_secrets, encryptor::Encryptor, errors::AppError, build_http_client, timestamp::{get_timezone, parse_datetime_in_timezone}, service_provider::{opsgenie::OpsgenieRegion, schedule_provider::ScheduleProviderConfig, pager_duty::list_pagerduty_schedules, slack::{swap_slack_access_token, Slack, SLACK_BOT_SCOPES}}, db::{Repositories, SlackInstallation}, config::Config, rotations::{Rotation, RotationCadence, RotationsRepository}, overrides::{OverridesRepository, ScheduleOverride}, schedule_wizard::{loading_view, new_schedule_view, parse_new_schedule_submission, timezone_options, NEW_SCHEDULE_CALLBACK_ID, TIMEZONE_ACTION_ID}, slack_request_verifier::SlackRequestVerifier, http_router::HttpResponse};
 use form_urlencoded;
-use ring::hmac;
-use clap::{Args, Subcommand};
+use clap::{ArgGroup, Args, Subcommand};
 use clap::Parser;
 use lazy_static::lazy_static;
 use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::time::{timeout, Duration};
+
+// Slack gives up on the command after 3 seconds
+const LIST_PAGERDUTY_SCHEDULES_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -21,21 +26,76 @@ struct App {
 }
 
 #[derive(Debug, Args)]
+#[command(group(ArgGroup::new("schedule_provider").required(true).args(["pagerduty_schedule", "opsgenie_schedule", "ical_url", "ical_calendar", "rotation"])))]
 struct ScheduleArgs {
     #[arg(long)]
     user_group: String,
 
-    #[arg(long, default_value = "0 9 ? * MON-FRI *")]
-    pagerduty_schedule: String,
+    #[arg(long)]
+    pagerduty_schedule: Option<String>,
 
     #[arg(long)]
     pagerduty_api_key: Option<String>,
 
+    #[arg(long)]
+    opsgenie_schedule: Option<String>,
+
+    #[arg(long)]
+    opsgenie_api_key: Option<String>,
+
+    #[arg(long)]
+    opsgenie_region: Option<OpsgenieRegion>,
+
+    #[arg(long)]
+    ical_url: Option<String>,
+
+    #[arg(long)]
+    ical_calendar: Option<String>,
+
+    #[arg(long)]
+    rotation: Option<String>,
+
     #[arg(long)]
     cron: String,
 
     #[arg(long)]
     timezone: Option<String>,
+
+    // What to do when an on-call user isn't found in Slack: skip, fail or alias
+    #[arg(long, default_value = "skip")]
+    missing_user: MissingUserPolicy,
+
+    // Refuse the update when the group would be too small or too large, or change too many users at once. 0 for no limit
+    #[arg(long, default_value_t = 1)]
+    min_group_size: usize,
+
+    #[arg(long)]
+    max_group_size: Option<usize>,
+
+    #[arg(long)]
+    max_churn: Option<usize>,
+
+    // The channel message when the user group changes, with placeholders like {incoming} and {outgoing}
+    #[arg(long)]
+    message_template: Option<String>,
+
+    #[arg(long, default_value = "text")]
+    message_format: MessageFormat,
+
+    // Post every handoff as a new message, or in the thread of the first one
+    #[arg(long, default_value = "new")]
+    post_mode: PostMode,
+
+    #[arg(long)]
+    notify_incoming: bool,
+
+    // Keep the on-call users in the channel topic, e.g. "On call: {on_call} until {next_handoff}"
+    #[arg(long)]
+    topic_template: Option<String>,
+
+    // Send a direct message to the incoming users the hours before their shift, 0 for no reminder
+    #[arg(long)]
+    remind_before_hours: Option<u32>,
 }
 
 #[derive(Debug, Args)]
@@ -44,10 +104,267 @@ struct SetupPagerdutyArgs {
     pagerduty_api_key: String,
 }
 
+impl ScheduleArgs {
+    fn guard_rails(&self) -> GuardRails {
+        GuardRails {
+            min_group_size: self.min_group_size,
+            max_group_size: optional_limit(self.max_group_size),
+            max_churn: optional_limit(self.max_churn),
+        }
+    }
+
+    fn notification(&self) -> Result<HandoffNotification, AppError> {
+        for template in self.message_template.iter().chain(self.topic_template.iter()) {
+            validate_template(template)?;
+        }
+
+        Ok(HandoffNotification {
+            template: self.message_template.clone(),
+            format: self.message_format,
+            post_mode: self.post_mode,
+            notify_incoming: self.notify_incoming,
+            topic_template: self.topic_template.clone(),
+            remind_before_hours: self.remind_before_hours.filter(|hours| *hours > 0),
+        })
+    }
+
+    fn provider_config(&self) -> ScheduleProviderConfig {
+        if let Some(opsgenie_schedule) = &self.opsgenie_schedule {
+            ScheduleProviderConfig::Opsgenie {
+                schedule_id: opsgenie_schedule.clone(),
+                api_key: self.opsgenie_api_key.clone(),
+                region: self.opsgenie_region,
+            }
+        } else if let Some(rotation) = &self.rotation {
+            ScheduleProviderConfig::Rotation { name: rotation.clone() }
+        } else if self.ical_url.is_some() || self.ical_calendar.is_some() {
+            ScheduleProviderConfig::Ical {
+                url: self.ical_url.clone(),
+                calendar: self.ical_calendar.clone(),
+            }
+        } else {
+            ScheduleProviderConfig::PagerDuty {
+                schedule_id: self.pagerduty_schedule.clone().unwrap_or_default(),
+                api_token: self.pagerduty_api_key.clone(),
+            }
+        }
+    }
+}
+
+// The limits of the guard rails are removed by 0
+fn optional_limit(limit: Option<usize>) -> Option<usize> {
+    limit.filter(|limit| *limit > 0)
+}
+
+#[derive(Debug, Args)]
+struct SetupOpsgenieArgs {
+    #[arg(long)]
+    opsgenie_api_key: String,
+
+    #[arg(long, default_value = "us")]
+    region: OpsgenieRegion,
+}
+
+#[derive(Debug, Args)]
+struct RotationArgs {
+    #[command(subcommand)]
+    command: RotationCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum RotationCommand {
+    Create(RotationCreateArgs),
+    Add(RotationMembersArgs),
+    Remove(RotationMembersArgs),
+    Show(RotationShowArgs),
+}
+
+#[derive(Debug, Args)]
+struct RotationCreateArgs {
+    name: String,
+
+    #[arg(long, num_args = 1.., required = true)]
+    members: Vec<String>,
+
+    // daily, weekly or a cron expression
+    #[arg(long, default_value = "weekly")]
+    cadence: String,
+
+    #[arg(long)]
+    timezone: Option<String>,
+
+    #[arg(long)]
+    start: Option<String>,
+
+    #[arg(long, default_value_t = 1)]
+    shift_size: usize,
+}
+
+#[derive(Debug, Args)]
+struct RotationMembersArgs {
+    name: String,
+
+    #[arg(num_args = 1.., required = true)]
+    users: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+struct RotationShowArgs {
+    name: String,
+}
+
+#[derive(Debug, Args)]
+struct AliasArgs {
+    #[command(subcommand)]
+    command: AliasCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum AliasCommand {
+    Add(AliasAddArgs),
+    Remove(AliasRemoveArgs),
+    List,
+}
+
+// alias add bob@example.com @bob
+#[derive(Debug, Args)]
+struct AliasAddArgs {
+    // The email in the schedule provider
+    email: String,
+
+    user: String,
+}
+
+#[derive(Debug, Args)]
+struct AliasRemoveArgs {
+    email: String,
+}
+
+// override <@alice> for <@bob> from <time> to <time>, override list, or override delete <id>
+#[derive(Debug, Args)]
+struct OverrideArgs {
+    // Either is required when there are more than one schedules in the channel
+    #[arg(long)]
+    user_group: Option<String>,
+
+    #[arg(long)]
+    task: Option<String>,
+
+    #[arg(num_args = 1.., required = true)]
+    words: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum OverrideCommand {
+    Create { replacements: Vec<String>, replaced: Vec<String>, start: String, end: String },
+    List,
+    Delete(String),
+}
+
+impl OverrideArgs {
+    fn command(&self) -> Result<OverrideCommand, AppError> {
+        let words: Vec<&str> = self.words.iter().map(|w| w.as_str()).collect();
+        let usage = "Usage: override @alice for @bob from 2024-01-02T09:00 to 2024-01-03T09:00, override list, or override delete <id>";
+
+        match words.as_slice() {
+            ["list"] => Ok(OverrideCommand::List),
+            ["delete", override_id] => Ok(OverrideCommand::Delete(override_id.to_string())),
+            _ => {
+                let position = |keyword: &str| words.iter().position(|w| w.eq_ignore_ascii_case(keyword));
+                match (position("for"), position("from"), position("to")) {
+                    (Some(for_index), Some(from_index), Some(to_index)) if 0 < for_index && for_index + 1 < from_index && from_index + 1 < to_index && to_index + 1 < words.len() => {
+                        Ok(OverrideCommand::Create {
+                            replacements: self.words[..for_index].to_vec(),
+                            replaced: self.words[for_index + 1..from_index].to_vec(),
+                            start: self.words[from_index + 1..to_index].join(" "),
+                            end: self.words[to_index + 1..].join(" "),
+                        })
+                    },
+                    _ => Err(AppError::OverrideError(usage.to_string())),
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+struct TaskArgs {
+    // The task id, or the user group of the schedule in the current channel
+    task: String,
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("fields").required(true).multiple(true).args(["cron", "timezone", "pagerduty_schedule", "missing_user", "min_group_size", "max_group_size", "max_churn", "message_template", "message_format", "post_mode", "notify_incoming", "topic_template", "remind_before_hours"])))]
+struct EditArgs {
+    task: String,
+
+    #[arg(long)]
+    cron: Option<String>,
+
+    #[arg(long)]
+    timezone: Option<String>,
+
+    #[arg(long)]
+    pagerduty_schedule: Option<String>,
+
+    #[arg(long)]
+    missing_user: Option<MissingUserPolicy>,
+
+    #[arg(long)]
+    min_group_size: Option<usize>,
+
+    #[arg(long)]
+    max_group_size: Option<usize>,
+
+    #[arg(long)]
+    max_churn: Option<usize>,
+
+    // An empty template restores the default message
+    #[arg(long)]
+    message_template: Option<String>,
+
+    #[arg(long)]
+    message_format: Option<MessageFormat>,
+
+    #[arg(long)]
+    post_mode: Option<PostMode>,
+
+    #[arg(long)]
+    notify_incoming: Option<bool>,
+
+    // An empty template stops updating the channel topic
+    #[arg(long)]
+    topic_template: Option<String>,
+
+    // 0 stops the reminders
+    #[arg(long)]
+    remind_before_hours: Option<u32>,
+}
+
+// pause <task> [until <date>]
+#[derive(Debug, Args)]
+struct PauseArgs {
+    task: String,
+
+    #[arg(num_args = 0..)]
+    until: Vec<String>,
+}
+
+impl PauseArgs {
+    fn resume_at(&self) -> Result<Option<String>, AppError> {
+        match self.until.split_first() {
+            None => Ok(None),
+            Some((keyword, date)) if keyword.eq_ignore_ascii_case("until") && !date.is_empty() => Ok(Some(date.join(" "))),
+            _ => Err(AppError::ScheduledTaskError("Usage: pause <task> [until 2024-01-02T09:00]".to_string())),
+        }
+    }
+}
+
 #[derive(Debug, Args)]
 struct ListSchedulesArgs {
+    // List the schedules of all channels in the workspace, instead of the current channel
     #[arg(long)]
-    all: Option<bool>,
+    all: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -55,6 +372,14 @@ enum Command {
     Schedule(ScheduleArgs),
     ListSchedules(ListSchedulesArgs),
     SetupPagerduty(SetupPagerdutyArgs),
+    SetupOpsgenie(SetupOpsgenieArgs),
+    Rotation(RotationArgs),
+    Override(OverrideArgs),
+    Alias(AliasArgs),
+    Edit(EditArgs),
+    Delete(TaskArgs),
+    Pause(PauseArgs),
+    Resume(TaskArgs),
     New,
 }
 
@@ -70,26 +395,340 @@ fn cleanse(text: &str) -> String {
     cleansed.to_string()
 }
 
+/**
+ * Slack escapes the mentioned users as <@U123|name>, a plain user id is accepted as well
+ */
+fn parse_slack_user(text: &str) -> Option<String> {
+    lazy_static! {
+        static ref SLACK_USER: Regex = Regex::new(r"^(?:<@([UW][A-Z0-9]+)(?:\|[^>]*)?>|([UW][A-Z0-9]+))$").unwrap();
+    }
+
+    SLACK_USER.captures(text.trim())
+        .and_then(|captures| captures.get(1).or(captures.get(2)))
+        .map(|user_id| user_id.as_str().to_string())
+}
+
+/**
+ * Slack escapes the emails as <mailto:bob@example.com|bob@example.com>, a plain email is accepted as well
+ */
+fn parse_email(text: &str) -> Option<String> {
+    lazy_static! {
+        static ref EMAIL: Regex = Regex::new(r"^(?:<mailto:([^|>]+@[^|>]+)(?:\|[^>]*)?>|([^<>|\s]+@[^<>|\s]+))$").unwrap();
+    }
+
+    EMAIL.captures(text.trim())
+        .and_then(|captures| captures.get(1).or(captures.get(2)))
+        .map(|email| EmailAlias::normalize_email(email.as_str()))
+}
+
+fn parse_slack_users(texts: &[String]) -> Result<Vec<String>, AppError> {
+    texts.iter()
+        .map(|text| parse_slack_user(text).ok_or(AppError::RotationError(format!("Invalid Slack user: {}, please mention the user with @", text))))
+        .collect()
+}
+
+async fn handle_rotation_command(command: RotationCommand, team: String, user_id: String, user_name: String, db: &dyn RotationsRepository) -> Result<Vec<String>, AppError> {
+    match command {
+        RotationCommand::Create(args) => {
+            if db.get_rotation(&team, &args.name).await?.is_some() {
+                return Err(AppError::RotationError(format!("Rotation {} already exists", args.name)));
+            }
+
+            let timezone_name = args.timezone.unwrap_or("UTC".to_string());
+            let timezone = Tz::from_str(&timezone_name).map_err(|_| AppError::RotationError(format!("Unknown timezone: {}", timezone_name)))?;
+            let start = match &args.start {
+                Some(start) => parse_datetime_in_timezone(start, &timezone)?,
+                None => Utc::now().with_timezone(&timezone),
+            };
+
+            let rotation = Rotation {
+                team,
+                name: args.name,
+                members: parse_slack_users(&args.members)?,
+                cadence: RotationCadence::from_str(&args.cadence)?,
+                timezone: timezone.to_string(),
+                start_at: start.to_rfc3339_opts(SecondsFormat::Secs, false),
+                shift_size: args.shift_size.max(1),
+
+                created_by_user_id: user_id,
+                created_by_user_name: user_name,
+                created_at: Utc::now().to_rfc3339(),
+                last_updated_at: Utc::now().to_rfc3339(),
+            };
+            db.save_rotation(&rotation).await?;
+
+            Ok(vec!(format!("Created rotation {} with {} members, handing over {} from {}", rotation.name, rotation.members.len(), rotation.cadence, rotation.start_at)))
+        },
+        RotationCommand::Add(args) => {
+            let mut rotation = db.get_rotation(&team, &args.name).await?
+                .ok_or(AppError::RotationError(format!("Rotation {} not found", args.name)))?;
+
+            for member in parse_slack_users(&args.users)? {
+                if !rotation.members.contains(&member) {
+                    rotation.members.push(member);
+                }
+            }
+            rotation.last_updated_at = Utc::now().to_rfc3339();
+            db.save_rotation(&rotation).await?;
+
+            Ok(vec!(format!("Rotation {} members: {}", rotation.name, format_slack_users(&rotation.members))))
+        },
+        RotationCommand::Remove(args) => {
+            let mut rotation = db.get_rotation(&team, &args.name).await?
+                .ok_or(AppError::RotationError(format!("Rotation {} not found", args.name)))?;
+
+            let removed_members = parse_slack_users(&args.users)?;
+            rotation.members.retain(|member| !removed_members.contains(member));
+            rotation.last_updated_at = Utc::now().to_rfc3339();
+            db.save_rotation(&rotation).await?;
+
+            Ok(vec!(format!("Rotation {} members: {}", rotation.name, format_slack_users(&rotation.members))))
+        },
+        RotationCommand::Show(args) => {
+            let rotation = db.get_rotation(&team, &args.name).await?
+                .ok_or(AppError::RotationError(format!("Rotation {} not found", args.name)))?;
+
+            let now = Utc::now();
+            let next_handoff = rotation.next_handoff_after(&now)?
+                .map(|handoff| handoff.to_rfc3339())
+                .unwrap_or("never".to_string());
+
+            Ok(vec!(
+                format!("Rotation {}: {} people on shift, handing over {} in {} from {}", rotation.name, rotation.shift_size, rotation.cadence, rotation.timezone, rotation.start_at),
+                format!("Members: {}", format_slack_users(&rotation.members)),
+                format!("On shift: {}, next handoff: {}", format_slack_users(&rotation.on_call_members_at(&now)?), next_handoff),
+            ))
+        },
+    }
+}
+
+async fn handle_alias_command(command: AliasCommand, team: String, user_id: String, user_name: String, db: &dyn EmailAliasesRepository) -> Result<Vec<String>, AppError> {
+    let parse_alias_email = |text: &str| parse_email(text).ok_or(AppError::AliasError(format!("Invalid email: {}", text)));
+
+    match command {
+        AliasCommand::Add(args) => {
+            let alias = EmailAlias {
+                team,
+                email: parse_alias_email(&args.email)?,
+                slack_user_id: parse_slack_user(&args.user)
+                    .ok_or(AppError::AliasError(format!("Invalid Slack user: {}, please mention the user with @", args.user)))?,
+
+                created_by_user_id: user_id,
+                created_by_user_name: user_name,
+                created_at: Utc::now().to_rfc3339(),
+            };
+            db.save_alias(&alias).await?;
+
+            Ok(vec!(format!("Mapped {} to <@{}>", alias.email, alias.slack_user_id)))
+        },
+        AliasCommand::Remove(args) => {
+            let email = parse_alias_email(&args.email)?;
+            if !db.list_aliases(&team).await?.iter().any(|alias| alias.email == email) {
+                return Err(AppError::AliasError(format!("No alias found for {}", email)));
+            }
+            db.delete_alias(&team, &email).await?;
+
+            Ok(vec!(format!("Removed the alias of {}", email)))
+        },
+        AliasCommand::List => {
+            let aliases = db.list_aliases(&team).await?;
+            if aliases.is_empty() {
+                return Ok(vec!(format!("No email aliases in this workspace")));
+            }
+
+            Ok(aliases.iter().map(|alias| format!("{} -> <@{}>", alias.email, alias.slack_user_id)).collect())
+        },
+    }
+}
+
+/**
+ * Parse the escaped user group, e.g. <!subteam^S123|@support>, into the user group id and handle
+ */
+fn parse_user_group(text: &str) -> Option<(String, String)> {
+    lazy_static! {
+        static ref USER_GROUP: Regex = Regex::new(r"<!subteam\^(\w+)\|@([^>]+)>").unwrap();
+    }
+
+    USER_GROUP.captures(text)
+        .map(|captures| (captures.get(1).unwrap().as_str().to_string(), captures.get(2).unwrap().as_str().to_string()))
+}
+
+async fn find_channel_tasks(db: &dyn ScheduledTasksRepository, team: &str, channel_id: &str, user_group: Option<&str>, task_id: Option<&str>) -> Result<Vec<ScheduledTask>, AppError> {
+    let user_group_id = match user_group {
+        Some(user_group) => Some(parse_user_group(user_group).ok_or(AppError::OverrideError(format!("Invalid user group: {}", user_group)))?.0),
+        None => None,
+    };
+
+    let (team_id, enterprise_id) = team.split_once(':').unwrap_or((team, ""));
+    Ok(db.list_scheduled_tasks_in_workspace(team_id, enterprise_id).await?
+        .into_iter()
+        .filter(|t| t.channel_id == channel_id)
+        .filter(|t| user_group_id.as_ref().map(|id| &t.user_group_id == id).unwrap_or(true))
+        .filter(|t| task_id.map(|id| t.task_id == id || t.legacy_task_id.as_deref() == Some(id)).unwrap_or(true))
+        .collect())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_override_command(args: OverrideArgs, team: String, channel_id: String, user_id: String, user_name: String, tasks_db: &dyn ScheduledTasksRepository, overrides_db: &dyn OverridesRepository) -> Result<Vec<String>, AppError> {
+    let command = args.command()?;
+    let tasks = find_channel_tasks(tasks_db, &team, &channel_id, args.user_group.as_deref(), args.task.as_deref()).await?;
+    if tasks.is_empty() {
+        return Err(AppError::OverrideError("No schedule found in this channel".to_string()));
+    }
+
+    let now = Utc::now();
+    match command {
+        OverrideCommand::Create { replacements, replaced, start, end } => {
+            let task = match tasks.as_slice() {
+                [task] => task,
+                _ => {
+                    let user_groups = tasks.iter().map(|t| format!("@{} ({})", t.user_group_handle, t.task_id)).collect::<Vec<String>>().join(", ");
+                    return Err(AppError::OverrideError(format!("There are {} schedules in this channel: {}, please choose one with --user-group or --task", tasks.len(), user_groups)));
+                },
+            };
+
+            let timezone = get_timezone(&task.timezone);
+            let start = parse_datetime_in_timezone(&start, &timezone)?;
+            let end = parse_datetime_in_timezone(&end, &timezone)?;
+            if end <= start || end <= now {
+                return Err(AppError::OverrideError(format!("The override should end after {} and in the future", start.to_rfc3339())));
+            }
+
+            let schedule_override = ScheduleOverride {
+                task: ScheduleOverride::task_key(&task.team, &task.task_id),
+                override_id: format!("{:08x}", rand::random::<u32>()),
+                team: task.team.clone(),
+                task_id: task.task_id.clone(),
+                replacement_user_ids: parse_slack_users(&replacements).map_err(|_| AppError::OverrideError(format!("Invalid Slack users: {}", replacements.join(" "))))?,
+                replaced_user_ids: parse_slack_users(&replaced).map_err(|_| AppError::OverrideError(format!("Invalid Slack users: {}", replaced.join(" "))))?,
+                start_timestamp_utc: start.timestamp(),
+                end_timestamp_utc: end.timestamp(),
+                start_time: start.to_rfc3339(),
+                end_time: end.to_rfc3339(),
+
+                created_by_user_id: user_id,
+                created_by_user_name: user_name,
+                created_at: now.to_rfc3339(),
+            };
+            overrides_db.save_override(&schedule_override).await?;
+
+            Ok(vec!(format!("Override {}: {} covers {} in @{} from {} to {}, it takes effect at the next update of the user group",
+                schedule_override.override_id,
+                format_slack_users(&schedule_override.replacement_user_ids),
+                format_slack_users(&schedule_override.replaced_user_ids),
+                task.user_group_handle,
+                schedule_override.start_time,
+                schedule_override.end_time,
+            )))
+        },
+        OverrideCommand::List => {
+            let mut messages = vec![];
+            for task in &tasks {
+                for o in overrides_db.list_overrides(&task.team, &task.task_id).await? {
+                    if o.end_timestamp_utc > now.timestamp() {
+                        messages.push(format!("{}: {} covers {} in @{} from {} to {}", o.override_id, format_slack_users(&o.replacement_user_ids), format_slack_users(&o.replaced_user_ids), task.user_group_handle, o.start_time, o.end_time));
+                    }
+                }
+            }
+
+            if messages.is_empty() {
+                messages.push("No upcoming overrides in this channel".to_string());
+            }
+            Ok(messages)
+        },
+        OverrideCommand::Delete(override_id) => {
+            for task in &tasks {
+                let overrides = overrides_db.list_overrides(&task.team, &task.task_id).await?;
+                if overrides.iter().any(|o| o.override_id == override_id) {
+                    overrides_db.delete_override(&task.team, &task.task_id, &override_id).await?;
+                    return Ok(vec!(format!("Deleted override {} of @{}", override_id, task.user_group_handle)));
+                }
+            }
+
+            Err(AppError::OverrideError(format!("Override {} not found in this channel", override_id)))
+        },
+    }
+}
+
+/**
+ * Reply the errors caused by the input of the user, instead of failing the request
+ */
+fn reply_user_errors(result: Result<Vec<String>, AppError>) -> Result<Vec<String>, AppError> {
+    match result {
+        Ok(messages) => Ok(messages),
+        Err(err) => user_error_message(&err).map(|message| vec!(message)).ok_or(err),
+    }
+}
+
+fn user_error_message(err: &AppError) -> Option<String> {
+    match err {
+        AppError::RotationError(message)
+        | AppError::OverrideError(message)
+        | AppError::AliasError(message)
+        | AppError::ScheduledTaskError(message)
+        | AppError::InvalidDateTimeError(message) => Some(message.clone()),
+        AppError::ConcurrentUpdateError(_) => Some("The schedule was changed at the same time, e.g. by another command or the updater, please try again".to_string()),
+        _ => None,
+    }
+}
+
+fn parse_task_timezone(timezone: &str) -> Result<Tz, AppError> {
+    Tz::from_str(timezone).map_err(|_| AppError::ScheduledTaskError(format!("Unknown timezone: {}", timezone)))
+}
+
+fn format_slack_users(user_ids: &[String]) -> String {
+    user_ids.iter().map(|id| format!("<@{}>", id)).collect::<Vec<String>>().join(", ")
+}
+
 fn get_param(params: &HashMap<String, String>, name: &str) -> String {
     params.get(&name.to_string()).unwrap_or(&"".to_string()).to_string()
 }
 
-pub async fn handle_slack_oauth(env: &str, query_map: QueryMap) -> Result<ApiGatewayProxyResponse, AppError> {
+pub async fn handle_slack_install(env: &str) -> Result<HttpResponse, AppError> {
+    let config = Config::new(env)?;
+    let aws_config = config.load_aws_config().await;
+    let secrets = load_secrets(&aws_config, &config.secrets_source).await?;
+
+    let state = SlackRequestVerifier::from_secrets(&secrets).sign_oauth_state(Utc::now().timestamp())?;
+    let authorize_url = format!(
+        "https://slack.com/oauth/v2/authorize?{}",
+        form_urlencoded::Serializer::new(String::new())
+            .append_pair("client_id", &secrets.slack_client_id)
+            .append_pair("scope", &SLACK_BOT_SCOPES.join(","))
+            .append_pair("state", &state)
+            .finish()
+    );
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert("Location", authorize_url.parse().unwrap());
+
+    Ok(HttpResponse {
+        status_code: 302,
+        headers: response_headers,
+        ..Default::default()
+    })
+}
+
+pub async fn handle_slack_oauth(env: &str, query_map: QueryMap) -> Result<HttpResponse, AppError> {
     let code_parameter = query_map.first("code");
 
     match code_parameter {
         Some(temporary_code) => {
             let http_client = build_http_client()?;
-            let config = ::aws_config::load_defaults(BehaviorVersion::latest()).await;
-            let secrets_client = SecretsClient::new(&config);
-            let secrets = secrets_client.get_secret("on-call-support/secrets").await?;
+            let app_config = Config::new(env)?;
+            let config = app_config.load_aws_config().await;
+            let secrets = load_secrets(&config, &app_config.secrets_source).await?;
+
+            if let Err(err) = SlackRequestVerifier::from_secrets(&secrets).verify_oauth_state(query_map.first("state"), Utc::now().timestamp()) {
+                println!("Rejected Slack OAuth callback, error: {:?}", err);
+                return Ok(response(401, format!("Invalid slack oauth state: {}", err)));
+            }
 
             let encryptor = Encryptor::new(&secrets.encryption_key);
+            let repositories = Repositories::from_config(&config, &app_config, encryptor).await?;
 
             let oauth_response = swap_slack_access_token(&http_client, temporary_code, &secrets.slack_client_id, &secrets.slack_client_secret).await?;
             
-            // Save to dynamodb
-            let db = SlackInstallationsDynamoDb::new(&config, format!("on-call-support-installations-{}", env), encryptor);
             let installation = SlackInstallation {
                 team_id: oauth_response.team.id,
                 team_name: oauth_response.team.name,
@@ -106,207 +745,892 @@ pub async fn handle_slack_oauth(env: &str, query_map: QueryMap) -> Result<ApiGat
                 bot_user_id: oauth_response.bot_user_id,
 
                 pager_duty_token: None,
+                opsgenie_token: None,
+                opsgenie_region: None,
             };
 
-            db.save_slack_installation(&installation).await?;
-            Ok(response(200, format!("Received slack oauth callback.")))
+            repositories.installations.save_slack_installation(&installation).await?;
+            Ok(response(200, "Received slack oauth callback.".to_string()))
         },
-        None => Ok(response(400, format!("Invalid request"))),
+        None => Ok(response(400, "Invalid request".to_string())),
     }
 }
 
-pub async fn handle_slack_command(env: &str, request_header: HeaderMap<HeaderValue>, request_body: Option<String>) -> Result<ApiGatewayProxyResponse, AppError> {
-    let request_body = request_body.unwrap_or_default();
-    
-    let params: HashMap<String, String> = form_urlencoded::parse(request_body.as_bytes()).into_owned().collect();
-    // println!("params in body: {:?}", params);
-
-    let team_id = get_param(&params, "team_id");
-    let team_domain = get_param(&params, "team_domain");
-    let channel_id = get_param(&params, "channel_id");
-    let channel_name = get_param(&params, "channel_name");
-    let enterprise_id = get_param(&params, "enterprise_id");
-    let enterprise_name = get_param(&params, "enterprise_name");
-    let is_enterprise_install = get_param(&params, "is_enterprise_install").eq_ignore_ascii_case("true");
+/**
+ * The Slack workspace, channel and user of a command
+ */
+#[derive(Debug, Serialize, Deserialize)]
+struct SlackRequestContext {
+    team_id: String,
+    team_domain: String,
+    channel_id: String,
+    channel_name: String,
+    enterprise_id: String,
+    enterprise_name: String,
+    is_enterprise_install: bool,
 
-    let user_id = get_param(&params, "user_id");
-    let user_name = get_param(&params, "user_name");
-    let command = get_param(&params, "command");
-    let text = get_param(&params, "text");
-    let _response_url = get_param(&params, "response_url");
-    let slack_request_timestamp_str = request_header.get("X-Slack-Request-Timestamp").map(|v| v.to_str())
-        .expect("Missing X-Slack-Request-Timestamp")?;
-    let slack_request_signature = request_header.get("X-Slack-Signature").map(|v| v.to_str())
-        .expect("Missing X-Slack-Signature")?;
-    let now = Local::now().timestamp();
-
-    // println!("parsed parameter: {}", json!({
-    //     "team_id": team_id,
-    //     "team_domain": team_domain,
-    //     "channel_id": channel_id,
-    //     "channel_name": channel_name,
-    //     "user_id": user_id,
-    //     "user_name": user_name,
-    //     "command": command,
-    //     "text": text,
-    //     "response_url": response_url,
-    //     "X-Slack-Request-Timestamp": slack_request_timestamp_str,
-    //     "X-Slack-Signature": slack_request_signature,
-    //     "current timestamp": now,
-    // }));
-    
-    let slack_request_timestamp: i64 = slack_request_timestamp_str.parse::<i64>().expect("failed to parse timestamp");
-        
-    if (now - slack_request_timestamp).abs() > 60 * 5 {
-        return Ok(ApiGatewayProxyResponse {
-            status_code: 400,
-            body: Some(Body::from(format!("Invalid slack command due to invalid timestamp: {} {}", command, text))),
-            ..Default::default()
-        })
-    }
-    
-    let sig_basestring = format!("v0:{}:{}", slack_request_timestamp, request_body);
-    // println!("string to sign: {:?}", sig_basestring);
-
-    let signing_key = "aa2ad1a24622382aa823959083867312";
-    let verification_key = hmac::Key::new(hmac::HMAC_SHA256, signing_key.as_bytes());
-    let signature = hex::encode(hmac::sign(&verification_key, sig_basestring.as_bytes()).as_ref());
-
-    if format!("v0={}", signature) != slack_request_signature {
-        println!("Signature doesn't match {} vs {}", signature, slack_request_signature);
-        return Ok(ApiGatewayProxyResponse {
-            status_code: 400,
-            body: Some(Body::from(format!("Invalid slack command signature: {} {}", command, text))),
-            ..Default::default()
-        })
-    } else {
-        // println!("Signature matched");
-    }
-    
-    let arg = match shlex::split(cleanse(format!("{} {}", command, text).as_str()).as_str()) {
-        Some(args) => Some(App::parse_from(args.iter())),
-        None => None
-    };
+    user_id: String,
+    user_name: String,
+}
 
-    // println!("Parsed arg: {:?}", arg);
-    
-    let aws_config = ::aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let secrets = SecretsClient::new(&aws_config).get_secret("on-call-support/secrets").await?;
-    let encryptor = Encryptor::new(&secrets.encryption_key);
+impl SlackRequestContext {
+    fn from_params(params: &HashMap<String, String>) -> SlackRequestContext {
+        SlackRequestContext {
+            team_id: get_param(params, "team_id"),
+            team_domain: get_param(params, "team_domain"),
+            channel_id: get_param(params, "channel_id"),
+            channel_name: get_param(params, "channel_name"),
+            enterprise_id: get_param(params, "enterprise_id"),
+            enterprise_name: get_param(params, "enterprise_name"),
+            is_enterprise_install: get_param(params, "is_enterprise_install").eq_ignore_ascii_case("true"),
 
-    let response_body = match arg.unwrap().command {
-        Some(Command::Schedule(arg)) => {
-            // 
-            let user_group_id: String;
-            let user_group_handle: String;
-
-            let re = Regex::new(r"<!subteam\^(\w+)\|@([^>]+)>").unwrap();
-            if let Some(captures) = re.captures(arg.user_group.as_str()) {
-                user_group_id = captures.get(1).unwrap().as_str().to_string();
-                user_group_handle = captures.get(2).unwrap().as_str().to_string();
-            } else {
-                println!("Invalid user group: {}", arg.user_group);
+            user_id: get_param(params, "user_id"),
+            user_name: get_param(params, "user_name"),
+        }
+    }
+}
 
-                return Ok(ApiGatewayProxyResponse {
-                    status_code: 400,
-                    body: Some(Body::from(format!("Invalid user group: {}", arg.user_group))),
-                    ..Default::default()
-                })
-            }
-            
-            let lambda_arn = env::var("UPDATE_USER_GROUP_LAMBDA")?;
-            let lambda_role = env::var("UPDATE_USER_GROUP_LAMBDA_ROLE")?;
+/**
+ * Save the task and arm the scheduler, shared by the schedule command and the new schedule wizard
+ */
+#[allow(clippy::too_many_arguments)]
+async fn create_scheduled_task(
+    aws_config: &SdkConfig,
+    config: &Config,
+    repositories: &Repositories,
+    context: SlackRequestContext,
+    user_group_id: String,
+    user_group_handle: String,
+    provider_config: ScheduleProviderConfig,
+    cron: String,
+    timezone: Tz,
+    missing_user_policy: MissingUserPolicy,
+    guard_rails: GuardRails,
+    notification: HandoffNotification,
+) -> Result<ScheduledTask, AppError> {
+    let db = repositories.scheduled_tasks.as_ref();
+    let scheduler = build_scheduler(aws_config, config)?;
 
-            let db = ScheduledTasksDynamodb::new(&aws_config, format!("on-call-support-schedules-{}", env), encryptor);
-            let scheduler = EventBridgeScheduler::new(&aws_config, format!("on-call-support-dev_UpdateUserGroupSchedule_"), lambda_arn, lambda_role);
+    let now = Utc::now();
+    let next_schedule = get_next_schedule_from(&cron, &now.with_timezone(&timezone))
+        .ok_or(AppError::UnexpectedError(format!("The cron {} has no future scheduled time from now", cron)))?;
 
-            let timezone = Tz::from_str(&arg.timezone.unwrap_or("UTC".to_string())).unwrap();
-            let from = Utc::now().with_timezone(&timezone);
+    let team = format!("{}:{}", &context.team_id, &context.enterprise_id);
 
-            let next_schedule = get_next_schedule_from(&arg.cron, &from).expect("The cron has no future scheduled time from now");
+    // Scheduling the same user group and provider schedule in the channel again updates the existing task
+    let existing_task = db.list_scheduled_tasks_in_workspace(&context.team_id, &context.enterprise_id).await?
+        .into_iter()
+        .find(|t| t.channel_id == context.channel_id && t.user_group_id == user_group_id && t.provider_config.schedule_id() == provider_config.schedule_id());
 
-            let task_id = format!("{}:{}:{}:{}:{}", channel_name, channel_id, user_group_handle, user_group_id, arg.pagerduty_schedule);
+    let task = match existing_task {
+        // Only the settings of the command are changed, the state of the previous runs and the pause are kept
+        Some(mut task) => {
+            task.channel_name = context.channel_name;
+            task.user_group_handle = user_group_handle;
+            task.provider_config = provider_config;
+            task.cron = cron;
+            task.timezone = timezone.to_string();
+            task.missing_user_policy = missing_user_policy;
+            task.guard_rails = guard_rails;
+            task.notification = notification;
+            task.last_updated_at = now.to_rfc3339();
+            task.set_next_schedule_from(&now);
 
-            let task = ScheduledTask {
-                team: format!("{}:{}", &team_id, &enterprise_id),
-                task_id,
+            db.save_scheduled_task(&task).await?;
+            task
+        },
+        None => {
+            let mut task = ScheduledTask {
+                team,
+                task_id: "".to_string(),
+                legacy_task_id: None,
                 next_update_timestamp_utc: next_schedule.next_timestamp_utc,
                 next_update_time: next_schedule.next_datetime.to_rfc3339(),
+                next_trigger: TaskTrigger::UpdateUserGroup,
 
-                team_id,
-                team_domain,
-                channel_id,
-                channel_name,
-                enterprise_id,
-                enterprise_name,
-                is_enterprise_install,
+                team_id: context.team_id,
+                team_domain: context.team_domain,
+                channel_id: context.channel_id,
+                channel_name: context.channel_name,
+                enterprise_id: context.enterprise_id,
+                enterprise_name: context.enterprise_name,
+                is_enterprise_install: context.is_enterprise_install,
 
                 user_group_id,
                 user_group_handle,
-                pager_duty_schedule_id: arg.pagerduty_schedule,
-                pager_duty_token: arg.pagerduty_api_key,
-                cron: arg.cron,
+                provider_config,
+                cron,
                 timezone: timezone.to_string(),
+                missing_user_policy,
+                guard_rails,
+                notification,
 
-                created_by_user_id: user_id,
-                created_by_user_name: user_name,
-                created_at: Utc::now().to_rfc3339(),
-                last_updated_at: Utc::now().to_rfc3339(),
+                paused: false,
+                resume_timestamp_utc: None,
+                resume_time: None,
+                missed_runs: 0,
+                last_missed_time: None,
+                failed_runs: 0,
+                last_failed_time: None,
+                last_error: None,
+
+                version: 0,
+                claimed_until_timestamp_utc: None,
+
+                member_ids: vec![],
+                previous_member_ids: vec![],
+                members_changed_time: None,
+                notification_thread_ts: None,
+
+                created_by_user_id: context.user_id,
+                created_by_user_name: context.user_name,
+                created_at: now.to_rfc3339(),
+                last_updated_at: now.to_rfc3339(),
             };
-            
-            if let Err(err) = db.save_scheduled_task(&task).await {
-                println!("Failed to save to dynamodb, {:?}", err);
-                return Ok(response(500, format!("Can't process slack command due to save to dynamodb failed\nCommand: {} {}", command, text)))
+
+            // The first trigger may be the reminder before the first update
+            task.set_next_schedule_from(&now);
+
+            db.create_scheduled_task(&mut task).await?;
+            task
+        },
+    };
+
+    if let Some((_, next_schedule)) = task.calculate_next_trigger(&now) {
+        scheduler.update_next_schedule(&next_schedule).await?;
+    }
+
+    Ok(task)
+}
+
+/**
+ * Post a message to the channel of the task with the bot token of the workspace, failures are only logged
+ */
+async fn send_channel_message(repositories: &Repositories, task: &ScheduledTask, message: &str) {
+    let result = match repositories.installations.get_slack_installation(&task.team_id, &task.enterprise_id).await {
+        Ok(Some(installation)) => match build_http_client() {
+            Ok(http_client) => Slack::new(Arc::new(http_client), installation.access_token).send_message(&task.channel_id, message).await,
+            Err(err) => Err(err),
+        },
+        Ok(None) => Err(AppError::SlackError(format!("On-Call Support is not installed in the workspace: {}", task.team_domain))),
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = result {
+        println!("Failed to send message to channel {}, error: {:?}", task.channel_name, err);
+    }
+}
+
+/**
+ * Find a task by its id, or by the user group of a schedule in the current channel
+ */
+async fn find_task(db: &dyn ScheduledTasksRepository, context: &SlackRequestContext, reference: &str) -> Result<ScheduledTask, AppError> {
+    let tasks = db.list_scheduled_tasks_in_workspace(&context.team_id, &context.enterprise_id).await?;
+
+    if let Some(task) = tasks.iter().find(|t| t.task_id == reference || t.legacy_task_id.as_deref() == Some(reference)) {
+        return Ok(task.clone());
+    }
+
+    let user_group_id = parse_user_group(reference).map(|(id, _)| id);
+    let handle = reference.trim_start_matches('@');
+    let matched: Vec<&ScheduledTask> = tasks.iter()
+        .filter(|t| t.channel_id == context.channel_id)
+        .filter(|t| user_group_id.as_ref().map(|id| &t.user_group_id == id).unwrap_or(t.user_group_handle == handle))
+        .collect();
+
+    match matched.as_slice() {
+        [task] => Ok((*task).clone()),
+        [] => Err(AppError::ScheduledTaskError(format!("No schedule found for {} in this channel", reference))),
+        _ => {
+            let task_ids = matched.iter().map(|t| t.task_id.clone()).collect::<Vec<String>>().join(", ");
+            Err(AppError::ScheduledTaskError(format!("There are {} schedules for {}, please choose one of: {}", matched.len(), reference, task_ids)))
+        },
+    }
+}
+
+enum TaskAction {
+    Edit(EditArgs),
+    Delete,
+    Pause(Option<String>),
+    Resume,
+}
+
+async fn arm_scheduler(aws_config: &SdkConfig, config: &Config, task: &ScheduledTask, from: &DateTime<Utc>) -> Result<(), AppError> {
+    if let Some((_, next_schedule)) = task.calculate_next_trigger(from) {
+        build_scheduler(aws_config, config)?.update_next_schedule(&next_schedule).await?;
+    }
+
+    Ok(())
+}
+
+/**
+ * Edit, delete, pause or resume a task, and return the confirmation message
+ */
+async fn change_task(action: TaskAction, reference: &str, context: &SlackRequestContext, aws_config: &SdkConfig, config: &Config, repositories: &Repositories) -> Result<String, AppError> {
+    let db = repositories.scheduled_tasks.as_ref();
+    let mut task = find_task(db, context, reference).await?;
+    let now = Utc::now();
+
+    let message = match action {
+        TaskAction::Edit(args) => {
+            let mut changes = vec![];
+            if let Some(cron) = args.cron {
+                if !is_valid_cron(&cron) {
+                    return Err(AppError::ScheduledTaskError(format!("Invalid cron expression: {}", cron)));
+                }
+                changes.push(format!("cron: {}", cron));
+                task.cron = cron;
             }
 
-            if let Err(err) = scheduler.update_next_schedule(&next_schedule).await {
-                println!("Failed to update scheduler, {:?}", err);
-                return Ok(response(500, format!("Can't process slack command due to save to update scheduler\nCommand: {} {}", command, text)))
+            if let Some(timezone) = args.timezone {
+                let timezone = parse_task_timezone(&timezone)?;
+                changes.push(format!("timezone: {}", timezone));
+                task.timezone = timezone.to_string();
             }
-            
-            vec!(format!("Update user group: {}|{} based on pagerduty schedule: {}, at: {}", task.user_group_id, task.user_group_handle, &task.pager_duty_schedule_id, &task.cron))
+
+            // The task id is kept, so the task is updated in place
+            if let Some(pagerduty_schedule) = args.pagerduty_schedule {
+                match &mut task.provider_config {
+                    ScheduleProviderConfig::PagerDuty { schedule_id, .. } => *schedule_id = pagerduty_schedule.clone(),
+                    other => return Err(AppError::ScheduledTaskError(format!("The schedule is based on {}, not PagerDuty", other))),
+                }
+                changes.push(format!("PagerDuty schedule: {}", pagerduty_schedule));
+            }
+
+            if let Some(missing_user_policy) = args.missing_user {
+                changes.push(format!("missing users: {}", missing_user_policy));
+                task.missing_user_policy = missing_user_policy;
+            }
+
+            if let Some(min_group_size) = args.min_group_size {
+                changes.push(format!("minimum group size: {}", min_group_size));
+                task.guard_rails.min_group_size = min_group_size;
+            }
+
+            if let Some(max_group_size) = args.max_group_size {
+                task.guard_rails.max_group_size = optional_limit(Some(max_group_size));
+                changes.push(format!("maximum group size: {}", max_group_size));
+            }
+
+            if let Some(max_churn) = args.max_churn {
+                task.guard_rails.max_churn = optional_limit(Some(max_churn));
+                changes.push(format!("maximum churn: {}", max_churn));
+            }
+
+            if let Some(template) = args.message_template {
+                if template.trim().is_empty() {
+                    task.notification.template = None;
+                    changes.push("message template: default".to_string());
+                } else {
+                    validate_template(&template)?;
+                    changes.push(format!("message template: {}", template));
+                    task.notification.template = Some(template);
+                }
+            }
+
+            if let Some(message_format) = args.message_format {
+                changes.push(format!("message format: {}", message_format));
+                task.notification.format = message_format;
+            }
+
+            // Start a new thread when switching to the thread mode
+            if let Some(post_mode) = args.post_mode {
+                changes.push(format!("post mode: {}", post_mode));
+                task.notification.post_mode = post_mode;
+                task.notification_thread_ts = None;
+            }
+
+            if let Some(notify_incoming) = args.notify_incoming {
+                changes.push(format!("notify incoming users: {}", notify_incoming));
+                task.notification.notify_incoming = notify_incoming;
+            }
+
+            if let Some(template) = args.topic_template {
+                if template.trim().is_empty() {
+                    task.notification.topic_template = None;
+                    changes.push("channel topic: not updated".to_string());
+                } else {
+                    validate_template(&template)?;
+                    changes.push(format!("channel topic template: {}", template));
+                    task.notification.topic_template = Some(template);
+                }
+            }
+
+            if let Some(remind_before_hours) = args.remind_before_hours {
+                task.notification.remind_before_hours = Some(remind_before_hours).filter(|hours| *hours > 0);
+                changes.push(format!("remind before hours: {}", remind_before_hours));
+            }
+
+            task.last_updated_at = now.to_rfc3339();
+            task.set_next_schedule_from(&now);
+            db.save_scheduled_task(&task).await?;
+            arm_scheduler(aws_config, config, &task, &now).await?;
+
+            format!("<@{}> changed the schedule to update <!subteam^{}>, {}, next update at: {}", context.user_id, task.user_group_id, changes.join(", "), task.next_update_time)
         },
-        Some(Command::SetupPagerduty(args)) => {
-            let config = Config::new(env);
-            let slack_installations_db = SlackInstallationsDynamoDb::new(&aws_config, config.installations_table_name, encryptor.clone());
+        TaskAction::Delete => {
+            db.delete_scheduled_task(&task.team_id, &task.enterprise_id, &task.task_id).await?;
+
+            // The overrides only apply to the deleted task, and SQL has no TTL to remove them
+            for o in repositories.overrides.list_overrides(&task.team, &task.task_id).await? {
+                repositories.overrides.delete_override(&task.team, &task.task_id, &o.override_id).await?;
+            }
+
+            format!("<@{}> deleted the schedule to update <!subteam^{}> based on {}", context.user_id, task.user_group_id, &task.provider_config)
+        },
+        TaskAction::Pause(until) => {
+            let resume_at = match until {
+                Some(until) => {
+                    let resume_at = parse_datetime_in_timezone(&until, &get_timezone(&task.timezone))?;
+                    if resume_at <= now {
+                        return Err(AppError::ScheduledTaskError(format!("The resume time {} is in the past", resume_at.to_rfc3339())));
+                    }
+                    Some(resume_at)
+                },
+                None => None,
+            };
+
+            task.paused = true;
+            task.resume_timestamp_utc = resume_at.map(|resume_at| resume_at.timestamp());
+            task.resume_time = resume_at.map(|resume_at| resume_at.to_rfc3339());
+            task.last_updated_at = now.to_rfc3339();
+            db.update_paused(&mut task).await?;
+
+            match &task.resume_time {
+                Some(resume_time) => format!("<@{}> paused updating <!subteam^{}> until {}", context.user_id, task.user_group_id, resume_time),
+                None => format!("<@{}> paused updating <!subteam^{}>", context.user_id, task.user_group_id),
+            }
+        },
+        TaskAction::Resume => {
+            task.paused = false;
+            task.resume_timestamp_utc = None;
+            task.resume_time = None;
+            task.last_updated_at = now.to_rfc3339();
+            task.set_next_schedule_from(&now);
+            db.update_paused(&mut task).await?;
+
+            // The scheduler may not be armed for the task while it's paused
+            arm_scheduler(aws_config, config, &task, &now).await?;
+
+            format!("<@{}> resumed updating <!subteam^{}>, next update at: {}", context.user_id, task.user_group_id, task.next_update_time)
+        },
+    };
+
+    send_channel_message(repositories, &task, &message).await;
+
+    Ok(message)
+}
+
+/**
+ * Handle the interactions with the new schedule wizard, see https://api.slack.com/interactivity/handling
+ */
+pub async fn handle_slack_interactivity(env: &str, request_header: HeaderMap<HeaderValue>, request_body: Option<String>) -> Result<HttpResponse, AppError> {
+    let request_body = request_body.unwrap_or_default();
+
+    let config = Config::new(env)?;
+    let aws_config = config.load_aws_config().await;
+    let secrets = load_secrets(&aws_config, &config.secrets_source).await?;
+
+    if let Err(err) = SlackRequestVerifier::from_secrets(&secrets).verify(&request_header, &request_body, Utc::now().timestamp()) {
+        println!("Rejected Slack interaction, error: {:?}", err);
+        return Ok(response(401, format!("Invalid slack interaction: {}", err)));
+    }
+
+    let params: HashMap<String, String> = form_urlencoded::parse(request_body.as_bytes()).into_owned().collect();
+    let payload: Value = match serde_json::from_str(&get_param(&params, "payload")) {
+        Ok(payload) => payload,
+        Err(_) => return Ok(response(400, "Invalid request".to_string())),
+    };
+
+    match (payload["type"].as_str(), payload["view"]["callback_id"].as_str()) {
+        (Some("block_suggestion"), _) if payload["action_id"] == TIMEZONE_ACTION_ID => {
+            Ok(response(200, timezone_options(payload["value"].as_str().unwrap_or_default()).to_string()))
+        },
+        (Some("view_submission"), Some(NEW_SCHEDULE_CALLBACK_ID)) => {
+            let submission = match parse_new_schedule_submission(&payload["view"]["state"]["values"]) {
+                Ok(submission) => submission,
+                Err(errors) => return Ok(response(200, json!({ "response_action": "errors", "errors": errors }).to_string())),
+            };
 
+            let mut context: SlackRequestContext = serde_json::from_str(payload["view"]["private_metadata"].as_str().unwrap_or_default())
+                .map_err(|err| AppError::UnexpectedError(format!("Invalid private metadata of the view: {}", err)))?;
+            context.user_id = payload["user"]["id"].as_str().unwrap_or_default().to_string();
+            context.user_name = payload["user"]["username"].as_str().unwrap_or_default().to_string();
+
+            let repositories = Repositories::from_config(&aws_config, &config, Encryptor::new(&secrets.encryption_key)).await?;
+            let context_user_id = context.user_id.clone();
+            let provider_config = ScheduleProviderConfig::PagerDuty { schedule_id: submission.pagerduty_schedule_id, api_token: None };
+            let task = create_scheduled_task(&aws_config, &config, &repositories, context, submission.user_group_id, submission.user_group_handle, provider_config, submission.cron, submission.timezone, MissingUserPolicy::default(), GuardRails::default(), HandoffNotification::default()).await?;
+
+            let message = format!("<@{}> scheduled to update <!subteam^{}> based on {}, at: {} {}, task id: {}", context_user_id, task.user_group_id, &task.provider_config, &task.cron, &task.timezone, task.task_id);
+            send_channel_message(&repositories, &task, &message).await;
+
+            Ok(response(200, "".to_string()))
+        },
+        _ => {
+            println!("Ignored Slack interaction: {:?}", payload["type"]);
+            Ok(response(200, "".to_string()))
+        },
+    }
+}
+
+pub async fn handle_slack_command(env: &str, request_header: HeaderMap<HeaderValue>, request_body: Option<String>) -> Result<HttpResponse, AppError> {
+    let request_body = request_body.unwrap_or_default();
+    
+    let params: HashMap<String, String> = form_urlencoded::parse(request_body.as_bytes()).into_owned().collect();
+    // println!("params in body: {:?}", params);
+
+    let command = get_param(&params, "command");
+    let text = get_param(&params, "text");
+
+    let config = Config::new(env)?;
+    let aws_config = config.load_aws_config().await;
+    let secrets = load_secrets(&aws_config, &config.secrets_source).await?;
+
+    if let Err(err) = SlackRequestVerifier::from_secrets(&secrets).verify(&request_header, &request_body, Utc::now().timestamp()) {
+        println!("Rejected Slack command: {} {}, error: {:?}", command, text, err);
+        return Ok(response(401, format!("Invalid slack command: {}", err)));
+    }
+
+    let repositories = Repositories::from_config(&aws_config, &config, Encryptor::new(&secrets.encryption_key)).await?;
+
+    run_slack_command(&params, &aws_config, &config, &repositories).await
+}
+
+/**
+ * Run a verified slash command with the given storage
+ */
+async fn run_slack_command(params: &HashMap<String, String>, aws_config: &SdkConfig, config: &Config, repositories: &Repositories) -> Result<HttpResponse, AppError> {
+    let team_id = get_param(params, "team_id");
+    let channel_id = get_param(params, "channel_id");
+    let enterprise_id = get_param(params, "enterprise_id");
+
+    let user_id = get_param(params, "user_id");
+    let user_name = get_param(params, "user_name");
+    let command = get_param(params, "command");
+    let text = get_param(params, "text");
+    let _response_url = get_param(params, "response_url");
+
+    let Some(args) = shlex::split(cleanse(format!("{} {}", command, text).as_str()).as_str()) else {
+        return Ok(ephemeral_response(&format!("Invalid command, please check the quotes: {} {}", command, text)));
+    };
+
+    // The usage is replied to the user, instead of exiting the process on invalid arguments or --help
+    let arg = match App::try_parse_from(args.iter()) {
+        Ok(arg) => arg,
+        Err(err) => return Ok(ephemeral_response(&format!("```{}```", err.render()))),
+    };
+
+    let response_body = match arg.command {
+        Some(Command::Schedule(arg)) => {
+            let (user_group_id, user_group_handle) = match parse_user_group(arg.user_group.as_str()) {
+                Some(user_group) => user_group,
+                None => {
+                    println!("Invalid user group: {}", arg.user_group);
+
+                    return Ok(HttpResponse {
+                        status_code: 400,
+                        body: format!("Invalid user group: {}", arg.user_group),
+                        ..Default::default()
+                    })
+                },
+            };
+
+            let provider_config = arg.provider_config();
+            let guard_rails = arg.guard_rails();
+            let notification = match arg.notification() {
+                Ok(notification) => notification,
+                Err(err) => return Ok(response(400, user_error_message(&err).unwrap_or(err.to_string()))),
+            };
+            let timezone = match parse_task_timezone(&arg.timezone.unwrap_or("UTC".to_string())) {
+                Ok(timezone) => timezone,
+                Err(err) => return Ok(response(400, user_error_message(&err).unwrap_or(err.to_string()))),
+            };
+            let context = SlackRequestContext::from_params(params);
+
+            match create_scheduled_task(aws_config, config, repositories, context, user_group_id, user_group_handle, provider_config, arg.cron, timezone, arg.missing_user, guard_rails, notification).await {
+                Ok(task) => vec!(format!("Update user group: {}|{} based on {}, at: {}, task id: {}", task.user_group_id, task.user_group_handle, &task.provider_config, &task.cron, task.task_id)),
+                Err(err) => {
+                    println!("Failed to create scheduled task, {:?}", err);
+                    match user_error_message(&err) {
+                        Some(message) => vec!(message),
+                        None => return Ok(response(500, format!("Can't process slack command due to {}\nCommand: {} {}", err, command, text))),
+                    }
+                },
+            }
+        },
+        Some(Command::SetupPagerduty(args)) => {
             //TODO: validate if the installation exists
             //TODO: validate if the pagerduty token valid
 
-            slack_installations_db.update_pagerduty_token(team_id, enterprise_id, &args.pagerduty_api_key).await?;
+            repositories.installations.update_pagerduty_token(team_id, enterprise_id, &args.pagerduty_api_key).await?;
 
             vec!(format!("Setup pagerduty with api key"))
         },
-        Some(Command::ListSchedules(_args)) => {
-            let db = ScheduledTasksDynamodb::new(&aws_config, format!("on-call-support-schedules-{}", env), encryptor);
-            let tasks = db.list_scheduled_tasks().await?;
+        Some(Command::SetupOpsgenie(args)) => {
+            repositories.installations.update_opsgenie_token(team_id, enterprise_id, &args.opsgenie_api_key, args.region).await?;
+
+            vec!(format!("Setup opsgenie with api key in region {}", args.region))
+        },
+        Some(Command::ListSchedules(args)) => {
+            let tasks: Vec<ScheduledTask> = repositories.scheduled_tasks.list_scheduled_tasks_in_workspace(&team_id, &enterprise_id).await?
+                .into_iter()
+                .filter(|t| args.all || t.channel_id == channel_id)
+                .collect();
+
+            if tasks.is_empty() {
+                let scope = if args.all { "this workspace" } else { "this channel" };
+                vec!(format!("No schedules found in {}", scope))
+            } else {
+                tasks.into_iter()
+                    .map(|t| {
+                        let missed_runs = match &t.last_missed_time {
+                            Some(last_missed_time) if t.missed_runs > 0 => format!("\nMissed runs: {}, the latest was due at {}", t.missed_runs, last_missed_time),
+                            _ => "".to_string(),
+                        };
+                        let failed_runs = match (&t.last_failed_time, &t.last_error) {
+                            (Some(last_failed_time), Some(last_error)) if t.failed_runs > 0 => format!("\nFailed runs: {}, the latest failed at {}: {}", t.failed_runs, last_failed_time, last_error),
+                            _ => "".to_string(),
+                        };
+                        format!("## {} `{}`\nUpdate {} on {}\nNext schedule: {} ({}){}{}", t.channel_name, t.task_id, t.user_group_handle, t.cron, t.next_update_time, t.next_trigger, missed_runs, failed_runs)
+                    })
+                    .collect()
+            }
+        },
+        Some(Command::Rotation(args)) => {
+            reply_user_errors(handle_rotation_command(args.command, format!("{}:{}", &team_id, &enterprise_id), user_id, user_name, repositories.rotations.as_ref()).await)?
+        },
+        Some(Command::Alias(args)) => {
+            reply_user_errors(handle_alias_command(args.command, format!("{}:{}", &team_id, &enterprise_id), user_id, user_name, repositories.aliases.as_ref()).await)?
+        },
+        Some(Command::Override(args)) => {
+            reply_user_errors(handle_override_command(args, format!("{}:{}", &team_id, &enterprise_id), channel_id, user_id, user_name, repositories.scheduled_tasks.as_ref(), repositories.overrides.as_ref()).await)?
+        },
+        Some(Command::Edit(args)) => {
+            let context = SlackRequestContext::from_params(params);
+            let task = args.task.clone();
+            reply_user_errors(change_task(TaskAction::Edit(args), &task, &context, aws_config, config, repositories).await.map(|m| vec!(m)))?
+        },
+        Some(Command::Delete(args)) => {
+            let context = SlackRequestContext::from_params(params);
+            reply_user_errors(change_task(TaskAction::Delete, &args.task, &context, aws_config, config, repositories).await.map(|m| vec!(m)))?
+        },
+        Some(Command::Pause(args)) => {
+            let context = SlackRequestContext::from_params(params);
+            let result = match args.resume_at() {
+                Ok(until) => change_task(TaskAction::Pause(until), &args.task, &context, aws_config, config, repositories).await,
+                Err(err) => Err(err),
+            };
+            reply_user_errors(result.map(|m| vec!(m)))?
+        },
+        Some(Command::Resume(args)) => {
+            let context = SlackRequestContext::from_params(params);
+            reply_user_errors(change_task(TaskAction::Resume, &args.task, &context, aws_config, config, repositories).await.map(|m| vec!(m)))?
+        },
+        Some(Command::New) => {
+            let trigger_id = get_param(params, "trigger_id");
+            let context = SlackRequestContext::from_params(params);
+            let installation = match repositories.installations.get_slack_installation(&context.team_id, &context.enterprise_id).await? {
+                Some(installation) => installation,
+                None => return Ok(response(400, format!("On-Call Support is not installed in the workspace: {}", context.team_domain))),
+            };
 
-            tasks.into_iter()
-                .map(|t| format!("## {}\nUpdate {} on {}\nNext schedule: {}", t.channel_name, t.user_group_handle, t.cron, t.next_update_time))
-                .collect()
+            let http_client = Arc::new(build_http_client()?);
+            let slack = Slack::new(http_client.clone(), installation.access_token.clone());
+            let view_id = slack.open_view(&trigger_id, &loading_view()).await?;
+
+            let user_groups = slack.list_user_groups().await?;
+
+            // The command is still replied in time when PagerDuty is slow, the schedule id can be typed in instead
+            let pagerduty_schedules = match &installation.pager_duty_token {
+                Some(token) => match timeout(LIST_PAGERDUTY_SCHEDULES_TIMEOUT, list_pagerduty_schedules(&http_client, token)).await {
+                    Ok(Ok(schedules)) => schedules,
+                    Ok(Err(err)) => {
+                        println!("Failed to list PagerDuty schedules, error: {:?}", err);
+                        vec![]
+                    },
+                    Err(_) => {
+                        println!("Timed out listing PagerDuty schedules");
+                        vec![]
+                    },
+                },
+                None => vec![],
+            };
+
+            let private_metadata = serde_json::to_string(&context).unwrap();
+            slack.update_view(&view_id, &new_schedule_view(&user_groups, &pagerduty_schedules, &private_metadata)).await?;
+
+            vec!(format!("Opened the wizard to add a new schedule"))
         },
-        Some(Command::New) => vec!(format!("Show wizard to add new schedule")),
         None => vec!(format!("default command"))
     };
     
     let sections = response_body.into_iter()
-        .map(|p| format!(r#"{{"type": "section", "text": {{ "type": "mrkdwn", "text": "{}" }} }}"#, p))
-        .collect::<Vec<String>>()
-        .join(",\n")
-    ;
+        .map(|p| json!({ "type": "section", "text": { "type": "mrkdwn", "text": p } }))
+        .collect::<Vec<Value>>();
+
+    Ok(response(200, json!({ "blocks": sections }).to_string()))
+}
 
-    Ok(response(200, format!(r#"{{ "blocks": [{}] }}"#, sections)))
+/**
+ * A reply only visible to the user who ran the command
+ */
+fn ephemeral_response(text: &str) -> HttpResponse {
+    response(200, json!({ "response_type": "ephemeral", "text": text }).to_string())
 }
 
-pub fn response(status_code: i64, body: String) -> ApiGatewayProxyResponse {
+pub fn response(status_code: u16, body: String) -> HttpResponse {
     let mut response_headers = HeaderMap::new();
     response_headers.insert("response_type", "in_channel".parse().unwrap());
     response_headers.insert("Content-type", "application/json".parse().unwrap());
 
-    ApiGatewayProxyResponse {
+    HttpResponse {
         status_code,
         headers: response_headers,
-        body: Some(Body::from(body)),
-        ..Default::default()
+        body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use aws_config::SdkConfig;
+    use clap::Parser;
+
+    use crate::{config::{Config, SchedulerBackend}, db::Repositories, handoff_notification::{HandoffNotification, MessageFormat, PostMode}, scheduled_tasks::{GuardRails, ScheduledTask}};
+    use crate::slack_handler::{parse_email, parse_slack_user, parse_user_group, run_slack_command, App, Command, ListSchedulesArgs, OverrideArgs, OverrideCommand, PauseArgs};
+
+    fn override_args(text: &str) -> OverrideArgs {
+        OverrideArgs { user_group: None, task: None, words: shlex::split(text).unwrap() }
+    }
+
+    #[test]
+    fn parse_override_commands() {
+        assert_eq!(override_args("list").command().unwrap(), OverrideCommand::List);
+        assert_eq!(override_args("delete 7f3a9c01").command().unwrap(), OverrideCommand::Delete("7f3a9c01".to_string()));
+        assert_eq!(
+            override_args("<@U1|alice> <@U3> for <@U2|bob> from 2024-01-02 09:00 to 2024-01-03T09:00").command().unwrap(),
+            OverrideCommand::Create {
+                replacements: vec!["<@U1|alice>".to_string(), "<@U3>".to_string()],
+                replaced: vec!["<@U2|bob>".to_string()],
+                start: "2024-01-02 09:00".to_string(),
+                end: "2024-01-03T09:00".to_string(),
+            },
+        );
+        assert!(override_args("<@U1|alice> for <@U2|bob> from 2024-01-02").command().is_err());
+        assert!(override_args("for <@U2|bob> from 2024-01-02 to 2024-01-03").command().is_err());
+    }
+
+    #[test]
+    fn parse_slack_mentions() {
+        assert_eq!(parse_slack_user("<@U123ABC|alice>"), Some("U123ABC".to_string()));
+        assert_eq!(parse_slack_user("<@W123ABC>"), Some("W123ABC".to_string()));
+        assert_eq!(parse_slack_user("U123ABC"), Some("U123ABC".to_string()));
+        assert_eq!(parse_slack_user("@alice"), None);
+
+        assert_eq!(parse_user_group("<!subteam^S123|@support>"), Some(("S123".to_string(), "support".to_string())));
+        assert_eq!(parse_user_group("@support"), None);
+
+        assert_eq!(parse_email("<mailto:Bob@Example.com|Bob@Example.com>"), Some("bob@example.com".to_string()));
+        assert_eq!(parse_email("bob@example.com"), Some("bob@example.com".to_string()));
+        assert_eq!(parse_email("bob"), None);
+    }
+
+    #[test]
+    fn parse_pause_until() {
+        let pause_args = |until: &[&str]| PauseArgs { task: "support".to_string(), until: until.iter().map(|w| w.to_string()).collect() };
+
+        assert_eq!(pause_args(&[]).resume_at().unwrap(), None);
+        assert_eq!(pause_args(&["until", "2024-01-02", "09:00"]).resume_at().unwrap(), Some("2024-01-02 09:00".to_string()));
+        assert!(pause_args(&["until"]).resume_at().is_err());
+        assert!(pause_args(&["tomorrow"]).resume_at().is_err());
+    }
+
+    #[test]
+    fn parse_edit_with_only_given_fields() {
+        let app = App::try_parse_from(shlex::split("/on-call-support edit @support --cron \"0 8 ? * MON *\"").unwrap()).unwrap();
+        match app.command {
+            Some(Command::Edit(args)) => {
+                assert_eq!(args.task, "@support");
+                assert_eq!(args.cron, Some("0 8 ? * MON *".to_string()));
+                assert_eq!(args.timezone, None);
+                assert_eq!(args.pagerduty_schedule, None);
+                assert_eq!(args.missing_user, None);
+                assert_eq!(args.max_churn, None);
+                assert_eq!(args.post_mode, None);
+            },
+            other => panic!("Unexpected command: {:?}", other),
+        }
+
+        assert!(App::try_parse_from(shlex::split("/on-call-support edit @support").unwrap()).is_err());
+    }
+
+    #[test]
+    fn parse_schedule_guard_rails() {
+        let app = App::try_parse_from(shlex::split("/on-call-support schedule --user-group @support --rotation support --cron \"0 9 ? * MON *\" --max-group-size 0 --max-churn 2").unwrap()).unwrap();
+        match app.command {
+            Some(Command::Schedule(args)) => assert_eq!(args.guard_rails(), GuardRails { min_group_size: 1, max_group_size: None, max_churn: Some(2) }),
+            other => panic!("Unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_schedule_notification() {
+        let parse = |options: &str| match App::try_parse_from(shlex::split(&format!("/on-call-support schedule --user-group @support --rotation support --cron \"0 9 ? * MON *\" {}", options)).unwrap()).unwrap().command {
+            Some(Command::Schedule(args)) => args.notification(),
+            other => panic!("Unexpected command: {:?}", other),
+        };
+
+        let notification = parse("--message-template \"{incoming} took over from {outgoing}\" --message-format blocks --post-mode thread --notify-incoming --topic-template \"On call: {on_call}\" --remind-before-hours 12").unwrap();
+        assert_eq!(notification, HandoffNotification {
+            template: Some("{incoming} took over from {outgoing}".to_string()),
+            format: MessageFormat::Blocks,
+            post_mode: PostMode::Thread,
+            notify_incoming: true,
+            topic_template: Some("On call: {on_call}".to_string()),
+            remind_before_hours: Some(12),
+        });
+        assert_eq!(parse("").unwrap(), HandoffNotification::default());
+        assert!(parse("--message-template \"{someone} is on call\"").is_err());
+        assert!(parse("--topic-template \"On call: {someone}\"").is_err());
+    }
+
+    #[test]
+    fn parse_list_schedules_scope() {
+        let app = App::try_parse_from(shlex::split("/on-call-support list-schedules").unwrap()).unwrap();
+        assert!(matches!(app.command, Some(Command::ListSchedules(ListSchedulesArgs { all: false }))));
+
+        let app = App::try_parse_from(shlex::split("/on-call-support list-schedules --all").unwrap()).unwrap();
+        assert!(matches!(app.command, Some(Command::ListSchedules(ListSchedulesArgs { all: true }))));
+    }
+
+    fn command_params(text: &str) -> HashMap<String, String> {
+        [
+            ("team_id", "T123"), ("enterprise_id", "E123"), ("channel_id", "C123"), ("channel_name", "support"),
+            ("user_id", "U6HHTEST"), ("user_name", "test-user"), ("command", "/on-call-support"), ("text", text),
+        ].into_iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    fn task(team_id: &str, channel_id: &str, task_id: &str) -> ScheduledTask {
+        ScheduledTask {
+            team: format!("{}:E123", team_id),
+            task_id: task_id.to_string(),
+            team_id: team_id.to_string(),
+            channel_id: channel_id.to_string(),
+            channel_name: channel_id.to_lowercase(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn run_commands_with_in_memory_storage() {
+        let repositories = Repositories::in_memory();
+        let aws_config = SdkConfig::builder().build();
+        let config = Config::new("test").unwrap();
+
+        run_slack_command(&command_params("rotation create support --members <@U1> <@U2> --cadence daily"), &aws_config, &config, &repositories).await.unwrap();
+        let rotation = repositories.rotations.get_rotation("T123:E123", "support").await.unwrap().unwrap();
+        assert_eq!(rotation.members, vec!["U1", "U2"]);
+
+        for task in [task("T123", "C123", "ocs-0001"), task("T123", "C456", "ocs-0002"), task("T999", "C123", "ocs-0003")] {
+            repositories.scheduled_tasks.save_scheduled_task(&task).await.unwrap();
+        }
+
+        let channel_schedules = run_slack_command(&command_params("list-schedules"), &aws_config, &config, &repositories).await.unwrap().body;
+        assert!(channel_schedules.contains("ocs-0001"));
+        assert!(!channel_schedules.contains("ocs-0002"));
+        assert!(!channel_schedules.contains("ocs-0003"));
+
+        // The multi-line text of the sections is escaped in the reply
+        let blocks: serde_json::Value = serde_json::from_str(&channel_schedules).unwrap();
+        assert!(blocks["blocks"][0]["text"]["text"].as_str().unwrap().starts_with("## c123 `ocs-0001`\nUpdate"));
+
+        let workspace_schedules = run_slack_command(&command_params("list-schedules --all"), &aws_config, &config, &repositories).await.unwrap().body;
+        assert!(workspace_schedules.contains("ocs-0001"));
+        assert!(workspace_schedules.contains("ocs-0002"));
+        assert!(!workspace_schedules.contains("ocs-0003"));
+    }
+
+    #[tokio::test]
+    async fn manage_email_aliases() {
+        let repositories = Repositories::in_memory();
+        let aws_config = SdkConfig::builder().build();
+        let config = Config::new("test").unwrap();
+
+        run_slack_command(&command_params("alias add <mailto:Bob@Example.com|Bob@Example.com> <@U2|bob>"), &aws_config, &config, &repositories).await.unwrap();
+        let aliases = repositories.aliases.list_aliases("T123:E123").await.unwrap();
+        assert_eq!(aliases.iter().map(|a| (a.email.as_str(), a.slack_user_id.as_str())).collect::<Vec<_>>(), vec![("bob@example.com", "U2")]);
+
+        let listed = run_slack_command(&command_params("alias list"), &aws_config, &config, &repositories).await.unwrap().body;
+        assert!(listed.contains("bob@example.com -> <@U2>"));
+
+        let invalid = run_slack_command(&command_params("alias add bob <@U2>"), &aws_config, &config, &repositories).await.unwrap().body;
+        assert!(invalid.contains("Invalid email: bob"));
+
+        run_slack_command(&command_params("alias remove bob@example.com"), &aws_config, &config, &repositories).await.unwrap();
+        assert!(repositories.aliases.list_aliases("T123:E123").await.unwrap().is_empty());
+
+        let missing = run_slack_command(&command_params("alias remove bob@example.com"), &aws_config, &config, &repositories).await.unwrap().body;
+        assert!(missing.contains("No alias found for bob@example.com"));
+    }
+
+    #[tokio::test]
+    async fn reply_usage_of_invalid_commands() {
+        let repositories = Repositories::in_memory();
+        let aws_config = SdkConfig::builder().build();
+        let config = Config::new("test").unwrap();
+
+        let unknown_argument = run_slack_command(&command_params("list-schedules --everything"), &aws_config, &config, &repositories).await.unwrap();
+        assert_eq!(unknown_argument.status_code, 200);
+        assert!(unknown_argument.body.contains("ephemeral"));
+        assert!(unknown_argument.body.contains("--everything"));
+
+        let unclosed_quote = run_slack_command(&command_params("alias add \"bob"), &aws_config, &config, &repositories).await.unwrap().body;
+        assert!(unclosed_quote.contains("please check the quotes"));
+
+        let unknown_timezone = run_slack_command(&command_params("schedule --user-group <!subteam^S123|@support> --rotation support --cron '0 9 ? * MON-FRI *' --timezone Mars/Olympus"), &aws_config, &config, &repositories).await.unwrap();
+        assert_eq!(unknown_timezone.status_code, 400);
+        assert!(unknown_timezone.body.contains("Unknown timezone: Mars/Olympus"));
+    }
+
+    #[tokio::test]
+    async fn delete_overrides_with_the_task() {
+        let repositories = Repositories::in_memory();
+        let aws_config = SdkConfig::builder().build();
+        let config = Config::new("test").unwrap();
+        repositories.scheduled_tasks.save_scheduled_task(&task("T123", "C123", "ocs-0001")).await.unwrap();
+
+        run_slack_command(&command_params("override <@U2> for <@U1> from 2099-01-01T09:00 to 2099-01-02T09:00"), &aws_config, &config, &repositories).await.unwrap();
+        assert_eq!(repositories.overrides.list_overrides("T123:E123", "ocs-0001").await.unwrap().len(), 1);
+
+        run_slack_command(&command_params("delete ocs-0001"), &aws_config, &config, &repositories).await.unwrap();
+        assert!(repositories.scheduled_tasks.list_scheduled_tasks_in_workspace("T123", "E123").await.unwrap().is_empty());
+        assert!(repositories.overrides.list_overrides("T123:E123", "ocs-0001").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reschedule_keeps_the_state_of_existing_task() {
+        let repositories = Repositories::in_memory();
+        let aws_config = SdkConfig::builder().build();
+        let config = Config { scheduler_backend: SchedulerBackend::InProcess, ..Config::new("test").unwrap() };
+
+        let existing_task = ScheduledTask {
+            paused: true,
+            missed_runs: 2,
+            member_ids: vec!["U1".to_string()],
+            notification_thread_ts: Some("1700000000.000100".to_string()),
+            ..task("T123", "C123", "ocs-0001")
+        };
+        repositories.scheduled_tasks.save_scheduled_task(&existing_task).await.unwrap();
+
+        run_slack_command(&command_params("schedule --user-group <!subteam^S123|@support> --rotation support --cron '0 10 ? * MON-FRI *'"), &aws_config, &config, &repositories).await.unwrap();
+
+        let tasks = repositories.scheduled_tasks.list_scheduled_tasks_in_workspace("T123", "E123").await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].cron, "0 10 ? * MON-FRI *");
+        assert_eq!((tasks[0].paused, tasks[0].missed_runs, tasks[0].version), (true, 2, 2));
+        assert_eq!(tasks[0].member_ids, existing_task.member_ids);
+        assert_eq!(tasks[0].notification_thread_ts, existing_task.notification_thread_ts);
     }
 }
@@ -0,0 +1,186 @@
+use aws_lambda_events::http::{HeaderMap, HeaderValue};
+use ring::hmac;
+
+use crate::{errors::AppError, secrets::Secrets};
+
+const SLACK_SIGNATURE_VERSION: &str = "v0";
+const MAX_REQUEST_AGE_SECONDS: i64 = 60 * 5;
+const MAX_OAUTH_STATE_AGE_SECONDS: i64 = 60 * 10;
+
+/**
+ * Verifies requests sent by Slack, see https://api.slack.com/authentication/verifying-requests-from-slack
+ *
+ * More than one signing secret can be active at the same time, so the secret can be rotated without downtime.
+ */
+pub struct SlackRequestVerifier {
+    keys: Vec<hmac::Key>,
+}
+
+impl SlackRequestVerifier {
+    pub fn new(signing_secrets: &[String]) -> SlackRequestVerifier {
+        let keys = signing_secrets.iter()
+            .filter(|secret| !secret.is_empty())
+            .map(|secret| hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()))
+            .collect();
+
+        SlackRequestVerifier { keys }
+    }
+
+    pub fn from_secrets(secrets: &Secrets) -> SlackRequestVerifier {
+        SlackRequestVerifier::new(&secrets.slack_signing_secrets())
+    }
+
+    pub fn verify(&self, headers: &HeaderMap<HeaderValue>, body: &str, now: i64) -> Result<(), AppError> {
+        let timestamp = get_header(headers, "X-Slack-Request-Timestamp")?;
+        let signature = get_header(headers, "X-Slack-Signature")?;
+
+        let timestamp = timestamp.parse::<i64>().map_err(AppError::ParseIntError)?;
+        if (now - timestamp).abs() > MAX_REQUEST_AGE_SECONDS {
+            return Err(AppError::StaleSlackRequestError(timestamp));
+        }
+
+        let signature = signature.strip_prefix(&format!("{}=", SLACK_SIGNATURE_VERSION))
+            .ok_or(AppError::InvalidSlackSignatureError)?;
+
+        self.verify_signature(&format!("{}:{}:{}", SLACK_SIGNATURE_VERSION, timestamp, body), signature)
+    }
+
+    /**
+     * Generate the `state` parameter of the OAuth flow, in the format of `<timestamp>.<signature>`
+     */
+    pub fn sign_oauth_state(&self, now: i64) -> Result<String, AppError> {
+        let key = self.keys.first().ok_or(AppError::InvalidSlackSignatureError)?;
+        let signature = hmac::sign(key, format!("{}:{}:oauth", SLACK_SIGNATURE_VERSION, now).as_bytes());
+
+        Ok(format!("{}.{}", now, hex::encode(signature.as_ref())))
+    }
+
+    pub fn verify_oauth_state(&self, state: Option<&str>, now: i64) -> Result<(), AppError> {
+        let state = state.ok_or(AppError::InvalidSlackSignatureError)?;
+        let (timestamp, signature) = state.split_once('.').ok_or(AppError::InvalidSlackSignatureError)?;
+
+        let timestamp = timestamp.parse::<i64>().map_err(AppError::ParseIntError)?;
+        if now - timestamp > MAX_OAUTH_STATE_AGE_SECONDS || timestamp > now + MAX_REQUEST_AGE_SECONDS {
+            return Err(AppError::StaleSlackRequestError(timestamp));
+        }
+
+        self.verify_signature(&format!("{}:{}:oauth", SLACK_SIGNATURE_VERSION, timestamp), signature)
+    }
+
+    fn verify_signature(&self, sig_basestring: &str, signature_hex: &str) -> Result<(), AppError> {
+        let signature = hex::decode(signature_hex).map_err(|_| AppError::InvalidSlackSignatureError)?;
+
+        // hmac::verify compares the signatures in constant time
+        let matched = self.keys.iter()
+            .any(|key| hmac::verify(key, sig_basestring.as_bytes(), &signature).is_ok());
+
+        if matched {
+            Ok(())
+        } else {
+            Err(AppError::InvalidSlackSignatureError)
+        }
+    }
+}
+
+fn get_header<'a>(headers: &'a HeaderMap<HeaderValue>, name: &str) -> Result<&'a str, AppError> {
+    let value = headers.get(name).ok_or(AppError::MissingSlackHeaderError(name.to_string()))?;
+    Ok(value.to_str()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_lambda_events::http::{HeaderMap, HeaderValue};
+
+    use crate::{errors::AppError, slack_request_verifier::SlackRequestVerifier};
+
+    // Example request from https://api.slack.com/authentication/verifying-requests-from-slack
+    const SIGNING_SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const TIMESTAMP: i64 = 1531420618;
+    const BODY: &str = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+    const SIGNATURE: &str = "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+
+    fn headers(timestamp: &str, signature: &str) -> HeaderMap<HeaderValue> {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-slack-request-timestamp", timestamp.parse().unwrap());
+        headers.insert("x-slack-signature", signature.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn verify_slack_example_request() {
+        let verifier = SlackRequestVerifier::new(&[SIGNING_SECRET.to_string()]);
+
+        let result = verifier.verify(&headers(&TIMESTAMP.to_string(), SIGNATURE), BODY, TIMESTAMP + 10);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn verify_with_rotated_signing_secrets() {
+        let verifier = SlackRequestVerifier::new(&["new-signing-secret".to_string(), SIGNING_SECRET.to_string()]);
+
+        let result = verifier.verify(&headers(&TIMESTAMP.to_string(), SIGNATURE), BODY, TIMESTAMP);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn reject_tampered_body() {
+        let verifier = SlackRequestVerifier::new(&[SIGNING_SECRET.to_string()]);
+        let body = BODY.replace("user_name=roadrunner", "user_name=coyote");
+
+        let result = verifier.verify(&headers(&TIMESTAMP.to_string(), SIGNATURE), &body, TIMESTAMP);
+        assert!(matches!(result, Err(AppError::InvalidSlackSignatureError)));
+    }
+
+    #[test]
+    fn reject_wrong_signing_secret() {
+        let verifier = SlackRequestVerifier::new(&["aa2ad1a24622382aa823959083867312".to_string()]);
+
+        let result = verifier.verify(&headers(&TIMESTAMP.to_string(), SIGNATURE), BODY, TIMESTAMP);
+        assert!(matches!(result, Err(AppError::InvalidSlackSignatureError)));
+    }
+
+    #[test]
+    fn reject_malformed_signature() {
+        let verifier = SlackRequestVerifier::new(&[SIGNING_SECRET.to_string()]);
+
+        let result = verifier.verify(&headers(&TIMESTAMP.to_string(), "a2114d57b48eac39"), BODY, TIMESTAMP);
+        assert!(matches!(result, Err(AppError::InvalidSlackSignatureError)));
+
+        let result = verifier.verify(&headers(&TIMESTAMP.to_string(), "v0=not-hex"), BODY, TIMESTAMP);
+        assert!(matches!(result, Err(AppError::InvalidSlackSignatureError)));
+    }
+
+    #[test]
+    fn reject_stale_timestamp() {
+        let verifier = SlackRequestVerifier::new(&[SIGNING_SECRET.to_string()]);
+
+        let result = verifier.verify(&headers(&TIMESTAMP.to_string(), SIGNATURE), BODY, TIMESTAMP + 301);
+        assert!(matches!(result, Err(AppError::StaleSlackRequestError(TIMESTAMP))));
+    }
+
+    #[test]
+    fn reject_missing_headers() {
+        let verifier = SlackRequestVerifier::new(&[SIGNING_SECRET.to_string()]);
+        let mut headers = headers(&TIMESTAMP.to_string(), SIGNATURE);
+        headers.remove("x-slack-signature");
+
+        let result = verifier.verify(&headers, BODY, TIMESTAMP);
+        assert!(matches!(result, Err(AppError::MissingSlackHeaderError(name)) if name == "X-Slack-Signature"));
+
+        let result = verifier.verify(&HeaderMap::new(), BODY, TIMESTAMP);
+        assert!(matches!(result, Err(AppError::MissingSlackHeaderError(name)) if name == "X-Slack-Request-Timestamp"));
+    }
+
+    #[test]
+    fn sign_and_verify_oauth_state() {
+        let verifier = SlackRequestVerifier::new(&[SIGNING_SECRET.to_string()]);
+        let state = verifier.sign_oauth_state(TIMESTAMP).unwrap();
+
+        assert!(verifier.verify_oauth_state(Some(&state), TIMESTAMP + 60).is_ok());
+        assert!(matches!(verifier.verify_oauth_state(Some(&state), TIMESTAMP + 601), Err(AppError::StaleSlackRequestError(_))));
+        assert!(matches!(verifier.verify_oauth_state(None, TIMESTAMP), Err(AppError::InvalidSlackSignatureError)));
+
+        let forged = format!("{}.{}", TIMESTAMP, "00".repeat(32));
+        assert!(matches!(verifier.verify_oauth_state(Some(&forged), TIMESTAMP), Err(AppError::InvalidSlackSignatureError)));
+    }
+}
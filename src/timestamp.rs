@@ -1,7 +1,9 @@
-use chrono::{Utc, DateTime};
+use chrono::{Utc, DateTime, NaiveDate, NaiveDateTime, TimeZone};
 use chrono_tz::Tz;
 use std::str::FromStr;
 
+use crate::errors::AppError;
+
 pub fn get_current_timestamp_utc() -> DateTime<Utc> {
     Utc::now()
 }
@@ -17,5 +19,43 @@ pub fn get_current_timestamp(tz: Tz) -> DateTime<Tz> {
 }
 
 pub fn get_timezone(tz: &str) -> Tz {
-    Tz::from_str(&tz).unwrap()
+    Tz::from_str(tz).unwrap()
+}
+
+/**
+ * Parse a date time either in RFC 3339 or as a local date time in the given timezone
+ */
+pub fn parse_datetime_in_timezone(text: &str, timezone: &Tz) -> Result<DateTime<Tz>, AppError> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Ok(datetime.with_timezone(timezone));
+    }
+
+    let local = ["%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"].iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))
+        .ok_or(AppError::InvalidDateTimeError(format!("Invalid time: {}, expecting YYYY-MM-DD or YYYY-MM-DDTHH:MM", text)))?;
+
+    timezone.from_local_datetime(&local).earliest()
+        .ok_or(AppError::InvalidDateTimeError(format!("Time {} doesn't exist in timezone {}", text, timezone)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::TimeZone;
+    use chrono_tz::Tz;
+
+    use crate::timestamp::parse_datetime_in_timezone;
+
+    #[test]
+    fn parse_local_and_rfc3339_datetime() {
+        let melbourne_tz = Tz::from_str("Australia/Melbourne").unwrap();
+
+        assert_eq!(parse_datetime_in_timezone("2023-03-27", &melbourne_tz).unwrap(), melbourne_tz.with_ymd_and_hms(2023, 3, 27, 0, 0, 0).unwrap());
+        assert_eq!(parse_datetime_in_timezone("2023-03-27T09:30", &melbourne_tz).unwrap(), melbourne_tz.with_ymd_and_hms(2023, 3, 27, 9, 30, 0).unwrap());
+        assert_eq!(parse_datetime_in_timezone("2023-03-27 09:30", &melbourne_tz).unwrap(), melbourne_tz.with_ymd_and_hms(2023, 3, 27, 9, 30, 0).unwrap());
+        assert_eq!(parse_datetime_in_timezone("2023-03-26T22:00:00Z", &melbourne_tz).unwrap(), melbourne_tz.with_ymd_and_hms(2023, 3, 27, 9, 0, 0).unwrap());
+        assert!(parse_datetime_in_timezone("next monday", &melbourne_tz).is_err());
+    }
 }
@@ -1,31 +1,81 @@
-use std::{sync::Arc, collections::HashMap, env};
+use std::{sync::Arc, collections::{BTreeSet, HashMap}};
 
-use aws_config::{BehaviorVersion, SdkConfig};
+use aws_config::SdkConfig;
 use futures::StreamExt;
-use crate::{config::Config, db::{SlackInstallation, SlackInstallationsDynamoDb}, encryptor::Encryptor, scheduled_tasks::{EventBridgeScheduler, ScheduledTask, ScheduledTasksDynamodb}, secrets::SecretsClient};
+use crate::{aliases::EmailAlias, handoff_notification::{reminder_message, Handoff, PostMode}, config::{Config, SecretsSource}, db::{Repositories, SlackInstallation}, encryptor::Encryptor, overrides::{apply_overrides, ScheduleOverride}, rotations::RotationsRepository, scheduled_tasks::{build_scheduler, InProcessScheduler, MissingUserPolicy, ScheduledTask, ScheduledTasksRepository, Scheduler, TaskTrigger}, secrets::{load_secrets, Secrets}};
 
-use chrono::{Utc, Duration, DateTime};
+use chrono::{Duration, Utc, DateTime};
 use reqwest::Client;
-use crate::{build_http_client, errors::AppError, service_provider::{pager_duty::PagerDuty, slack::Slack}};
+use serde_json::{json, Value};
+use crate::{build_http_client, cron::one_off_schedule, errors::AppError, timestamp::get_timezone, service_provider::{ical::{Ical, IcalSource}, opsgenie::Opsgenie, pager_duty::PagerDuty, schedule_provider::{OnCallUser, ScheduleProvider, ScheduleProviderConfig}, slack::Slack}};
 
+// Stop re-running the due tasks after a few rounds, in case the schedule keeps them due
+const MAX_UPDATE_ROUNDS: usize = 5;
+const FALLBACK_TRIGGER_SECONDS: i64 = 24 * 60 * 60;
+
+/**
+ * Find the Slack user of an on-call user, or None when the user is skipped by the missing user policy
+ */
+async fn find_slack_user_id(slack: &Slack, user: &OnCallUser, missing_user_policy: MissingUserPolicy, aliases: &HashMap<String, String>) -> Result<Option<String>, AppError> {
+    if let Some(slack_user_id) = &user.slack_user_id {
+        return Ok(Some(slack_user_id.clone()));
+    }
+
+    if let Some(slack_user) = slack.get_user_by_email(&user.email).await? {
+        return Ok(Some(slack_user.id));
+    }
+
+    match missing_user_policy {
+        MissingUserPolicy::Skip => Ok(None),
+        MissingUserPolicy::Fail => Err(AppError::SlackUserNotFoundError(user.email.clone())),
+        MissingUserPolicy::Alias => Ok(aliases.get(&EmailAlias::normalize_email(&user.email)).cloned()),
+    }
+}
+
+/**
+ * The members of a user group before and after an update, compared regardless of the order
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserGroupMembers {
+    pub previous_user_ids: BTreeSet<String>,
+    pub user_ids: BTreeSet<String>,
+
+    // The handoff message posted to the channel
+    pub message_ts: Option<String>,
+}
+
+impl UserGroupMembers {
+    pub fn is_changed(&self) -> bool {
+        self.previous_user_ids != self.user_ids
+    }
+
+    pub fn incoming_user_ids(&self) -> Vec<String> {
+        self.user_ids.difference(&self.previous_user_ids).cloned().collect()
+    }
+
+    pub fn outgoing_user_ids(&self) -> Vec<String> {
+        self.previous_user_ids.difference(&self.user_ids).cloned().collect()
+    }
+}
+
+/**
+ * Update the user group of the task to the users on call, and post the changes to the channel of the task
+ */
 pub async fn update_user_group(
-    http_client: Arc<Box<Client>>, 
-    pager_duty_api_key: &str,
-    pager_duty_schedule_id: &str,
-    pager_duty_schedule_from: DateTime<Utc>,
+    http_client: Arc<Client>, 
+    schedule_provider: &dyn ScheduleProvider,
+    overrides: &[ScheduleOverride],
+    aliases: &HashMap<String, String>,
+    on_call_at: DateTime<Utc>,
     slack_api_key: &str,
-    slack_channel_id: &str,
-    slack_user_group_name: &str,
-) -> Result<(), AppError>{
+    task: &ScheduledTask,
+) -> Result<UserGroupMembers, AppError>{
     println!("Getting the current on-call users");
 
-    let from = pager_duty_schedule_from;
-    let until = from + Duration::minutes(10);
     // let now = Utc.with_ymd_and_hms(2023, 5, 18, 23, 0, 0).unwrap();
 
-    let pager_duty = PagerDuty::new(http_client.clone(), pager_duty_api_key.to_string(), pager_duty_schedule_id.to_string());
-    let oncall_users = pager_duty.get_on_call_users(from).await?;
-    println!("Found {} users on call from {} to {}",  oncall_users.len(), from, until);
+    let oncall_users = schedule_provider.get_on_call_users(on_call_at).await?;
+    println!("Found {} users on call at {}",  oncall_users.len(), on_call_at);
     
     for user in &oncall_users {
         println!("  - User: {}, {}", user.name, user.email);
@@ -33,137 +83,644 @@ pub async fn update_user_group(
 
     let slack = Slack::new(http_client.clone(), slack_api_key.to_string());
 
-    let user_group = slack.get_user_group(&slack_user_group_name).await?;
+    let user_group = slack.get_user_group(&task.user_group_handle).await?;
     println!("Found user group: {:?}", user_group);
 
-    let slack_user_ids: Vec<String> = futures::stream::iter(&oncall_users).then(|user| async {
-        let slack_user = slack.get_user_by_email(&user.email).await
-            .expect(format!("Couldn't find user in Slack by email: {:?}", user.email).as_str());
-        slack_user.expect(format!("Couldn't find user in Slack by email: {:?}", user.email).as_str()).id
-    }).collect().await;
+    let mut scheduled_user_ids: Vec<String> = vec![];
+    let mut missing_users: Vec<&OnCallUser> = vec![];
+    for user in &oncall_users {
+        match find_slack_user_id(&slack, user, task.missing_user_policy, aliases).await? {
+            // The same user may be on call in more than one layer of the schedule
+            Some(slack_user_id) if scheduled_user_ids.contains(&slack_user_id) => {},
+            Some(slack_user_id) => scheduled_user_ids.push(slack_user_id),
+            None => missing_users.push(user),
+        }
+    }
+
+    if !missing_users.is_empty() {
+        let names = missing_users.iter().map(|user| format!("{} ({})", user.name, user.email)).collect::<Vec<String>>().join(", ");
+        println!("Skipped the users not found in Slack: {}", names);
+        slack.send_message(&task.channel_id, &format!(
+            "Couldn't find {} in Slack, updating <!subteam^{}> without them. Map their emails with `/on-call-support alias add <email> @user` and `--missing-user alias`",
+            names, &user_group.id,
+        )).await?;
+    }
     
+    let assignments = apply_overrides(&scheduled_user_ids, overrides, &on_call_at);
+    let user_ids: BTreeSet<String> = assignments.iter().map(|a| a.slack_user_id.clone()).collect();
+
     let current_users = slack.get_user_group_users(&user_group.id).await?;
+    let previous_user_ids: BTreeSet<String> = current_users.iter().cloned().collect();
+    // The names are only logged, the deactivated users are shown by their ids
     let current_user_names: Vec<String> = futures::stream::iter(&current_users).then(|user_id| async {
-        let id = user_id.clone();
-        let slack_user = slack.get_user_by_id(&id).await
-            .expect(format!("Couldn't find user in Slack by id: {:?}", id).as_str());
-        slack_user.expect(format!("Couldn't find user in Slack by id: {:?}", id).as_str()).name
+        match slack.get_user_by_id(user_id).await {
+            Ok(Some(slack_user)) => slack_user.name,
+            _ => user_id.clone(),
+        }
     }).collect().await;
-    
-    if current_users.len() > slack_user_ids.len() + 2 {
-        // send message to channel with message: failed to update user group due to too many users 
-        // return Err(AppError::SlackUpdateUserGroupError("Too many users in the current group, is the group correct?".to_string()));
-    }
 
     println!("Current user ids in group: {:?}", current_users);
     println!("Current user names in group: {:?}", current_user_names);
 
+    let mut members = UserGroupMembers { previous_user_ids, user_ids, message_ts: None };
+    if !members.is_changed() {
+        println!("No changes to user group {}, skipped updating", user_group.id);
+        return Ok(members);
+    }
+
+    let slack_user_ids: Vec<String> = members.user_ids.iter().cloned().collect();
+    if let Some(reason) = task.guard_rails.refusal_reason(&current_users, &slack_user_ids) {
+        println!("Refused to update user group {}: {}", user_group.id, reason);
+        slack.send_message(&task.channel_id, &format!("Didn't update <!subteam^{}>, {}. Check the schedule or change the limits with `/on-call-support edit {}`", &user_group.id, reason, task.task_id)).await?;
+        return Err(AppError::GuardRailError(reason));
+    }
+
     println!("Update users to group: {:?}", slack_user_ids);
-    
-    println!("Does users changed: {:?}", slack_user_ids != current_users);
     slack.update_user_group_users(&user_group.id, &slack_user_ids).await?;
-    
-    if slack_user_ids != current_users {
-        println!("Send message to channel");
-        let slack_users = slack_user_ids.iter().map(|id| format!("<@{}>", id)).collect::<Vec<String>>().join(", ");
-        slack.send_message(&slack_channel_id, &format!("Updated support user group <!subteam^{}> to: {}", &user_group.id, slack_users)).await?;
+
+    let next_schedule = task.calculate_next_schedule(&on_call_at);
+    let handoff = Handoff {
+        task_id: task.task_id.clone(),
+        user_group_id: user_group.id.clone(),
+        schedule: task.provider_config.to_string(),
+        on_call: assignments.iter().map(|a| a.to_slack_message()).collect(),
+        incoming_user_ids: members.incoming_user_ids(),
+        outgoing_user_ids: members.outgoing_user_ids(),
+        next_handoff_at: next_schedule.map(|schedule| schedule.next_datetime),
+    };
+
+    println!("Send message to channel");
+    let thread_ts = match task.notification.post_mode {
+        PostMode::Thread => task.notification_thread_ts.as_deref(),
+        PostMode::NewMessage => None,
+    };
+    let text = task.notification.render_text(&handoff);
+    let blocks = task.notification.render_blocks(&handoff);
+    members.message_ts = Some(slack.post_message(&task.channel_id, &text, blocks.as_ref(), thread_ts).await?);
+
+    // The group is already updated, so a failed topic update or direct message doesn't fail the task
+    if task.notification.topic_template.is_some() {
+        if let Err(err) = update_channel_topic(&slack, task, &handoff).await {
+            println!("Failed to update topic of channel {}, error: {:?}", task.channel_id, err);
+        }
+    }
+
+    if task.notification.notify_incoming {
+        for user_id in &handoff.incoming_user_ids {
+            if let Err(err) = slack.send_message(user_id, &handoff.direct_message()).await {
+                println!("Failed to send direct message to {}, error: {:?}", user_id, err);
+            }
+        }
+    }
+
+    Ok(members)
+}
+
+/**
+ * Send a direct message to the users joining the user group in the next shift, before the update of the user group.
+ * Return the reminded users, the users already in the group aren't reminded.
+ */
+pub async fn remind_incoming_users(
+    http_client: Arc<Client>,
+    schedule_provider: &dyn ScheduleProvider,
+    overrides: &[ScheduleOverride],
+    aliases: &HashMap<String, String>,
+    reminder_at: DateTime<Utc>,
+    slack_api_key: &str,
+    task: &ScheduledTask,
+) -> Result<Vec<String>, AppError> {
+    let remind_before_hours = task.notification.remind_before_hours.unwrap_or_default();
+    let next_update_at = reminder_at + Duration::hours(remind_before_hours as i64);
+    if next_update_at <= Utc::now() {
+        println!("Skipped the reminder of task {}, the update at {} has passed", task.task_id, next_update_at);
+        return Ok(vec![]);
+    }
+
+    let Some(shift) = schedule_provider.get_next_shift(reminder_at, next_update_at).await? else {
+        println!("No shift starts between {} and {}", reminder_at, next_update_at);
+        return Ok(vec![]);
+    };
+
+    let slack = Slack::new(http_client, slack_api_key.to_string());
+    let mut scheduled_user_ids: Vec<String> = vec![];
+    for user in &shift.users {
+        // The missing users are reported when updating the user group
+        match find_slack_user_id(&slack, user, task.missing_user_policy, aliases).await {
+            Ok(Some(slack_user_id)) if !scheduled_user_ids.contains(&slack_user_id) => scheduled_user_ids.push(slack_user_id),
+            Ok(_) => {},
+            Err(err) => println!("Couldn't find {} in Slack, skipped the reminder, error: {:?}", user, err),
+        }
+    }
+
+    let incoming_user_ids: BTreeSet<String> = apply_overrides(&scheduled_user_ids, overrides, &shift.start).into_iter()
+        .map(|assignment| assignment.slack_user_id)
+        .filter(|user_id| !task.member_ids.contains(user_id))
+        .collect();
+
+    let schedule = shift.schedule_name.unwrap_or(task.provider_config.to_string());
+    let message = reminder_message(&task.user_group_id, &schedule, &shift.start.with_timezone(&get_timezone(&task.timezone)));
+
+    let mut reminded_user_ids = vec![];
+    for user_id in incoming_user_ids {
+        match slack.send_message(&user_id, &message).await {
+            Ok(()) => reminded_user_ids.push(user_id),
+            Err(err) => println!("Failed to send reminder to {}, error: {:?}", user_id, err),
+        }
+    }
+
+    println!("Reminded {:?} of the shift starting at {}", reminded_user_ids, shift.start);
+    Ok(reminded_user_ids)
+}
+
+async fn update_channel_topic(slack: &Slack, task: &ScheduledTask, handoff: &Handoff) -> Result<(), AppError> {
+    let channel = slack.get_channel(&task.channel_id).await?;
+    let current_topic = channel.and_then(|channel| channel.topic).map(|topic| topic.value).unwrap_or_default();
+
+    match task.notification.render_topic(&current_topic, handoff) {
+        Some(topic) => {
+            println!("Update topic of channel {}", task.channel_id);
+            slack.update_channel_topic(&task.channel_id, &topic).await?;
+        },
+        None => println!("Topic of channel {} is up to date", task.channel_id),
     }
 
     Ok(())
 }
 
-async fn build_encryptor(aws_config: &SdkConfig, secret_name: &str) -> Result<Encryptor, AppError> {
-    let secrets_client = SecretsClient::new(&aws_config);
-    let encryption_key = secrets_client.get_secret(secret_name).await?;
-    
-    Ok(Encryptor::new(&encryption_key.encryption_key))
+async fn build_encryptor(aws_config: &SdkConfig, secrets_source: &SecretsSource) -> Result<Encryptor, AppError> {
+    let secrets = load_secrets(aws_config, secrets_source).await?;
+
+    Ok(Encryptor::new(&secrets.encryption_key))
 }
 
-async fn run_task(task: &ScheduledTask, slack_tokens: &HashMap<String, SlackInstallation>, http_client: Arc<Box<Client>>, scheduled_tasks_db: &ScheduledTasksDynamodb) -> Result<(), AppError>{
-    println!("Updating user group for task {}, scheduled at: {}", task.task_id, task.cron);
+async fn build_schedule_provider(task: &ScheduledTask, slack_installation: &SlackInstallation, http_client: Arc<Client>, rotations_db: &dyn RotationsRepository) -> Result<Box<dyn ScheduleProvider>, AppError> {
+    match &task.provider_config {
+        ScheduleProviderConfig::PagerDuty { schedule_id, api_token } => {
+            let pagerduty_token = api_token.clone()
+                .or(slack_installation.pager_duty_token.clone())
+                .ok_or(AppError::PagerDutyError("No PagerDuty token setup for the current Slack installation".to_string()))?;
+
+            Ok(Box::new(PagerDuty::new(http_client, pagerduty_token, schedule_id.clone())))
+        },
+        ScheduleProviderConfig::Opsgenie { schedule_id, api_key, region } => {
+            let opsgenie_token = api_key.clone()
+                .or(slack_installation.opsgenie_token.clone())
+                .ok_or(AppError::OpsgenieError("No Opsgenie api key setup for the current Slack installation".to_string()))?;
+            let region = region.or(slack_installation.opsgenie_region).unwrap_or_default();
+
+            Ok(Box::new(Opsgenie::new(http_client, opsgenie_token, region, schedule_id.clone())))
+        },
+        ScheduleProviderConfig::Ical { url, calendar } => {
+            let source = match (url, calendar) {
+                (Some(url), _) => IcalSource::Url(url.clone()),
+                (None, Some(calendar)) => IcalSource::Inline(calendar.clone()),
+                (None, None) => return Err(AppError::IcalError(format!("No iCalendar url or content for task {}", task.task_id))),
+            };
 
+            Ok(Box::new(Ical::new(http_client, source, get_timezone(&task.timezone))))
+        },
+        ScheduleProviderConfig::Rotation { name } => {
+            let rotation = rotations_db.get_rotation(&task.team, name).await?
+                .ok_or(AppError::RotationError(format!("Rotation {} not found for task {}", name, task.task_id)))?;
+
+            Ok(Box::new(rotation))
+        },
+    }
+}
+
+async fn run_task(task: &ScheduledTask, slack_tokens: &HashMap<String, SlackInstallation>, http_client: Arc<Client>, repositories: &Repositories) -> Result<(), AppError>{
+    println!("Running the {} trigger of task {}, scheduled at: {}", task.next_trigger, task.task_id, task.cron);
+
+    // An unreadable installation is skipped when listing, so the tasks of the workspace fail here without stopping the others
     let slack_installation = slack_tokens.get(&task.team_id)
-        .expect(format!("Could not find slack installation for team: {}, task: {}", task.team, task.task_id).as_str());
+        .ok_or(AppError::SlackError(format!("Could not find a readable slack installation for team: {}, task: {}", task.team, task.task_id)))?;
+
+    let schedule_provider = build_schedule_provider(task, slack_installation, http_client.clone(), repositories.rotations.as_ref()).await?;
+
+    let overrides = repositories.overrides.list_overrides(&task.team, &task.task_id).await?;
+
+    // Keyed by the email in lower case
+    let aliases: HashMap<String, String> = match task.missing_user_policy {
+        MissingUserPolicy::Alias => repositories.aliases.list_aliases(&task.team).await?
+            .into_iter()
+            .map(|alias| (alias.email, alias.slack_user_id))
+            .collect(),
+        _ => HashMap::new(),
+    };
+
+    if task.next_trigger == TaskTrigger::Reminder {
+        let reminder_at = DateTime::from_timestamp(task.next_update_timestamp_utc, 0).unwrap_or(Utc::now());
+        let reminded = remind_incoming_users(
+            http_client.clone(),
+            schedule_provider.as_ref(),
+            &overrides,
+            &aliases,
+            reminder_at,
+            &slack_installation.access_token,
+            task,
+        ).await;
+
+        // A failed reminder doesn't hold the update of the user group
+        if let Err(err) = reminded {
+            println!("Failed to send reminders of task {}, error: {:?}", task.task_id, err);
+        }
 
-    let pagerduty_token = &task.pager_duty_token.clone()
-        .or(slack_installation.pager_duty_token.clone())
-        .expect("No PagerDuty token setup for the current Slack installation");
+        // From the due time, so the update after the reminder isn't skipped when the reminder runs late
+        let mut updated_task = task.clone();
+        updated_task.last_updated_at = Utc::now().to_rfc3339();
+        updated_task.set_next_schedule_from(&reminder_at);
 
-    update_user_group(
+        return repositories.scheduled_tasks.update_next_schedule(&updated_task).await;
+    }
+
+    let members = update_user_group(
         http_client.clone(),
-        &pagerduty_token,
-        &task.pager_duty_schedule_id,
+        schedule_provider.as_ref(),
+        &overrides,
+        &aliases,
         Utc::now(),
         &slack_installation.access_token,
-        &task.channel_id,
-        &task.user_group_handle,
+        task,
     ).await?;
 
     let mut updated_task = task.clone();
     updated_task.last_updated_at = Utc::now().to_rfc3339();
-
-    if let Some(task_next_schedule) = updated_task.calculate_next_schedule(&Utc::now()) {
-        updated_task.next_update_timestamp_utc = task_next_schedule.next_timestamp_utc;
-        updated_task.next_update_time = task_next_schedule.next_datetime.to_rfc3339();
-    } else {
-        updated_task.next_update_timestamp_utc = -1;
-        updated_task.next_update_time = "".to_string();
+    updated_task.record_members(&members.previous_user_ids, &members.user_ids, &Utc::now());
+    if task.notification.post_mode == PostMode::Thread && task.notification_thread_ts.is_none() {
+        updated_task.notification_thread_ts = members.message_ts;
     }
+    updated_task.failed_runs = 0;
+    updated_task.record_missed_runs(&Utc::now());
+    updated_task.set_next_schedule_from(&Utc::now());
 
-    scheduled_tasks_db.update_next_schedule(&updated_task).await?;
+    repositories.scheduled_tasks.update_next_schedule(&updated_task).await?;
     
     Ok(())
 }
 
+/**
+ * Move the next schedule of a due but paused task forward, so it's not due again on every run
+ */
+async fn skip_paused_task(task: &ScheduledTask, scheduled_tasks_db: &dyn ScheduledTasksRepository) -> Result<(), AppError> {
+    let mut updated_task = task.clone();
+    updated_task.last_updated_at = Utc::now().to_rfc3339();
+    updated_task.set_next_schedule_from(&Utc::now());
+
+    scheduled_tasks_db.update_next_schedule(&updated_task).await
+}
+
+/**
+ * Record the failure of a claimed task and move its next schedule forward, which also releases the claim,
+ * so the failed task isn't retried on every run until the claim expires
+ */
+async fn skip_failed_task(task: &ScheduledTask, err: &AppError, scheduled_tasks_db: &dyn ScheduledTasksRepository) -> Result<(), AppError> {
+    let mut updated_task = task.clone();
+    updated_task.last_updated_at = Utc::now().to_rfc3339();
+    updated_task.record_failure(err, &Utc::now());
+    updated_task.record_missed_runs(&Utc::now());
+    updated_task.set_next_schedule_from(&Utc::now());
+
+    scheduled_tasks_db.update_next_schedule(&updated_task).await
+}
+
+async fn resume_task(task: &mut ScheduledTask, scheduled_tasks_db: &dyn ScheduledTasksRepository) -> Result<(), AppError> {
+    println!("Resuming task {} paused until {:?}", task.task_id, task.resume_time);
+    task.paused = false;
+    task.resume_timestamp_utc = None;
+    task.resume_time = None;
+    task.last_updated_at = Utc::now().to_rfc3339();
+
+    scheduled_tasks_db.update_paused(task).await
+}
+
 pub async fn update_user_groups(env: &str) -> Result<(), AppError> {
-    let lambda_arn = env::var("UPDATE_USER_GROUP_LAMBDA")?;
-    let lambda_role = env::var("UPDATE_USER_GROUP_LAMBDA_ROLE")?;
-    let config = Config::new(env);
-    let aws_config = ::aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let http_client = Arc::new(Box::new(build_http_client()?));
-    let scheduler = EventBridgeScheduler::new(&aws_config, config.schedule_name_prefix, lambda_arn, lambda_role);
-    let encryptor = build_encryptor(&aws_config, &config.secret_name).await?;
-
-    let slack_installations_db = SlackInstallationsDynamoDb::new(&aws_config, config.installations_table_name, encryptor.clone());
-    let scheduled_tasks_db = ScheduledTasksDynamodb::new(&aws_config, config.schedules_table_name, encryptor.clone());
-    
-    let slack_tokens: HashMap<String, SlackInstallation> = slack_installations_db.list_installations().await?
+    let config = Config::new(env)?;
+    let aws_config = config.load_aws_config().await;
+    let http_client = Arc::new(build_http_client()?);
+    let scheduler = build_scheduler(&aws_config, &config)?;
+    let encryptor = build_encryptor(&aws_config, &config.secrets_source).await?;
+    let repositories = Repositories::from_config(&aws_config, &config, encryptor).await?;
+
+    update_and_schedule_next(&repositories, http_client, scheduler.as_ref(), config.max_concurrent_tasks).await
+}
+
+/**
+ * Keep updating the user groups in the standalone server, whenever the in-process scheduler is due.
+ * The secrets are loaded by the server, from the source in its config.
+ */
+pub async fn run_in_process_scheduler(config: Config, secrets: Secrets) -> Result<(), AppError> {
+    let aws_config = config.load_aws_config().await;
+    let http_client = Arc::new(build_http_client()?);
+    let scheduler = InProcessScheduler::shared();
+    let encryptor = Encryptor::new(&secrets.encryption_key);
+    let repositories = Repositories::from_config(&aws_config, &config, encryptor).await?;
+
+    loop {
+        scheduler.wait_until_due().await;
+
+        if let Err(err) = update_and_schedule_next(&repositories, http_client.clone(), scheduler.as_ref(), config.max_concurrent_tasks).await {
+            println!("Failed to update user groups: {:?}", err);
+        }
+    }
+}
+
+/**
+ * Run the due tasks and arm the scheduler for the task scheduled next
+ */
+async fn update_and_schedule_next(repositories: &Repositories, http_client: Arc<Client>, scheduler: &dyn Scheduler, max_concurrent_tasks: usize) -> Result<(), AppError> {
+    let mut next_schedule = None;
+    for round in 1..=MAX_UPDATE_ROUNDS {
+        let start_of_the_update = Utc::now();
+        let summary = update_due_user_groups(repositories, http_client.clone(), start_of_the_update, max_concurrent_tasks).await?;
+        next_schedule = summary.next_task.and_then(|next| next.calculate_next_trigger(&start_of_the_update)).map(|(_, schedule)| schedule);
+
+        // The next task became due while updating the user groups
+        match &next_schedule {
+            Some(schedule) if schedule.next_timestamp_utc <= Utc::now().timestamp() => {
+                println!("Task scheduled at {} is already due after round {}, updating again", schedule.next_datetime, round);
+            },
+            _ => break,
+        }
+    }
+
+    // Re-run at least daily, in case a trigger is lost
+    let fallback_schedule = one_off_schedule(&(Utc::now() + Duration::seconds(FALLBACK_TRIGGER_SECONDS)).with_timezone(&chrono_tz::UTC));
+    let next_schedule = match next_schedule {
+        Some(schedule) if schedule.next_timestamp_utc < fallback_schedule.next_timestamp_utc => schedule,
+        _ => fallback_schedule,
+    };
+    scheduler.update_next_schedule(&next_schedule).await?;
+
+    println!("Finished updating user groups");
+
+    Ok(())
+}
+
+/**
+ * The outcome of the due tasks in an update, logged at the end of the run
+ */
+#[derive(Debug, Default)]
+pub struct UpdateSummary {
+    pub updated_task_ids: Vec<String>,
+    pub skipped_task_ids: Vec<String>,
+    pub failed_tasks: Vec<(String, AppError)>,
+
+    // The task scheduled next to arm the scheduler
+    pub next_task: Option<ScheduledTask>,
+}
+
+impl UpdateSummary {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "updated": self.updated_task_ids,
+            "skipped": self.skipped_task_ids,
+            "failed": self.failed_tasks.iter().map(|(task_id, err)| json!({ "task_id": task_id, "error": err.to_string() })).collect::<Vec<Value>>(),
+            "next_task": self.next_task.as_ref().map(|t| json!({ "task_id": t.task_id, "next_update_time": t.next_update_time })),
+        })
+    }
+}
+
+enum TaskOutcome {
+    Updated(String),
+    Skipped(String),
+    Failed(String, Box<AppError>),
+}
+
+/**
+ * Claim and run a due task, the failures are kept to the task so the other tasks still run
+ */
+async fn claim_and_run_task(mut task: ScheduledTask, slack_tokens: Arc<HashMap<String, SlackInstallation>>, http_client: Arc<Client>, repositories: Repositories) -> TaskOutcome {
+    // Another updater may be running the same task, e.g. a manual run overlapping the scheduled one
+    match repositories.scheduled_tasks.claim_scheduled_task(&mut task, &Utc::now()).await {
+        Ok(true) => match run_task(&task, &slack_tokens, http_client, &repositories).await {
+            Ok(()) => TaskOutcome::Updated(task.task_id),
+            Err(err) => {
+                println!("Failed to update user group for task: {}, error: {}", task.task_id, err);
+                if let Err(skip_err) = skip_failed_task(&task, &err, repositories.scheduled_tasks.as_ref()).await {
+                    println!("Failed to move the next schedule of failed task: {}, error: {}", task.task_id, skip_err);
+                }
+                TaskOutcome::Failed(task.task_id, Box::new(err))
+            },
+        },
+        Ok(false) => {
+            println!("Skipped {}, which is changed or claimed by another updater", task.task_id);
+            TaskOutcome::Skipped(task.task_id)
+        },
+        Err(err) => {
+            println!("Failed to claim task: {}, error: {}", task.task_id, err);
+            TaskOutcome::Failed(task.task_id, Box::new(err))
+        },
+    }
+}
+
+/**
+ * Run the tasks due at the start of the update, at most the given number of tasks at a time
+ */
+pub async fn update_due_user_groups(repositories: &Repositories, http_client: Arc<Client>, start_of_the_update: DateTime<Utc>, max_concurrent_tasks: usize) -> Result<UpdateSummary, AppError> {
+    let scheduled_tasks_db = repositories.scheduled_tasks.as_ref();
+
+    let slack_tokens: Arc<HashMap<String, SlackInstallation>> = Arc::new(repositories.installations.list_installations().await?
         .into_iter()
         .map(|i| (i.team_id.clone(), i))
-        .collect();
+        .collect());
 
-    let tasks = scheduled_tasks_db.list_scheduled_tasks().await?;
-    println!("Found {} tasks", tasks.len());
+    let tasks = scheduled_tasks_db.list_due_scheduled_tasks(&start_of_the_update).await?;
+    println!("Found {} due tasks", tasks.len());
 
+    let mut summary = UpdateSummary::default();
     let mut timestamp_of_next_trigger = i64::MAX;
-    let mut next_task = None;
-    let start_of_the_update = Utc::now();
-    for task in tasks {
-        if task.next_update_timestamp_utc > 0 && task.next_update_timestamp_utc <= Utc::now().timestamp() {
-            let task_result = run_task(&task, &slack_tokens, http_client.clone(), &scheduled_tasks_db).await;
-            if let Err(err) = task_result {
-                println!("Failed to update user group for task: {}, error: {}", task.task_id, err);
+    let mut due_tasks = vec![];
+    for mut task in tasks {
+        let is_due = task.next_update_timestamp_utc > 0 && task.next_update_timestamp_utc <= Utc::now().timestamp();
+
+        if task.is_paused_at(&Utc::now()) {
+            println!("Skipped paused task {}, resume at: {:?}", task.task_id, task.resume_time);
+            if is_due {
+                summary.skipped_task_ids.push(task.task_id.clone());
+                if let Err(err) = skip_paused_task(&task, scheduled_tasks_db).await {
+                    println!("Failed to update next schedule of paused task: {}, error: {}", task.task_id, err);
+                }
+            }
+
+            // The resume command arms the scheduler again, only the tasks with a resume time need to be triggered
+            if task.resume_timestamp_utc.is_none() {
+                continue;
             }
         } else {
-            println!("Skipped {}, next trigger is: {} which is: {} greater than {}", task.task_id, task.next_update_time, task.next_update_timestamp_utc, Utc::now().timestamp());
+            // The resume time has passed
+            if task.paused {
+                if let Err(err) = resume_task(&mut task, scheduled_tasks_db).await {
+                    println!("Failed to resume task: {}, error: {}", task.task_id, err);
+                }
+            }
+
+            if is_due {
+                due_tasks.push(task.clone());
+            } else {
+                println!("Skipped {}, next trigger is: {} which is: {} greater than {}", task.task_id, task.next_update_time, task.next_update_timestamp_utc, Utc::now().timestamp());
+            }
         }
 
-        if task.next_update_timestamp_utc > 0 && task.next_update_timestamp_utc < timestamp_of_next_trigger {
-            timestamp_of_next_trigger = task.next_update_timestamp_utc;
-            next_task = Some(task.clone());
+        if let Some((_, next_schedule)) = task.calculate_next_trigger(&start_of_the_update) {
+            if next_schedule.next_timestamp_utc < timestamp_of_next_trigger {
+                timestamp_of_next_trigger = next_schedule.next_timestamp_utc;
+                summary.next_task = Some(task.clone());
+            }
         }
     }
 
-    // at least re-run daily
-    // (Utc::now() + Duration::days(1)).timestamp()
-    if let Some(next) = next_task {
-        if let Some(next_schedule) = next.calculate_next_schedule(&start_of_the_update) {
-            //TODO: if next schedule is earlier than now, re-run the above loop
-            scheduler.update_next_schedule(&next_schedule).await?;
+    // Each task runs in its own tokio task, so even a panic only fails the task
+    let outcomes: Vec<TaskOutcome> = futures::stream::iter(due_tasks)
+        .map(|task| {
+            let task_id = task.task_id.clone();
+            let handle = tokio::spawn(claim_and_run_task(task, slack_tokens.clone(), http_client.clone(), repositories.clone()));
+            async move {
+                handle.await.unwrap_or_else(|err| TaskOutcome::Failed(task_id, Box::new(AppError::UnexpectedError(format!("Task panicked: {}", err)))))
+            }
+        })
+        .buffer_unordered(max_concurrent_tasks.max(1))
+        .collect()
+        .await;
+
+    for outcome in outcomes {
+        match outcome {
+            TaskOutcome::Updated(task_id) => summary.updated_task_ids.push(task_id),
+            TaskOutcome::Skipped(task_id) => summary.skipped_task_ids.push(task_id),
+            TaskOutcome::Failed(task_id, err) => summary.failed_tasks.push((task_id, *err)),
         }
     }
 
-    println!("Finished updating user groups");
+    // The index is eventually consistent, so the next schedules of the tasks updated above are checked as well
+    if let Some(task) = scheduled_tasks_db.get_next_scheduled_task(&start_of_the_update).await? {
+        if task.next_update_timestamp_utc < timestamp_of_next_trigger {
+            summary.next_task = Some(task);
+        }
+    }
 
-    Ok(())
+    println!("Update summary: {}", summary.to_json());
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::{build_http_client, db::{sql_client, Repositories, SlackInstallation, SlackInstallationsRepository, SlackInstallationsSql}, encryptor::Encryptor, scheduled_tasks::ScheduledTask};
+    use crate::errors::AppError;
+    use crate::user_group_updater::update_due_user_groups;
+
+    fn task(task_id: &str, next_update_timestamp_utc: i64, paused: bool) -> ScheduledTask {
+        ScheduledTask { task_id: task_id.to_string(), next_update_timestamp_utc, paused, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn skip_paused_tasks_with_in_memory_storage() {
+        let repositories = Repositories::in_memory();
+        let now = Utc::now();
+        let paused_task = task("ocs-0001", now.timestamp() - 60, true);
+        let future_task = task("ocs-0002", now.timestamp() + 3600, false);
+        repositories.scheduled_tasks.save_scheduled_task(&paused_task).await.unwrap();
+        repositories.scheduled_tasks.save_scheduled_task(&future_task).await.unwrap();
+
+        let summary = update_due_user_groups(&repositories, Arc::new(build_http_client().unwrap()), now, 4).await.unwrap();
+
+        // The paused task is moved to its next schedule, but the scheduler is only armed for the active task
+        let tasks = repositories.scheduled_tasks.list_scheduled_tasks_in_workspace("T123", "E123").await.unwrap();
+        assert!(tasks.iter().all(|t| t.next_update_timestamp_utc > now.timestamp()));
+        assert_eq!(summary.next_task.map(|t| t.task_id), Some("ocs-0002".to_string()));
+        assert_eq!(summary.skipped_task_ids, vec!["ocs-0001".to_string()]);
+
+        let due_at = Utc.timestamp_opt(now.timestamp() + 3600, 0).unwrap();
+        assert_eq!(repositories.scheduled_tasks.list_due_scheduled_tasks(&due_at).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn summarise_failed_tasks() {
+        let repositories = Repositories::in_memory();
+        let now = Utc::now();
+        for task_id in ["ocs-0001", "ocs-0002", "ocs-0003"] {
+            repositories.scheduled_tasks.save_scheduled_task(&task(task_id, now.timestamp() - 60, false)).await.unwrap();
+        }
+
+        // Without the Slack installation, every task fails on its own instead of stopping the update
+        let summary = update_due_user_groups(&repositories, Arc::new(build_http_client().unwrap()), now, 2).await.unwrap();
+
+        let mut failed_task_ids: Vec<String> = summary.failed_tasks.iter().map(|(task_id, _)| task_id.clone()).collect();
+        failed_task_ids.sort();
+        assert_eq!(failed_task_ids, vec!["ocs-0001", "ocs-0002", "ocs-0003"]);
+        assert!(summary.failed_tasks.iter().all(|(_, err)| matches!(err, AppError::SlackError(_))));
+        assert!(summary.updated_task_ids.is_empty());
+        assert_eq!(summary.to_json()["failed"].as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn move_failed_tasks_to_next_schedule() {
+        let repositories = Repositories::in_memory();
+        let now = Utc::now();
+        repositories.installations.save_slack_installation(&SlackInstallation::default()).await.unwrap();
+        // The rotation of the task doesn't exist, so building the schedule provider fails
+        repositories.scheduled_tasks.save_scheduled_task(&task("ocs-0001", now.timestamp() - 60, false)).await.unwrap();
+
+        let summary = update_due_user_groups(&repositories, Arc::new(build_http_client().unwrap()), now, 4).await.unwrap();
+        assert!(matches!(summary.failed_tasks.as_slice(), [(task_id, err)] if task_id == "ocs-0001" && matches!(err, AppError::RotationError(_))));
+
+        // The failure is recorded, and the task isn't due again until its next schedule
+        let tasks = repositories.scheduled_tasks.list_scheduled_tasks_in_workspace("T123", "E123").await.unwrap();
+        assert_eq!(tasks[0].failed_runs, 1);
+        assert!(tasks[0].last_error.as_ref().is_some_and(|err| err.contains("Rotation support not found")));
+        assert!(!tasks[0].is_claimed_at(&now));
+        assert!(tasks[0].next_update_timestamp_utc > now.timestamp());
+
+        let summary = update_due_user_groups(&repositories, Arc::new(build_http_client().unwrap()), now, 4).await.unwrap();
+        assert!(summary.failed_tasks.is_empty());
+        assert!(repositories.scheduled_tasks.list_due_scheduled_tasks(&now).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn fail_tasks_of_unreadable_installations() {
+        let pool = sql_client::connect("sqlite::memory:").await.unwrap();
+        let repositories = Repositories {
+            installations: Arc::new(SlackInstallationsSql::new(pool.clone(), Encryptor::new("plain text key which should be s"))),
+            ..Repositories::in_memory()
+        };
+        let now = Utc::now();
+
+        // The installation of T123 is encrypted with another key, the one of T456 is readable but the rotation of its task doesn't exist
+        SlackInstallationsSql::new(pool, Encryptor::new("another key which should be s123")).save_slack_installation(&SlackInstallation::default()).await.unwrap();
+        repositories.installations.save_slack_installation(&SlackInstallation { team_id: "T456".to_string(), ..Default::default() }).await.unwrap();
+        repositories.scheduled_tasks.save_scheduled_task(&task("ocs-0001", now.timestamp() - 60, false)).await.unwrap();
+        repositories.scheduled_tasks.save_scheduled_task(&ScheduledTask { team: "T456:E123".to_string(), team_id: "T456".to_string(), ..task("ocs-0002", now.timestamp() - 60, false) }).await.unwrap();
+
+        let summary = update_due_user_groups(&repositories, Arc::new(build_http_client().unwrap()), now, 4).await.unwrap();
+
+        let mut failed_tasks: Vec<(&str, bool)> = summary.failed_tasks.iter()
+            .map(|(task_id, err)| (task_id.as_str(), matches!(err, AppError::SlackError(_))))
+            .collect();
+        failed_tasks.sort();
+        assert_eq!(failed_tasks, vec![("ocs-0001", true), ("ocs-0002", false)]);
+    }
+
+    #[tokio::test]
+    async fn skip_tasks_claimed_by_another_updater() {
+        let repositories = Repositories::in_memory();
+        let now = Utc::now();
+        repositories.scheduled_tasks.save_scheduled_task(&task("ocs-0001", now.timestamp() - 60, false)).await.unwrap();
+        let mut due_task = repositories.scheduled_tasks.list_due_scheduled_tasks(&now).await.unwrap().remove(0);
+        assert!(repositories.scheduled_tasks.claim_scheduled_task(&mut due_task, &now).await.unwrap());
+
+        let summary = update_due_user_groups(&repositories, Arc::new(build_http_client().unwrap()), now, 4).await.unwrap();
+        assert_eq!(summary.skipped_task_ids, vec!["ocs-0001".to_string()]);
+
+        // The task is left to the other updater, which releases the claim after updating the next schedule
+        let tasks = repositories.scheduled_tasks.list_due_scheduled_tasks(&now).await.unwrap();
+        assert_eq!(tasks[0].version, due_task.version);
+        assert!(tasks[0].is_claimed_at(&now));
+
+        due_task.set_next_schedule_from(&now);
+        repositories.scheduled_tasks.update_next_schedule(&due_task).await.unwrap();
+        assert!(repositories.scheduled_tasks.update_next_schedule(&due_task).await.is_err());
+    }
 }